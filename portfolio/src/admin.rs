@@ -1,574 +1,580 @@
-use actix_web::{web, HttpResponse};
+use actix_web::{web, HttpRequest, HttpResponse, ResponseError};
 use actix_session::Session;
 use actix_multipart::Multipart;
+use bcrypt::verify;
 use futures_util::StreamExt;
+use rusqlite::OptionalExtension;
 use crate::AppState;
+use crate::blobs;
 use crate::db;
+use crate::error::AppError;
 use crate::auth;
+use crate::csrf;
+use crate::pagination::{self, PageQuery, Pagination};
+use crate::revisions;
+use crate::routes;
+use crate::two_factor;
+use crate::uploads::{self, UploadError};
 use crate::models::*;
 
-fn require_auth(session: &Session) -> Option<HttpResponse> {
-    if !auth::is_authenticated(session) {
-        Some(HttpResponse::Found()
+// Default page sizes of the admin lists
+const ADMIN_BLOGS_PER_PAGE: i64 = 25;
+const ADMIN_MESSAGES_PER_PAGE: i64 = 20;
+
+// Revisions listed on an edit page, newest first
+const REVISIONS_SHOWN: i64 = 20;
+
+/// Role-aware guard: redirects to the login page when nobody is logged in and
+/// refuses the request when the admin's role is below `role`.
+fn require_auth(session: &Session, role: Role) -> Option<HttpResponse> {
+    match auth::current_role(session) {
+        None => Some(HttpResponse::Found()
             .append_header(("Location", "/admin/login"))
-            .finish())
-    } else {
-        None
+            .finish()),
+        Some(current) if current < role => Some(HttpResponse::from_error(AppError::Forbidden)),
+        Some(_) => None,
     }
 }
 
-pub async fn dashboard(data: web::Data<AppState>, session: Session) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
-    }
-    
-    let conn = data.db.lock().unwrap();
-    
-    let profile = db::get_profile(&conn).unwrap();
-    let projects = db::get_projects(&conn).unwrap_or_default();
-    let blogs = db::get_blogs(&conn).unwrap_or_default();
-    let skills = db::get_skills(&conn).unwrap_or_default();
-    let messages = db::get_messages(&conn).unwrap_or_default();
-    let unread_count = db::get_unread_message_count(&conn).unwrap_or(0);
-    
+/// Template context pre-filled with what every admin page needs.
+fn admin_context(session: &Session) -> tera::Context {
     let mut context = tera::Context::new();
+    context.insert("csrf_token", &csrf::token(session));
+    context.insert("current_username", &auth::current_username(session));
+    context.insert("current_role", &auth::current_role(session));
+    context
+}
+
+pub async fn dashboard(data: web::Data<AppState>, session: Session) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Author) {
+        return Ok(redirect);
+    }
+    
+    let day_ago = (chrono::Utc::now() - chrono::Duration::hours(24)).format("%Y-%m-%d %H:%M:%S").to_string();
+    let (profile, projects, blogs, skills, messages, unread_count, failed_logins, failed_logins_24h) = db::call(&data.db, move |conn| {
+        Ok((
+            db::get_profile(conn)?,
+            db::get_projects(conn)?,
+            db::get_blogs(conn)?,
+            db::get_skills(conn)?,
+            db::get_messages(conn)?,
+            db::get_unread_message_count(conn)?,
+            db::get_failed_login_attempts(conn, 20)?,
+            db::count_failed_logins_since(conn, &day_ago)?,
+        ))
+    }).await?;
+    
+    let mut context = admin_context(&session);
     context.insert("profile", &profile);
     context.insert("projects_count", &projects.len());
     context.insert("blogs_count", &blogs.len());
     context.insert("skills_count", &skills.len());
     context.insert("messages_count", &messages.len());
     context.insert("unread_count", &unread_count);
+    context.insert("failed_logins", &failed_logins);
+    context.insert("failed_logins_24h", &failed_logins_24h);
     context.insert("page_title", "Admin Dashboard");
     
-    let rendered = data.tera.render("admin/dashboard.html", &context).unwrap();
-    HttpResponse::Ok().content_type("text/html").body(rendered)
+    let rendered = data.tera.render("admin/dashboard.html", &context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
 }
 
-pub async fn profile_page(data: web::Data<AppState>, session: Session) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
-    }
-    
-    let conn = data.db.lock().unwrap();
-    let profile = db::get_profile(&conn).unwrap();
-    
-    let mut context = tera::Context::new();
+/// Renders the profile page together with its two-factor section. `extra`
+/// carries per-request values such as status messages or new recovery codes.
+async fn render_profile(
+    data: &web::Data<AppState>,
+    session: &Session,
+    extra: tera::Context,
+) -> Result<HttpResponse, AppError> {
+    let username = auth::current_username(session).unwrap_or_default();
+    let (profile, admin, recovery_codes_left) = db::call(&data.db, move |conn| {
+        let admin = db::get_admin(conn, &username)?;
+        let recovery_codes_left = db::count_recovery_codes_left(conn, admin.id)?;
+        Ok((db::get_profile(conn)?, admin, recovery_codes_left))
+    }).await?;
+    
+    let mut context = admin_context(session);
     context.insert("profile", &profile);
     context.insert("page_title", "Edit Profile");
     context.insert("success", &false);
+    context.insert("two_factor_enabled", &admin.totp_secret.is_some());
+    context.insert("recovery_codes_left", &recovery_codes_left);
+    
+    if admin.totp_secret.is_none() {
+        // Keep offering the same secret until enrollment is confirmed, so reloading doesn't invalidate a scanned code
+        let secret = match session.get::<String>(two_factor::PENDING_SECRET_KEY).unwrap_or(None) {
+            Some(secret) => secret,
+            None => {
+                let secret = two_factor::generate_secret();
+                session.insert(two_factor::PENDING_SECRET_KEY, &secret)?;
+                secret
+            }
+        };
+        context.insert("totp_qr_svg", &two_factor::qr_code_svg(&secret, &admin.username).unwrap_or_default());
+        context.insert("totp_secret", &secret);
+    }
     
-    let rendered = data.tera.render("admin/profile.html", &context).unwrap();
-    HttpResponse::Ok().content_type("text/html").body(rendered)
+    context.extend(extra);
+    
+    let rendered = data.tera.render("admin/profile.html", &context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
 }
 
-pub async fn update_profile(
-    data: web::Data<AppState>,
-    form: web::Form<ProfileForm>,
-    session: Session,
-) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+pub async fn profile_page(data: web::Data<AppState>, session: Session) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Author) {
+        return Ok(redirect);
     }
     
-    let conn = data.db.lock().unwrap();
-    let _ = db::update_profile(&conn, &form);
-    let profile = db::get_profile(&conn).unwrap();
-    
-    let mut context = tera::Context::new();
-    context.insert("profile", &profile);
-    context.insert("page_title", "Edit Profile");
-    context.insert("success", &true);
-    
-    let rendered = data.tera.render("admin/profile.html", &context).unwrap();
-    HttpResponse::Ok().content_type("text/html").body(rendered)
+    render_profile(&data, &session, tera::Context::new()).await
 }
 
-pub async fn skills_page(data: web::Data<AppState>, session: Session) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+pub async fn skills_page(data: web::Data<AppState>, session: Session) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
     }
     
-    let conn = data.db.lock().unwrap();
-    let profile = db::get_profile(&conn).unwrap();
-    let skills = db::get_skills(&conn).unwrap_or_default();
+    let (profile, skills) = db::call(&data.db, |conn| {
+        Ok((db::get_profile(conn)?, db::get_skills(conn)?))
+    }).await?;
     
-    let mut context = tera::Context::new();
+    let mut context = admin_context(&session);
     context.insert("profile", &profile);
     context.insert("skills", &skills);
     context.insert("page_title", "Manage Skills");
     
-    let rendered = data.tera.render("admin/skills.html", &context).unwrap();
-    HttpResponse::Ok().content_type("text/html").body(rendered)
+    let rendered = data.tera.render("admin/skills.html", &context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
 }
 
 pub async fn add_skill(
     data: web::Data<AppState>,
-    mut payload: Multipart,
+    payload: Multipart,
     session: Session,
-) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
-    }
-    
-    let mut skill_form = SkillForm::default();
-    let mut icon_url = String::new();
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
+    }
+    
+    let (fields, mut files) = extract_multipart_with_files(payload, &[("icon_file", uploads::IMAGE_TYPES)]).await?;
+    let icon_file = files.remove("icon_file");
+    let field = |name: &str| fields.get(name).cloned().unwrap_or_default();
+    let mut skill_form = SkillForm {
+        name: field("name"),
+        category: field("category"),
+        proficiency: field("proficiency").parse().unwrap_or(80),
+        icon: field("icon"),
+        ..SkillForm::default()
+    };
     
-    // Process multipart form
-    while let Some(item) = payload.next().await {
-        if let Ok(mut field) = item {
-            // Get field name from content disposition
-            let field_name = field.content_disposition()
-                .and_then(|cd| cd.get_name().map(|s| s.to_string()))
-                .unwrap_or_default();
-            
-            // Check if it's a file field with actual content
-            let filename = field.content_disposition()
-                .and_then(|cd| cd.get_filename().map(|s| s.to_string()))
-                .unwrap_or_default();
-            
-            let is_file_with_content = !filename.is_empty();
-            
-            if field_name == "icon_file" && is_file_with_content {
-                // Handle file upload
-                let content_type = field.content_type()
-                    .map(|ct| ct.to_string())
-                    .unwrap_or_else(|| "image/png".to_string());
-                
-                let mut file_data = Vec::new();
-                while let Some(chunk) = field.next().await {
-                    if let Ok(bytes) = chunk {
-                        file_data.extend_from_slice(&bytes);
-                    }
-                }
-                
-                if !file_data.is_empty() {
-                    // Save to database as image
-                    let conn = data.db.lock().unwrap();
-                    let image_id = uuid::Uuid::new_v4().to_string();
-                    let _ = db::save_image(&conn, &image_id, &filename, &content_type, &file_data);
-                    icon_url = format!("/images/{}", image_id);
-                }
-            } else {
-                // Handle text fields
-                let mut value = String::new();
-                while let Some(chunk) = field.next().await {
-                    if let Ok(bytes) = chunk {
-                        if let Ok(s) = std::str::from_utf8(&bytes) {
-                            value.push_str(s);
-                        }
-                    }
-                }
-                
-                match field_name.as_str() {
-                    "name" => skill_form.name = value,
-                    "category" => skill_form.category = value,
-                    "proficiency" => skill_form.proficiency = value.parse().unwrap_or(80),
-                    "icon" => skill_form.icon = value,
-                    _ => {}
-                }
-            }
-        }
+    // Save uploaded icon as an image
+    if let Some(file) = icon_file {
+        skill_form.icon_url = save_upload(&data, "/images", file).await?;
     }
     
-    // Set icon_url if file was uploaded
-    skill_form.icon_url = icon_url;
+    db::call(&data.db, move |conn| db::add_skill(conn, &skill_form)).await?;
     
-    let conn = data.db.lock().unwrap();
-    let _ = db::add_skill(&conn, &skill_form);
-    
-    HttpResponse::Found()
+    Ok(HttpResponse::Found()
         .append_header(("Location", "/admin/skills"))
-        .finish()
+        .finish())
 }
 
 pub async fn delete_skill(
     data: web::Data<AppState>,
     path: web::Path<i32>,
     session: Session,
-) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
     }
     
-    let conn = data.db.lock().unwrap();
-    let _ = db::delete_skill(&conn, path.into_inner());
+    let id = path.into_inner();
+    db::call(&data.db, move |conn| db::trash_item(conn, TrashKind::Skill, id)).await?;
     
-    HttpResponse::Found()
+    Ok(HttpResponse::Found()
         .append_header(("Location", "/admin/skills"))
-        .finish()
+        .finish())
 }
 
-pub async fn projects_page(data: web::Data<AppState>, session: Session) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+pub async fn projects_page(data: web::Data<AppState>, session: Session) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
     }
     
-    let conn = data.db.lock().unwrap();
-    let profile = db::get_profile(&conn).unwrap();
-    let projects = db::get_projects(&conn).unwrap_or_default();
+    let (profile, projects) = db::call(&data.db, |conn| {
+        Ok((db::get_profile(conn)?, db::get_projects(conn)?))
+    }).await?;
     
-    let mut context = tera::Context::new();
+    let mut context = admin_context(&session);
     context.insert("profile", &profile);
     context.insert("projects", &projects);
     context.insert("page_title", "Manage Projects");
     
-    let rendered = data.tera.render("admin/projects.html", &context).unwrap();
-    HttpResponse::Ok().content_type("text/html").body(rendered)
+    let rendered = data.tera.render("admin/projects.html", &context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
 }
 
-pub async fn add_project_page(data: web::Data<AppState>, session: Session) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+pub async fn add_project_page(data: web::Data<AppState>, session: Session) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
     }
     
-    let conn = data.db.lock().unwrap();
-    let profile = db::get_profile(&conn).unwrap();
+    let profile = db::call(&data.db, |conn| db::get_profile(conn)).await?;
     
-    let mut context = tera::Context::new();
+    let mut context = admin_context(&session);
     context.insert("profile", &profile);
     context.insert("page_title", "Add Project");
     context.insert("editing", &false);
     
-    let rendered = data.tera.render("admin/project_form.html", &context).unwrap();
-    HttpResponse::Ok().content_type("text/html").body(rendered)
-}
-
-pub async fn add_project(
-    data: web::Data<AppState>,
-    form: web::Form<ProjectForm>,
-    session: Session,
-) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
-    }
-    
-    let conn = data.db.lock().unwrap();
-    let _ = db::add_project(&conn, &form);
-    
-    HttpResponse::Found()
-        .append_header(("Location", "/admin/projects"))
-        .finish()
+    let rendered = data.tera.render("admin/project_form.html", &context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
 }
 
 pub async fn edit_project_page(
     data: web::Data<AppState>,
     path: web::Path<i32>,
     session: Session,
-) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
     }
     
-    let conn = data.db.lock().unwrap();
-    let profile = db::get_profile(&conn).unwrap();
-    
-    match db::get_project_by_id(&conn, path.into_inner()) {
-        Ok(project) => {
-            let mut context = tera::Context::new();
+    let id = path.into_inner();
+    let (profile, project, revisions) = db::call(&data.db, move |conn| {
+        Ok((
+            db::get_profile(conn)?,
+            db::get_project_by_id(conn, id).optional()?,
+            db::get_revisions(conn, RevisionKind::Project, id, REVISIONS_SHOWN)?,
+        ))
+    }).await?;
+    
+    match project {
+        Some(project) => {
+            let mut context = admin_context(&session);
             context.insert("profile", &profile);
             context.insert("project", &project);
+            context.insert("revisions", &revisions);
             context.insert("page_title", "Edit Project");
             context.insert("editing", &true);
             
-            let rendered = data.tera.render("admin/project_form.html", &context).unwrap();
-            HttpResponse::Ok().content_type("text/html").body(rendered)
+            let rendered = data.tera.render("admin/project_form.html", &context)?;
+            Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
         }
-        Err(_) => HttpResponse::NotFound().body("Project not found"),
+        None => Err(AppError::NotFound("Project")),
     }
 }
 
-pub async fn update_project(
+pub async fn delete_project(
     data: web::Data<AppState>,
     path: web::Path<i32>,
-    form: web::Form<ProjectForm>,
     session: Session,
-) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
     }
     
-    let conn = data.db.lock().unwrap();
-    let _ = db::update_project(&conn, path.into_inner(), &form);
+    let id = path.into_inner();
+    db::call(&data.db, move |conn| db::trash_item(conn, TrashKind::Project, id)).await?;
     
-    HttpResponse::Found()
+    Ok(HttpResponse::Found()
         .append_header(("Location", "/admin/projects"))
-        .finish()
+        .finish())
 }
 
-pub async fn delete_project(
-    data: web::Data<AppState>,
-    path: web::Path<i32>,
-    session: Session,
-) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
-    }
-    
-    let conn = data.db.lock().unwrap();
-    let _ = db::delete_project(&conn, path.into_inner());
-    
-    HttpResponse::Found()
-        .append_header(("Location", "/admin/projects"))
-        .finish()
+/// A row of the admin blog list. Posts that aren't live carry a preview link to share.
+#[derive(serde::Serialize)]
+struct BlogListItem {
+    #[serde(flatten)]
+    blog: Blog,
+    preview_url: Option<String>,
 }
 
-pub async fn blogs_page(data: web::Data<AppState>, session: Session) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+pub async fn blogs_page(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    query: web::Query<PageQuery>,
+    session: Session,
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Author) {
+        return Ok(redirect);
     }
     
-    let conn = data.db.lock().unwrap();
-    let profile = db::get_profile(&conn).unwrap();
-    let blogs = db::get_blogs(&conn).unwrap_or_default();
-    
-    let mut context = tera::Context::new();
+    // Authors only see their own posts
+    let author_id = match auth::current_role(&session) {
+        Some(Role::Author) => Some(auth::current_admin_id(&session).unwrap_or(0)),
+        _ => None,
+    };
+    let (page, per_page) = query.resolve(ADMIN_BLOGS_PER_PAGE);
+    let (profile, blogs, total) = db::call(&data.db, move |conn| {
+        Ok((
+            db::get_profile(conn)?,
+            db::get_blogs_page(conn, author_id, per_page, pagination::offset(page, per_page))?,
+            db::count_blogs(conn, author_id)?,
+        ))
+    }).await?;
+    let pagination = Pagination::new("/admin/blogs", page, per_page, ADMIN_BLOGS_PER_PAGE, total);
+    
+    let site_url = routes::base_url(&data, &req);
+    let blogs: Vec<BlogListItem> = blogs
+        .into_iter()
+        .map(|blog| {
+            let is_live = blog.published && blog.publish_at.is_none();
+            let preview_url = (!is_live).then(|| format!("{}{}", site_url, data.preview_links.url(blog.id)));
+            BlogListItem { blog, preview_url }
+        })
+        .collect();
+    
+    let mut context = admin_context(&session);
     context.insert("profile", &profile);
     context.insert("blogs", &blogs);
+    context.insert("preview_link_hours", &(data.preview_links.ttl_secs() / 3600));
+    context.insert("pagination", &pagination);
     context.insert("page_title", "Manage Blogs");
     
-    let rendered = data.tera.render("admin/blogs.html", &context).unwrap();
-    HttpResponse::Ok().content_type("text/html").body(rendered)
+    let rendered = data.tera.render("admin/blogs.html", &context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
 }
 
-pub async fn add_blog_page(data: web::Data<AppState>, session: Session) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+pub async fn add_blog_page(data: web::Data<AppState>, session: Session) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Author) {
+        return Ok(redirect);
     }
     
-    let conn = data.db.lock().unwrap();
-    let profile = db::get_profile(&conn).unwrap();
+    let profile = db::call(&data.db, |conn| db::get_profile(conn)).await?;
     
-    let mut context = tera::Context::new();
+    let mut context = admin_context(&session);
     context.insert("profile", &profile);
     context.insert("page_title", "Add Blog Post");
     context.insert("editing", &false);
     
-    let rendered = data.tera.render("admin/blog_form.html", &context).unwrap();
-    HttpResponse::Ok().content_type("text/html").body(rendered)
-}
-
-pub async fn add_blog(
-    data: web::Data<AppState>,
-    form: web::Form<BlogForm>,
-    session: Session,
-) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
-    }
-    
-    let conn = data.db.lock().unwrap();
-    let _ = db::add_blog(&conn, &form);
-    
-    HttpResponse::Found()
-        .append_header(("Location", "/admin/blogs"))
-        .finish()
+    let rendered = data.tera.render("admin/blog_form.html", &context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
 }
 
 pub async fn edit_blog_page(
     data: web::Data<AppState>,
     path: web::Path<i32>,
     session: Session,
-) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Author) {
+        return Ok(redirect);
     }
     
-    let conn = data.db.lock().unwrap();
-    let profile = db::get_profile(&conn).unwrap();
-    
-    match db::get_blog_by_id(&conn, path.into_inner()) {
-        Ok(blog) => {
-            let mut context = tera::Context::new();
+    let id = path.into_inner();
+    let (profile, blog, revisions) = db::call(&data.db, move |conn| {
+        Ok((
+            db::get_profile(conn)?,
+            db::get_blog_by_id(conn, id).optional()?,
+            db::get_revisions(conn, RevisionKind::Blog, id, REVISIONS_SHOWN)?,
+        ))
+    }).await?;
+    
+    match blog {
+        Some(blog) if !can_edit_blog(&session, &blog) => Err(AppError::Forbidden),
+        Some(blog) => {
+            let mut context = admin_context(&session);
             context.insert("profile", &profile);
             context.insert("blog", &blog);
+            context.insert("revisions", &revisions);
             context.insert("page_title", "Edit Blog Post");
             context.insert("editing", &true);
             
-            let rendered = data.tera.render("admin/blog_form.html", &context).unwrap();
-            HttpResponse::Ok().content_type("text/html").body(rendered)
+            let rendered = data.tera.render("admin/blog_form.html", &context)?;
+            Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
         }
-        Err(_) => HttpResponse::NotFound().body("Blog post not found"),
+        None => Err(AppError::NotFound("Blog post")),
     }
 }
 
-pub async fn update_blog(
+/// Reads the `datetime-local` value of the publish date field, which is taken as UTC.
+fn parse_publish_at(value: &str) -> Option<String> {
+    let value = value.trim();
+    chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M")
+        .or_else(|_| chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%dT%H:%M:%S"))
+        .ok()
+        .map(|at| at.format("%Y-%m-%d %H:%M:%S").to_string())
+}
+
+/// Editors and owners can edit any post; authors only their own drafts.
+fn can_edit_blog(session: &Session, blog: &Blog) -> bool {
+    match auth::current_role(session) {
+        Some(Role::Author) => {
+            !blog.published && blog.author_id.is_some() && blog.author_id == auth::current_admin_id(session)
+        }
+        Some(_) => true,
+        None => false,
+    }
+}
+
+pub async fn delete_blog(
     data: web::Data<AppState>,
     path: web::Path<i32>,
-    form: web::Form<BlogForm>,
     session: Session,
-) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
     }
     
-    let conn = data.db.lock().unwrap();
-    let _ = db::update_blog(&conn, path.into_inner(), &form);
+    let id = path.into_inner();
+    db::call(&data.db, move |conn| db::trash_item(conn, TrashKind::Blog, id)).await?;
     
-    HttpResponse::Found()
+    Ok(HttpResponse::Found()
         .append_header(("Location", "/admin/blogs"))
-        .finish()
+        .finish())
 }
 
-pub async fn delete_blog(
+pub async fn messages_page(
     data: web::Data<AppState>,
-    path: web::Path<i32>,
+    query: web::Query<PageQuery>,
     session: Session,
-) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
-    }
-    
-    let conn = data.db.lock().unwrap();
-    let _ = db::delete_blog(&conn, path.into_inner());
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
+    }
+    
+    let (page, per_page) = query.resolve(ADMIN_MESSAGES_PER_PAGE);
+    let (profile, messages, total) = db::call(&data.db, move |conn| {
+        Ok((
+            db::get_profile(conn)?,
+            db::get_messages_page(conn, per_page, pagination::offset(page, per_page))?,
+            db::count_messages(conn)?,
+        ))
+    }).await?;
+    let pagination = Pagination::new("/admin/messages", page, per_page, ADMIN_MESSAGES_PER_PAGE, total);
+    
+    let mut context = admin_context(&session);
+    context.insert("profile", &profile);
+    context.insert("messages", &messages);
+    context.insert("pagination", &pagination);
+    context.insert("page_title", "Messages");
     
-    HttpResponse::Found()
-        .append_header(("Location", "/admin/blogs"))
-        .finish()
+    let rendered = data.tera.render("admin/messages.html", &context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
 }
 
-pub async fn messages_page(data: web::Data<AppState>, session: Session) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+pub async fn search_page(
+    data: web::Data<AppState>,
+    query: web::Query<SearchQuery>,
+    session: Session,
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
     }
     
-    let conn = data.db.lock().unwrap();
-    let profile = db::get_profile(&conn).unwrap();
-    let messages = db::get_messages(&conn).unwrap_or_default();
+    let q = query.into_inner().q.unwrap_or_default().trim().to_string();
+    let term = q.clone();
+    let (profile, mut results, mut messages) = db::call(&data.db, move |conn| {
+        Ok((
+            db::get_profile(conn)?,
+            db::search_content(conn, &term, true, routes::SEARCH_LIMIT)?,
+            db::search_messages(conn, &term, routes::SEARCH_LIMIT)?,
+        ))
+    }).await?;
+    for result in results.iter_mut().chain(messages.iter_mut()) {
+        result.snippet = routes::highlight_snippet(&result.snippet);
+    }
     
-    let mut context = tera::Context::new();
+    let mut context = admin_context(&session);
     context.insert("profile", &profile);
+    context.insert("query", &q);
+    context.insert("results", &results);
     context.insert("messages", &messages);
-    context.insert("page_title", "Messages");
+    context.insert("page_title", "Search");
     
-    let rendered = data.tera.render("admin/messages.html", &context).unwrap();
-    HttpResponse::Ok().content_type("text/html").body(rendered)
+    let rendered = data.tera.render("admin/search.html", &context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
 }
 
 pub async fn delete_message(
     data: web::Data<AppState>,
     path: web::Path<i32>,
     session: Session,
-) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
     }
     
-    let conn = data.db.lock().unwrap();
-    let _ = db::delete_message(&conn, path.into_inner());
+    let id = path.into_inner();
+    db::call(&data.db, move |conn| db::trash_item(conn, TrashKind::Message, id)).await?;
     
-    HttpResponse::Found()
+    Ok(HttpResponse::Found()
         .append_header(("Location", "/admin/messages"))
-        .finish()
+        .finish())
 }
 
-// Helper function to extract multipart form data
-async fn extract_multipart_data(mut payload: Multipart) -> (std::collections::HashMap<String, String>, Option<(String, String, Vec<u8>)>) {
-    let mut fields: std::collections::HashMap<String, String> = std::collections::HashMap::new();
-    let mut image_data: Option<(String, String, Vec<u8>)> = None;
-    
-    while let Some(item) = payload.next().await {
-        if let Ok(mut field) = item {
-            // Get field name from content disposition
-            let field_name = field.content_disposition()
-                .and_then(|cd| cd.get_name().map(|s| s.to_string()))
-                .unwrap_or_default();
-            
-            // Check if it's a file field with actual content
-            let filename = field.content_disposition()
-                .and_then(|cd| cd.get_filename().map(|s| s.to_string()))
-                .unwrap_or_default();
-            
-            let is_file_with_content = !filename.is_empty();
-            
-            if is_file_with_content {
-                let content_type = field.content_type()
-                    .map(|ct| ct.to_string())
-                    .unwrap_or_else(|| "application/octet-stream".to_string());
-                
-                let mut data = Vec::new();
-                while let Some(chunk) = field.next().await {
-                    if let Ok(bytes) = chunk {
-                        data.extend_from_slice(&bytes);
-                    }
-                }
-                
-                if !data.is_empty() {
-                    log::info!("Received file upload: {} ({} bytes)", filename, data.len());
-                    image_data = Some((filename, content_type, data));
-                }
-            } else {
-                // Regular text field (or empty file field - still read to drain it)
-                let mut value = String::new();
-                while let Some(chunk) = field.next().await {
-                    if let Ok(bytes) = chunk {
-                        if let Ok(text) = std::str::from_utf8(&bytes) {
-                            value.push_str(text);
-                        }
-                    }
-                }
-                if !field_name.is_empty() {
-                    log::debug!("Form field: {} = {}", field_name, &value);
-                    fields.insert(field_name, value);
-                }
-            }
-        }
-    }
-    
-    log::info!("Extracted {} form fields", fields.len());
-    (fields, image_data)
+// An uploaded file: its name, the content type it was identified as, and its bytes
+type UploadedFile = (String, String, Vec<u8>);
+
+// Helper function to extract multipart form data; any file in it must be an image
+async fn extract_multipart_data(payload: Multipart) -> Result<(std::collections::HashMap<String, String>, Option<UploadedFile>), UploadError> {
+    let (fields, files) = read_multipart(payload, |_| Some(uploads::IMAGE_TYPES)).await?;
+    Ok((fields, files.into_values().next()))
 }
 
-// Extract multiple files from multipart data
-async fn extract_multipart_with_files(mut payload: Multipart) -> (std::collections::HashMap<String, String>, std::collections::HashMap<String, (String, String, Vec<u8>)>) {
+// Extract multiple files from multipart data, each checked against the types its field accepts
+async fn extract_multipart_with_files(
+    payload: Multipart,
+    accepted: &[(&str, &'static [&'static str])],
+) -> Result<(std::collections::HashMap<String, String>, std::collections::HashMap<String, UploadedFile>), UploadError> {
+    read_multipart(payload, |name| {
+        accepted.iter().find(|(field, _)| *field == name).map(|(_, types)| *types)
+    }).await
+}
+
+async fn read_multipart(
+    mut payload: Multipart,
+    accepted_types: impl Fn(&str) -> Option<&'static [&'static str]>,
+) -> Result<(std::collections::HashMap<String, String>, std::collections::HashMap<String, UploadedFile>), UploadError> {
     let mut fields: std::collections::HashMap<String, String> = std::collections::HashMap::new();
-    let mut files: std::collections::HashMap<String, (String, String, Vec<u8>)> = std::collections::HashMap::new();
+    let mut files: std::collections::HashMap<String, UploadedFile> = std::collections::HashMap::new();
+    let mut parts = 0;
     
     while let Some(item) = payload.next().await {
-        if let Ok(mut field) = item {
-            let field_name = field.content_disposition()
-                .and_then(|cd| cd.get_name().map(|s| s.to_string()))
-                .unwrap_or_default();
-            
-            let filename = field.content_disposition()
-                .and_then(|cd| cd.get_filename().map(|s| s.to_string()))
-                .unwrap_or_default();
-            
-            let is_file_with_content = !filename.is_empty();
+        let mut field = item.map_err(|_| UploadError::Malformed)?;
+        parts += 1;
+        if parts > uploads::MAX_FIELDS {
+            return Err(UploadError::TooManyFields);
+        }
+        
+        // Get field name from content disposition
+        let field_name = field.content_disposition()
+            .and_then(|cd| cd.get_name().map(|s| s.to_string()))
+            .unwrap_or_default();
+        
+        // Check if it's a file field with actual content
+        let filename = field.content_disposition()
+            .and_then(|cd| cd.get_filename().map(|s| s.to_string()))
+            .unwrap_or_default();
+        
+        if !filename.is_empty() {
+            let data = uploads::read_field(&mut field, &field_name, uploads::MAX_FILE_BYTES).await?;
+            if data.is_empty() {
+                continue;
+            }
             
-            if is_file_with_content {
-                let content_type = field.content_type()
-                    .map(|ct| ct.to_string())
-                    .unwrap_or_else(|| "application/octet-stream".to_string());
-                
-                let mut data = Vec::new();
-                while let Some(chunk) = field.next().await {
-                    if let Ok(bytes) = chunk {
-                        data.extend_from_slice(&bytes);
-                    }
-                }
-                
-                if !data.is_empty() {
-                    log::info!("Received file upload for field '{}': {} ({} bytes)", field_name, filename, data.len());
-                    files.insert(field_name, (filename, content_type, data));
-                }
-            } else {
-                let mut value = String::new();
-                while let Some(chunk) = field.next().await {
-                    if let Ok(bytes) = chunk {
-                        if let Ok(text) = std::str::from_utf8(&bytes) {
-                            value.push_str(text);
-                        }
-                    }
-                }
-                if !field_name.is_empty() {
-                    fields.insert(field_name, value);
-                }
+            // The browser's idea of the content type is ignored; the bytes decide
+            let allowed = accepted_types(&field_name).unwrap_or_default();
+            let (content_type, data) = uploads::validate(&filename, data, allowed)?;
+            log::info!("Received file upload for field '{}': {} ({}, {} bytes)", field_name, filename, content_type, data.len());
+            files.insert(field_name, (filename, content_type.to_string(), data));
+        } else {
+            // Regular text field (or empty file field - still read to drain it)
+            let value = uploads::read_field(&mut field, &field_name, uploads::MAX_TEXT_BYTES).await?;
+            if !field_name.is_empty() {
+                let value = String::from_utf8_lossy(&value).into_owned();
+                log::debug!("Form field: {} = {}", field_name, &value);
+                fields.insert(field_name, value);
             }
         }
     }
     
     log::info!("Extracted {} form fields and {} files", fields.len(), files.len());
-    (fields, files)
+    Ok((fields, files))
+}
+
+// Stores an uploaded file and returns its URL under `prefix` ("/images" or "/files")
+async fn save_upload(data: &AppState, prefix: &str, (filename, content_type, bytes): UploadedFile) -> Result<String, AppError> {
+    let id = blobs::save_image(&data.db, &data.blobs, filename, content_type, bytes).await?;
+    Ok(format!("{}/{}", prefix, id))
 }
 
 // Generic image upload endpoint (AJAX)
@@ -576,32 +582,37 @@ pub async fn upload_image(
     data: web::Data<AppState>,
     payload: Multipart,
     session: Session,
-) -> HttpResponse {
+) -> Result<HttpResponse, AppError> {
     if !auth::is_authenticated(&session) {
-        return HttpResponse::Unauthorized().json(serde_json::json!({"error": "Unauthorized"}));
+        return Ok(HttpResponse::Unauthorized().json(serde_json::json!({"error": "Unauthorized"})));
     }
     
-    let (_, image_data) = extract_multipart_data(payload).await;
+    let image_data = match extract_multipart_data(payload).await {
+        Ok((_, image_data)) => image_data,
+        Err(e) => {
+            let error = AppError::from(e);
+            return Ok(HttpResponse::build(error.status_code()).json(serde_json::json!({"error": error.to_string()})));
+        }
+    };
     
     if let Some((filename, content_type, bytes)) = image_data {
-        let image_id = uuid::Uuid::new_v4().to_string();
-        let conn = data.db.lock().unwrap();
+        let saved = blobs::save_image(&data.db, &data.blobs, filename, content_type, bytes).await;
         
-        match db::save_image(&conn, &image_id, &filename, &content_type, &bytes) {
-            Ok(_) => {
+        match saved {
+            Ok(image_id) => {
                 let image_url = format!("/images/{}", image_id);
-                HttpResponse::Ok().json(serde_json::json!({
+                Ok(HttpResponse::Ok().json(serde_json::json!({
                     "success": true,
                     "image_url": image_url,
                     "image_id": image_id
-                }))
+                })))
             }
-            Err(e) => HttpResponse::InternalServerError().json(serde_json::json!({
+            Err(e) => Ok(HttpResponse::InternalServerError().json(serde_json::json!({
                 "error": format!("Failed to save image: {}", e)
-            }))
+            })))
         }
     } else {
-        HttpResponse::BadRequest().json(serde_json::json!({"error": "No image provided"}))
+        Ok(HttpResponse::BadRequest().json(serde_json::json!({"error": "No image provided"})))
     }
 }
 
@@ -610,66 +621,162 @@ pub async fn update_profile_with_image(
     data: web::Data<AppState>,
     payload: Multipart,
     session: Session,
-) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
     }
     
-    let (fields, files) = extract_multipart_with_files(payload).await;
+    let accepted = [("avatar_file", uploads::IMAGE_TYPES), ("resume_file", uploads::DOCUMENT_TYPES)];
+    let (fields, mut files) = extract_multipart_with_files(payload, &accepted).await?;
     
     log::info!("Updating profile with fields: {:?}", fields.keys().collect::<Vec<_>>());
     
-    // Get the connection once
-    let conn = data.db.lock().unwrap();
-    
     // Handle avatar image upload if present
-    let avatar_url = if let Some((filename, content_type, bytes)) = files.get("avatar_file") {
-        let image_id = uuid::Uuid::new_v4().to_string();
-        let _ = db::save_image(&conn, &image_id, filename, content_type, bytes);
-        format!("/images/{}", image_id)
-    } else {
-        fields.get("avatar_url").cloned().unwrap_or_default()
+    let avatar_url = match files.remove("avatar_file") {
+        Some(file) => save_upload(&data, "/images", file).await?,
+        None => fields.get("avatar_url").cloned().unwrap_or_default(),
     };
     
     // Handle resume file upload if present
-    let resume_url = if let Some((filename, content_type, bytes)) = files.get("resume_file") {
-        let file_id = uuid::Uuid::new_v4().to_string();
-        let _ = db::save_image(&conn, &file_id, filename, content_type, bytes);
-        format!("/files/{}", file_id)
-    } else {
-        fields.get("resume_url").cloned().unwrap_or_default()
+    let resume_url = match files.remove("resume_file") {
+        Some(file) => save_upload(&data, "/files", file).await?,
+        None => fields.get("resume_url").cloned().unwrap_or_default(),
     };
     
-    let form = ProfileForm {
-        name: fields.get("name").cloned().unwrap_or_default(),
-        title: fields.get("title").cloned().unwrap_or_default(),
-        bio: fields.get("bio").cloned().unwrap_or_default(),
-        email: fields.get("email").cloned().unwrap_or_default(),
-        phone: fields.get("phone").cloned().unwrap_or_default(),
-        location: fields.get("location").cloned().unwrap_or_default(),
-        github_url: fields.get("github_url").cloned().unwrap_or_default(),
-        linkedin_url: fields.get("linkedin_url").cloned().unwrap_or_default(),
-        twitter_url: fields.get("twitter_url").cloned().unwrap_or_default(),
-        resume_url,
-        avatar_url,
-    };
+    db::call(&data.db, move |conn| {
+        let form = ProfileForm {
+            name: fields.get("name").cloned().unwrap_or_default(),
+            title: fields.get("title").cloned().unwrap_or_default(),
+            bio: fields.get("bio").cloned().unwrap_or_default(),
+            email: fields.get("email").cloned().unwrap_or_default(),
+            phone: fields.get("phone").cloned().unwrap_or_default(),
+            location: fields.get("location").cloned().unwrap_or_default(),
+            github_url: fields.get("github_url").cloned().unwrap_or_default(),
+            linkedin_url: fields.get("linkedin_url").cloned().unwrap_or_default(),
+            twitter_url: fields.get("twitter_url").cloned().unwrap_or_default(),
+            resume_url,
+            avatar_url,
+        };
+        
+        db::update_profile(conn, &form)?;
+        log::info!("Profile updated successfully");
+        
+        Ok(())
+    }).await?;
     
-    match db::update_profile(&conn, &form) {
-        Ok(_) => log::info!("Profile updated successfully"),
-        Err(e) => log::error!("Failed to update profile: {}", e),
+    let mut extra = tera::Context::new();
+    extra.insert("success", &true);
+    render_profile(&data, &session, extra).await
+}
+
+fn two_factor_flash(message: &str, is_error: bool) -> tera::Context {
+    let mut extra = tera::Context::new();
+    extra.insert(if is_error { "two_factor_error" } else { "two_factor_message" }, message);
+    extra
+}
+
+pub async fn enable_two_factor(
+    data: web::Data<AppState>,
+    form: web::Form<TwoFactorForm>,
+    session: Session,
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Author) {
+        return Ok(redirect);
     }
     
-    let profile = db::get_profile(&conn).unwrap();
+    let secret = match session.get::<String>(two_factor::PENDING_SECRET_KEY).unwrap_or(None) {
+        Some(secret) => secret,
+        None => {
+            return Ok(HttpResponse::Found()
+                .append_header(("Location", "/admin/profile"))
+                .finish());
+        }
+    };
     
-    drop(conn);
+    // Enrollment only counts once the authenticator has produced a valid code
+    let step = match two_factor::verify_code(&secret, &form.code, 0) {
+        Some(step) => step,
+        None => {
+            let flash = two_factor_flash("That code didn't match. Check the time on your device and try again.", true);
+            return render_profile(&data, &session, flash).await;
+        }
+    };
     
-    let mut context = tera::Context::new();
-    context.insert("profile", &profile);
-    context.insert("page_title", "Edit Profile");
-    context.insert("success", &true);
+    let recovery_codes = two_factor::generate_recovery_codes();
+    let code_hashes: Vec<String> = recovery_codes.iter().map(|code| two_factor::hash_recovery_code(code)).collect();
+    let username = auth::current_username(&session).unwrap_or_default();
+    let name = username.clone();
+    db::call(&data.db, move |conn| {
+        let admin = db::get_admin(conn, &name)?;
+        db::enable_totp(conn, admin.id, &secret, step, &code_hashes)
+    }).await?;
+    session.remove(two_factor::PENDING_SECRET_KEY);
+    log::info!("Two-factor authentication enabled for {}", username);
+    
+    let mut flash = two_factor_flash("Two-factor authentication is on.", false);
+    flash.insert("recovery_codes", &recovery_codes);
+    render_profile(&data, &session, flash).await
+}
+
+pub async fn disable_two_factor(
+    data: web::Data<AppState>,
+    form: web::Form<TwoFactorForm>,
+    session: Session,
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Author) {
+        return Ok(redirect);
+    }
+    
+    let username = auth::current_username(&session).unwrap_or_default();
+    let name = username.clone();
+    let code = form.into_inner().code;
+    let disabled = db::call(&data.db, move |conn| {
+        let admin = db::get_admin(conn, &name)?;
+        if !auth::check_second_factor(conn, &admin, &code)? {
+            return Ok(false);
+        }
+        db::disable_totp(conn, admin.id)?;
+        Ok(true)
+    }).await?;
+    
+    let flash = if disabled {
+        log::info!("Two-factor authentication disabled for {}", username);
+        two_factor_flash("Two-factor authentication is off.", false)
+    } else {
+        two_factor_flash("Invalid authentication code - two-factor authentication is still on.", true)
+    };
+    render_profile(&data, &session, flash).await
+}
+
+pub async fn regenerate_recovery_codes(
+    data: web::Data<AppState>,
+    form: web::Form<TwoFactorForm>,
+    session: Session,
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Author) {
+        return Ok(redirect);
+    }
+    
+    let recovery_codes = two_factor::generate_recovery_codes();
+    let code_hashes: Vec<String> = recovery_codes.iter().map(|code| two_factor::hash_recovery_code(code)).collect();
+    let username = auth::current_username(&session).unwrap_or_default();
+    let code = form.into_inner().code;
+    let replaced = db::call(&data.db, move |conn| {
+        let admin = db::get_admin(conn, &username)?;
+        if !auth::check_second_factor(conn, &admin, &code)? {
+            return Ok(false);
+        }
+        db::replace_recovery_codes(conn, admin.id, &code_hashes)?;
+        Ok(true)
+    }).await?;
+    
+    if !replaced {
+        return render_profile(&data, &session, two_factor_flash("Invalid authentication code.", true)).await;
+    }
     
-    let rendered = data.tera.render("admin/profile.html", &context).unwrap();
-    HttpResponse::Ok().content_type("text/html").body(rendered)
+    let mut flash = two_factor_flash("New recovery codes generated. The old ones no longer work.", false);
+    flash.insert("recovery_codes", &recovery_codes);
+    render_profile(&data, &session, flash).await
 }
 
 // Add project with image upload support
@@ -677,50 +784,48 @@ pub async fn add_project_with_image(
     data: web::Data<AppState>,
     payload: Multipart,
     session: Session,
-) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
     }
     
-    let (fields, image_data) = extract_multipart_data(payload).await;
+    let (fields, image_data) = extract_multipart_data(payload).await?;
     
     log::info!("Adding project with fields: {:?}", fields.keys().collect::<Vec<_>>());
     
-    // Get the connection once and use it for both operations
-    let conn = data.db.lock().unwrap();
+    let editor_id = auth::current_admin_id(&session);
     
     // Handle image upload if present
-    let image_url = if let Some((filename, content_type, bytes)) = image_data {
-        let image_id = uuid::Uuid::new_v4().to_string();
-        let _ = db::save_image(&conn, &image_id, &filename, &content_type, &bytes);
-        format!("/images/{}", image_id)
-    } else {
-        fields.get("image_url").cloned().unwrap_or_default()
-    };
-    
-    let form = ProjectForm {
-        title: fields.get("title").cloned().unwrap_or_default(),
-        description: fields.get("description").cloned().unwrap_or_default(),
-        content: fields.get("content").cloned().unwrap_or_default(),
-        image_url,
-        demo_url: fields.get("demo_url").cloned().unwrap_or_default(),
-        github_url: fields.get("github_url").cloned().unwrap_or_default(),
-        technologies: fields.get("technologies").cloned().unwrap_or_default(),
-        featured: fields.get("featured").map(|s| s.to_string()),
+    let image_url = match image_data {
+        Some(file) => save_upload(&data, "/images", file).await?,
+        None => fields.get("image_url").cloned().unwrap_or_default(),
     };
     
-    log::info!("Adding project: {}", form.title);
-    
-    match db::add_project(&conn, &form) {
-        Ok(_) => log::info!("Project added successfully"),
-        Err(e) => log::error!("Failed to add project: {}", e),
-    }
-    
-    drop(conn); // Explicitly drop the lock
+    db::call(&data.db, move |conn| {
+        let form = ProjectForm {
+            title: fields.get("title").cloned().unwrap_or_default(),
+            description: fields.get("description").cloned().unwrap_or_default(),
+            content: fields.get("content").cloned().unwrap_or_default(),
+            image_url,
+            demo_url: fields.get("demo_url").cloned().unwrap_or_default(),
+            github_url: fields.get("github_url").cloned().unwrap_or_default(),
+            technologies: fields.get("technologies").cloned().unwrap_or_default(),
+            featured: fields.get("featured").map(|s| s.to_string()),
+            seo_title: fields.get("seo_title").cloned().unwrap_or_default(),
+            seo_description: fields.get("seo_description").cloned().unwrap_or_default(),
+        };
+        
+        log::info!("Adding project: {}", form.title);
+        
+        // A failed save is shown rather than redirected past, so nothing is silently lost
+        db::add_project(conn, &form, editor_id)?;
+        log::info!("Project added successfully");
+        Ok(())
+    }).await?;
     
-    HttpResponse::Found()
+    Ok(HttpResponse::Found()
         .append_header(("Location", "/admin/projects"))
-        .finish()
+        .finish())
 }
 
 // Update project with image upload support
@@ -729,49 +834,48 @@ pub async fn update_project_with_image(
     path: web::Path<i32>,
     payload: Multipart,
     session: Session,
-) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
     }
     
     let project_id = path.into_inner();
-    let (fields, image_data) = extract_multipart_data(payload).await;
+    let (fields, image_data) = extract_multipart_data(payload).await?;
     
     log::info!("Updating project {} with fields: {:?}", project_id, fields.keys().collect::<Vec<_>>());
     
-    // Get the connection once and use it for both operations
-    let conn = data.db.lock().unwrap();
+    let editor_id = auth::current_admin_id(&session);
     
     // Handle image upload if present
-    let image_url = if let Some((filename, content_type, bytes)) = image_data {
-        let image_id = uuid::Uuid::new_v4().to_string();
-        let _ = db::save_image(&conn, &image_id, &filename, &content_type, &bytes);
-        format!("/images/{}", image_id)
-    } else {
-        fields.get("image_url").cloned().unwrap_or_default()
-    };
-    
-    let form = ProjectForm {
-        title: fields.get("title").cloned().unwrap_or_default(),
-        description: fields.get("description").cloned().unwrap_or_default(),
-        content: fields.get("content").cloned().unwrap_or_default(),
-        image_url,
-        demo_url: fields.get("demo_url").cloned().unwrap_or_default(),
-        github_url: fields.get("github_url").cloned().unwrap_or_default(),
-        technologies: fields.get("technologies").cloned().unwrap_or_default(),
-        featured: fields.get("featured").map(|s| s.to_string()),
+    let image_url = match image_data {
+        Some(file) => save_upload(&data, "/images", file).await?,
+        None => fields.get("image_url").cloned().unwrap_or_default(),
     };
     
-    match db::update_project(&conn, project_id, &form) {
-        Ok(_) => log::info!("Project {} updated successfully", project_id),
-        Err(e) => log::error!("Failed to update project: {}", e),
+    let updated = db::call(&data.db, move |conn| {
+        let form = ProjectForm {
+            title: fields.get("title").cloned().unwrap_or_default(),
+            description: fields.get("description").cloned().unwrap_or_default(),
+            content: fields.get("content").cloned().unwrap_or_default(),
+            image_url,
+            demo_url: fields.get("demo_url").cloned().unwrap_or_default(),
+            github_url: fields.get("github_url").cloned().unwrap_or_default(),
+            technologies: fields.get("technologies").cloned().unwrap_or_default(),
+            featured: fields.get("featured").map(|s| s.to_string()),
+            seo_title: fields.get("seo_title").cloned().unwrap_or_default(),
+            seo_description: fields.get("seo_description").cloned().unwrap_or_default(),
+        };
+        
+        db::update_project(conn, project_id, &form, editor_id)
+    }).await?;
+    if !updated {
+        return Err(AppError::NotFound("Project"));
     }
+    log::info!("Project {} updated successfully", project_id);
     
-    drop(conn);
-    
-    HttpResponse::Found()
+    Ok(HttpResponse::Found()
         .append_header(("Location", "/admin/projects"))
-        .finish()
+        .finish())
 }
 
 // Add blog with image upload support
@@ -779,48 +883,47 @@ pub async fn add_blog_with_image(
     data: web::Data<AppState>,
     payload: Multipart,
     session: Session,
-) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Author) {
+        return Ok(redirect);
     }
     
-    let (fields, image_data) = extract_multipart_data(payload).await;
+    let (fields, image_data) = extract_multipart_data(payload).await?;
     
     log::info!("Adding blog with fields: {:?}", fields.keys().collect::<Vec<_>>());
     
-    // Get the connection once
-    let conn = data.db.lock().unwrap();
-    
+    let author_id = auth::current_admin_id(&session);
+    // Authors write drafts; an editor publishes them
+    let may_publish = auth::current_role(&session) >= Some(Role::Editor);
     // Handle image upload if present
-    let image_url = if let Some((filename, content_type, bytes)) = image_data {
-        let image_id = uuid::Uuid::new_v4().to_string();
-        let _ = db::save_image(&conn, &image_id, &filename, &content_type, &bytes);
-        format!("/images/{}", image_id)
-    } else {
-        fields.get("image_url").cloned().unwrap_or_default()
+    let image_url = match image_data {
+        Some(file) => save_upload(&data, "/images", file).await?,
+        None => fields.get("image_url").cloned().unwrap_or_default(),
     };
     
-    let form = BlogForm {
-        title: fields.get("title").cloned().unwrap_or_default(),
-        excerpt: fields.get("excerpt").cloned().unwrap_or_default(),
-        content: fields.get("content").cloned().unwrap_or_default(),
-        image_url,
-        tags: fields.get("tags").cloned().unwrap_or_default(),
-        published: fields.get("published").map(|s| s.to_string()),
-    };
-    
-    log::info!("Adding blog: {}", form.title);
-    
-    match db::add_blog(&conn, &form) {
-        Ok(_) => log::info!("Blog added successfully"),
-        Err(e) => log::error!("Failed to add blog: {}", e),
-    }
-    
-    drop(conn);
+    db::call(&data.db, move |conn| {
+        let form = BlogForm {
+            title: fields.get("title").cloned().unwrap_or_default(),
+            excerpt: fields.get("excerpt").cloned().unwrap_or_default(),
+            content: fields.get("content").cloned().unwrap_or_default(),
+            image_url,
+            tags: fields.get("tags").cloned().unwrap_or_default(),
+            published: fields.get("published").filter(|_| may_publish).map(|s| s.to_string()),
+            seo_title: fields.get("seo_title").cloned().unwrap_or_default(),
+            seo_description: fields.get("seo_description").cloned().unwrap_or_default(),
+            publish_at: fields.get("publish_at").filter(|_| may_publish).and_then(|s| parse_publish_at(s)),
+        };
+        
+        log::info!("Adding blog: {}", form.title);
+        
+        db::add_blog(conn, &form, author_id)?;
+        log::info!("Blog added successfully");
+        Ok(())
+    }).await?;
     
-    HttpResponse::Found()
+    Ok(HttpResponse::Found()
         .append_header(("Location", "/admin/blogs"))
-        .finish()
+        .finish())
 }
 
 // Update blog with image upload support
@@ -829,110 +932,117 @@ pub async fn update_blog_with_image(
     path: web::Path<i32>,
     payload: Multipart,
     session: Session,
-) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Author) {
+        return Ok(redirect);
     }
     
     let blog_id = path.into_inner();
-    let (fields, image_data) = extract_multipart_data(payload).await;
+    let existing = db::call(&data.db, move |conn| db::get_blog_by_id(conn, blog_id).optional()).await?;
+    match existing {
+        Some(blog) if !can_edit_blog(&session, &blog) => return Err(AppError::Forbidden),
+        Some(_) => {}
+        None => return Err(AppError::NotFound("Blog post")),
+    }
     
-    log::info!("Updating blog {} with fields: {:?}", blog_id, fields.keys().collect::<Vec<_>>());
+    let (fields, image_data) = extract_multipart_data(payload).await?;
     
-    // Get the connection once
-    let conn = data.db.lock().unwrap();
+    log::info!("Updating blog {} with fields: {:?}", blog_id, fields.keys().collect::<Vec<_>>());
     
+    let editor_id = auth::current_admin_id(&session);
+    let may_publish = auth::current_role(&session) >= Some(Role::Editor);
     // Handle image upload if present
-    let image_url = if let Some((filename, content_type, bytes)) = image_data {
-        let image_id = uuid::Uuid::new_v4().to_string();
-        let _ = db::save_image(&conn, &image_id, &filename, &content_type, &bytes);
-        format!("/images/{}", image_id)
-    } else {
-        fields.get("image_url").cloned().unwrap_or_default()
+    let image_url = match image_data {
+        Some(file) => save_upload(&data, "/images", file).await?,
+        None => fields.get("image_url").cloned().unwrap_or_default(),
     };
     
-    let form = BlogForm {
-        title: fields.get("title").cloned().unwrap_or_default(),
-        excerpt: fields.get("excerpt").cloned().unwrap_or_default(),
-        content: fields.get("content").cloned().unwrap_or_default(),
-        image_url,
-        tags: fields.get("tags").cloned().unwrap_or_default(),
-        published: fields.get("published").map(|s| s.to_string()),
-    };
-    
-    match db::update_blog(&conn, blog_id, &form) {
-        Ok(_) => log::info!("Blog {} updated successfully", blog_id),
-        Err(e) => log::error!("Failed to update blog: {}", e),
+    let updated = db::call(&data.db, move |conn| {
+        let form = BlogForm {
+            title: fields.get("title").cloned().unwrap_or_default(),
+            excerpt: fields.get("excerpt").cloned().unwrap_or_default(),
+            content: fields.get("content").cloned().unwrap_or_default(),
+            image_url,
+            tags: fields.get("tags").cloned().unwrap_or_default(),
+            published: fields.get("published").filter(|_| may_publish).map(|s| s.to_string()),
+            seo_title: fields.get("seo_title").cloned().unwrap_or_default(),
+            seo_description: fields.get("seo_description").cloned().unwrap_or_default(),
+            publish_at: fields.get("publish_at").filter(|_| may_publish).and_then(|s| parse_publish_at(s)),
+        };
+        
+        db::update_blog(conn, blog_id, &form, editor_id)
+    }).await?;
+    if !updated {
+        return Err(AppError::NotFound("Blog post"));
     }
+    log::info!("Blog {} updated successfully", blog_id);
     
-    drop(conn);
-    
-    HttpResponse::Found()
+    Ok(HttpResponse::Found()
         .append_header(("Location", "/admin/blogs"))
-        .finish()
+        .finish())
 }
 
 // Services management
-pub async fn services_page(data: web::Data<AppState>, session: Session) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+pub async fn services_page(data: web::Data<AppState>, session: Session) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
     }
     
-    let conn = data.db.lock().unwrap();
-    let profile = db::get_profile(&conn).unwrap();
-    let services = db::get_services(&conn).unwrap_or_default();
+    let (profile, services) = db::call(&data.db, |conn| {
+        Ok((db::get_profile(conn)?, db::get_services(conn)?))
+    }).await?;
     
-    let mut context = tera::Context::new();
+    let mut context = admin_context(&session);
     context.insert("profile", &profile);
     context.insert("services", &services);
     context.insert("page_title", "Manage Services");
     
-    let rendered = data.tera.render("admin/services.html", &context).unwrap();
-    HttpResponse::Ok().content_type("text/html").body(rendered)
+    let rendered = data.tera.render("admin/services.html", &context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
 }
 
-pub async fn add_service_page(data: web::Data<AppState>, session: Session) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+pub async fn add_service_page(data: web::Data<AppState>, session: Session) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
     }
     
-    let conn = data.db.lock().unwrap();
-    let profile = db::get_profile(&conn).unwrap();
+    let profile = db::call(&data.db, |conn| db::get_profile(conn)).await?;
     
-    let mut context = tera::Context::new();
+    let mut context = admin_context(&session);
     context.insert("profile", &profile);
     context.insert("page_title", "Add Service");
     
-    let rendered = data.tera.render("admin/service_form.html", &context).unwrap();
-    HttpResponse::Ok().content_type("text/html").body(rendered)
+    let rendered = data.tera.render("admin/service_form.html", &context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
 }
 
 pub async fn edit_service_page(
     data: web::Data<AppState>,
     path: web::Path<i32>,
     session: Session,
-) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
     }
     
     let service_id = path.into_inner();
-    let conn = data.db.lock().unwrap();
-    let profile = db::get_profile(&conn).unwrap();
+    let (profile, service) = db::call(&data.db, move |conn| {
+        Ok((db::get_profile(conn)?, db::get_service(conn, service_id).optional()?))
+    }).await?;
     
-    match db::get_service(&conn, service_id) {
-        Ok(service) => {
-            let mut context = tera::Context::new();
+    match service {
+        Some(service) => {
+            let mut context = admin_context(&session);
             context.insert("profile", &profile);
             context.insert("service", &service);
             context.insert("page_title", "Edit Service");
             
-            let rendered = data.tera.render("admin/service_form.html", &context).unwrap();
-            HttpResponse::Ok().content_type("text/html").body(rendered)
+            let rendered = data.tera.render("admin/service_form.html", &context)?;
+            Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
         }
-        Err(_) => HttpResponse::Found()
+        None => Ok(HttpResponse::Found()
             .append_header(("Location", "/admin/services"))
-            .finish(),
+            .finish()),
     }
 }
 
@@ -940,44 +1050,38 @@ pub async fn add_service_with_image(
     data: web::Data<AppState>,
     payload: Multipart,
     session: Session,
-) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
     }
     
-    let (fields, image_data) = extract_multipart_data(payload).await;
+    let (fields, image_data) = extract_multipart_data(payload).await?;
     
     log::info!("Adding service with fields: {:?}", fields.keys().collect::<Vec<_>>());
     
-    let conn = data.db.lock().unwrap();
-    
     // Handle image upload if present
-    let image_url = if let Some((filename, content_type, bytes)) = image_data {
-        let image_id = uuid::Uuid::new_v4().to_string();
-        let _ = db::save_image(&conn, &image_id, &filename, &content_type, &bytes);
-        format!("/images/{}", image_id)
-    } else {
-        fields.get("image_url").cloned().unwrap_or_default()
+    let image_url = match image_data {
+        Some(file) => save_upload(&data, "/images", file).await?,
+        None => fields.get("image_url").cloned().unwrap_or_default(),
     };
     
-    let form = ServiceForm {
-        name: fields.get("name").cloned().unwrap_or_default(),
-        description: fields.get("description").cloned().unwrap_or_default(),
-        image_url,
-        icon: fields.get("icon").cloned().unwrap_or_default(),
-        order_index: fields.get("order_index").and_then(|s| s.parse().ok()).unwrap_or(0),
-    };
-    
-    match db::add_service(&conn, &form) {
-        Ok(_) => log::info!("Service added successfully"),
-        Err(e) => log::error!("Failed to add service: {}", e),
-    }
-    
-    drop(conn);
+    db::call(&data.db, move |conn| {
+        let form = ServiceForm {
+            name: fields.get("name").cloned().unwrap_or_default(),
+            description: fields.get("description").cloned().unwrap_or_default(),
+            image_url,
+            icon: fields.get("icon").cloned().unwrap_or_default(),
+            order_index: fields.get("order_index").and_then(|s| s.parse().ok()).unwrap_or(0),
+        };
+        
+        db::add_service(conn, &form)?;
+        log::info!("Service added successfully");
+        Ok(())
+    }).await?;
     
-    HttpResponse::Found()
+    Ok(HttpResponse::Found()
         .append_header(("Location", "/admin/services"))
-        .finish()
+        .finish())
 }
 
 pub async fn update_service_with_image(
@@ -985,147 +1089,138 @@ pub async fn update_service_with_image(
     path: web::Path<i32>,
     payload: Multipart,
     session: Session,
-) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
     }
     
     let service_id = path.into_inner();
-    let (fields, image_data) = extract_multipart_data(payload).await;
+    let (fields, image_data) = extract_multipart_data(payload).await?;
     
     log::info!("Updating service {} with fields: {:?}", service_id, fields.keys().collect::<Vec<_>>());
     
-    let conn = data.db.lock().unwrap();
-    
     // Handle image upload if present
-    let image_url = if let Some((filename, content_type, bytes)) = image_data {
-        let image_id = uuid::Uuid::new_v4().to_string();
-        let _ = db::save_image(&conn, &image_id, &filename, &content_type, &bytes);
-        format!("/images/{}", image_id)
-    } else {
-        fields.get("image_url").cloned().unwrap_or_default()
-    };
-    
-    let form = ServiceForm {
-        name: fields.get("name").cloned().unwrap_or_default(),
-        description: fields.get("description").cloned().unwrap_or_default(),
-        image_url,
-        icon: fields.get("icon").cloned().unwrap_or_default(),
-        order_index: fields.get("order_index").and_then(|s| s.parse().ok()).unwrap_or(0),
+    let image_url = match image_data {
+        Some(file) => save_upload(&data, "/images", file).await?,
+        None => fields.get("image_url").cloned().unwrap_or_default(),
     };
     
-    match db::update_service(&conn, service_id, &form) {
-        Ok(_) => log::info!("Service {} updated successfully", service_id),
-        Err(e) => log::error!("Failed to update service: {}", e),
+    let updated = db::call(&data.db, move |conn| {
+        let form = ServiceForm {
+            name: fields.get("name").cloned().unwrap_or_default(),
+            description: fields.get("description").cloned().unwrap_or_default(),
+            image_url,
+            icon: fields.get("icon").cloned().unwrap_or_default(),
+            order_index: fields.get("order_index").and_then(|s| s.parse().ok()).unwrap_or(0),
+        };
+        
+        db::update_service(conn, service_id, &form)
+    }).await?;
+    if !updated {
+        return Err(AppError::NotFound("Service"));
     }
+    log::info!("Service {} updated successfully", service_id);
     
-    drop(conn);
-    
-    HttpResponse::Found()
+    Ok(HttpResponse::Found()
         .append_header(("Location", "/admin/services"))
-        .finish()
+        .finish())
 }
 
 pub async fn delete_service(
     data: web::Data<AppState>,
     path: web::Path<i32>,
     session: Session,
-) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
     }
     
     let service_id = path.into_inner();
-    let conn = data.db.lock().unwrap();
+    db::call(&data.db, move |conn| db::trash_item(conn, TrashKind::Service, service_id)).await?;
+    log::info!("Service {} moved to the trash", service_id);
     
-    match db::delete_service(&conn, service_id) {
-        Ok(_) => log::info!("Service {} deleted successfully", service_id),
-        Err(e) => log::error!("Failed to delete service: {}", e),
-    }
-    
-    HttpResponse::Found()
+    Ok(HttpResponse::Found()
         .append_header(("Location", "/admin/services"))
-        .finish()
+        .finish())
 }
 
 // Email Settings
 pub async fn email_settings_page(
     data: web::Data<AppState>,
     session: Session,
-) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Owner) {
+        return Ok(redirect);
     }
     
-    let conn = data.db.lock().unwrap();
-    let email_settings = db::get_email_settings(&conn).unwrap_or_default();
+    let email_settings = db::call(&data.db, |conn| db::get_email_settings(conn)).await?;
     
-    let mut context = tera::Context::new();
+    let mut context = admin_context(&session);
     context.insert("email_settings", &email_settings);
     context.insert("page_title", "Email Settings");
     
-    let rendered = data.tera.render("admin/email_settings.html", &context).unwrap();
-    HttpResponse::Ok().content_type("text/html").body(rendered)
+    let rendered = data.tera.render("admin/email_settings.html", &context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
 }
 
 pub async fn update_email_settings(
     data: web::Data<AppState>,
     form: web::Form<EmailSettingsForm>,
     session: Session,
-) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Owner) {
+        return Ok(redirect);
     }
     
-    let conn = data.db.lock().unwrap();
-    
-    match db::update_email_settings(&conn, &form) {
-        Ok(_) => log::info!("Email settings updated successfully"),
-        Err(e) => log::error!("Failed to update email settings: {}", e),
-    }
+    let form = form.into_inner();
+    db::call(&data.db, move |conn| db::update_email_settings(conn, &form)).await?;
+    log::info!("Email settings updated successfully");
     
-    HttpResponse::Found()
+    Ok(HttpResponse::Found()
         .append_header(("Location", "/admin/email-settings"))
-        .finish()
+        .finish())
 }
 
 pub async fn test_email(
     data: web::Data<AppState>,
     session: Session,
-) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Owner) {
+        return Ok(redirect);
     }
     
-    let conn = data.db.lock().unwrap();
+    let settings = db::call(&data.db, |conn| db::get_email_settings(conn).optional()).await?;
     
-    if let Ok(settings) = db::get_email_settings(&conn) {
+    if let Some(settings) = settings {
         if !settings.enabled {
-            return HttpResponse::Ok()
+            return Ok(HttpResponse::Ok()
                 .content_type("application/json")
-                .body(r#"{"success": false, "message": "Email notifications are disabled"}"#);
+                .body(r#"{"success": false, "message": "Email notifications are disabled"}"#));
         }
         
-        let test_result = crate::email::send_notification_email(
-            &settings,
-            "Test User",
-            &settings.notification_email,
-            "Test Email",
-            "This is a test email from your portfolio website. If you received this, email notifications are working correctly!",
-        );
+        // SMTP delivery blocks, so keep it off the async workers
+        let test_result = web::block(move || {
+            crate::email::send_notification_email(
+                &settings,
+                "Test User",
+                &settings.notification_email,
+                "Test Email",
+                "This is a test email from your portfolio website. If you received this, email notifications are working correctly!",
+            )
+        }).await?;
         
         match test_result {
-            Ok(_) => HttpResponse::Ok()
+            Ok(_) => Ok(HttpResponse::Ok()
                 .content_type("application/json")
-                .body(r#"{"success": true, "message": "Test email sent successfully!"}"#),
-            Err(e) => HttpResponse::Ok()
+                .body(r#"{"success": true, "message": "Test email sent successfully!"}"#)),
+            Err(e) => Ok(HttpResponse::Ok()
                 .content_type("application/json")
-                .body(format!(r#"{{"success": false, "message": "{}"}}"#, e.replace('"', "\\\""))),
+                .body(format!(r#"{{"success": false, "message": "{}"}}"#, e.replace('"', "\\\"")))),
         }
     } else {
-        HttpResponse::Ok()
+        Ok(HttpResponse::Ok()
             .content_type("application/json")
-            .body(r#"{"success": false, "message": "Could not load email settings"}"#)
+            .body(r#"{"success": false, "message": "Could not load email settings"}"#))
     }
 }
 
@@ -1133,121 +1228,351 @@ pub async fn test_email(
 pub async fn site_content_page(
     data: web::Data<AppState>,
     session: Session,
-) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
     }
     
-    let conn = data.db.lock().unwrap();
-    let content_by_section = db::get_site_content_by_section(&conn).unwrap_or_default();
+    let (content_by_section, revisions) = db::call(&data.db, |conn| {
+        Ok((
+            db::get_site_content_by_section(conn)?,
+            db::get_revisions(conn, RevisionKind::SiteContent, 0, REVISIONS_SHOWN)?,
+        ))
+    }).await?;
     
-    let mut context = tera::Context::new();
+    let mut context = admin_context(&session);
     context.insert("content_by_section", &content_by_section);
+    context.insert("revisions", &revisions);
     context.insert("page_title", "Site Content");
     
-    let rendered = data.tera.render("admin/site_content.html", &context).unwrap();
-    HttpResponse::Ok().content_type("text/html").body(rendered)
+    let rendered = data.tera.render("admin/site_content.html", &context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
 }
 
 pub async fn update_site_content(
     data: web::Data<AppState>,
     form: web::Form<std::collections::HashMap<String, String>>,
     session: Session,
-) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
     }
     
-    let conn = data.db.lock().unwrap();
+    let updates = form.into_inner();
+    let editor_id = auth::current_admin_id(&session);
+    db::call(&data.db, move |conn| db::update_site_content_batch(conn, &updates, editor_id)).await?;
+    log::info!("Site content updated successfully");
     
-    match db::update_site_content_batch(&conn, &form.into_inner()) {
-        Ok(_) => log::info!("Site content updated successfully"),
-        Err(e) => log::error!("Failed to update site content: {}", e),
+    Ok(HttpResponse::Found()
+        .append_header(("Location", "/admin/site-content"))
+        .finish())
+}
+
+// Revision history
+
+/// The edit page of the item a revision belongs to, which also lists its history.
+fn revision_edit_url(revision: &Revision) -> String {
+    match revision.kind {
+        RevisionKind::Blog => format!("/admin/blogs/edit/{}", revision.entity_id),
+        RevisionKind::Project => format!("/admin/projects/edit/{}", revision.entity_id),
+        RevisionKind::SiteContent => "/admin/site-content".to_string(),
+    }
+}
+
+/// A revision is open to whoever may edit the item it belongs to. `blog` is the
+/// post a blog revision belongs to.
+fn can_access_revision(session: &Session, revision: &Revision, blog: Option<&Blog>) -> bool {
+    match revision.kind {
+        RevisionKind::Blog => blog.is_some_and(|blog| can_edit_blog(session, blog)),
+        RevisionKind::Project | RevisionKind::SiteContent => auth::current_role(session) >= Some(Role::Editor),
+    }
+}
+
+/// Loads a revision along with the post it belongs to, if it is a blog revision.
+fn load_revision(conn: &rusqlite::Connection, id: i64) -> rusqlite::Result<Option<(Revision, Option<Blog>)>> {
+    let Some(revision) = db::get_revision(conn, id).optional()? else {
+        return Ok(None);
+    };
+    let blog = match revision.kind {
+        RevisionKind::Blog => db::get_blog_by_id(conn, revision.entity_id).optional()?,
+        _ => None,
+    };
+    Ok(Some((revision, blog)))
+}
+
+pub async fn revision_diff(
+    data: web::Data<AppState>,
+    query: web::Query<RevisionDiffQuery>,
+    session: Session,
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Author) {
+        return Ok(redirect);
     }
     
-    HttpResponse::Found()
-        .append_header(("Location", "/admin/site-content"))
-        .finish()
+    let RevisionDiffQuery { from, to } = query.into_inner();
+    let (from, to) = db::call(&data.db, move |conn| {
+        Ok((load_revision(conn, from.min(to))?, load_revision(conn, from.max(to))?))
+    }).await?;
+    
+    let (Some((from, blog)), Some((to, _))) = (from, to) else {
+        return Err(AppError::NotFound("Revision"));
+    };
+    if from.kind != to.kind || from.entity_id != to.entity_id {
+        return Err(AppError::NotFound("Revision"));
+    }
+    if !can_access_revision(&session, &from, blog.as_ref()) {
+        return Err(AppError::Forbidden);
+    }
+    
+    let changes = revisions::diff_snapshots(&from.snapshot, &to.snapshot);
+    
+    let mut context = admin_context(&session);
+    context.insert("from", &from);
+    context.insert("to", &to);
+    context.insert("changes", &changes);
+    context.insert("edit_url", &revision_edit_url(&from));
+    context.insert("page_title", "Compare Revisions");
+    
+    let rendered = data.tera.render("admin/revision_diff.html", &context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
+}
+
+pub async fn restore_revision(
+    data: web::Data<AppState>,
+    path: web::Path<i64>,
+    session: Session,
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Author) {
+        return Ok(redirect);
+    }
+    
+    let id = path.into_inner();
+    let loaded = db::call(&data.db, move |conn| load_revision(conn, id)).await?;
+    let Some((revision, blog)) = loaded else {
+        return Err(AppError::NotFound("Revision"));
+    };
+    if !can_access_revision(&session, &revision, blog.as_ref()) {
+        return Err(AppError::Forbidden);
+    }
+    
+    let editor_id = auth::current_admin_id(&session);
+    let may_publish = auth::current_role(&session) >= Some(Role::Editor);
+    let location = revision_edit_url(&revision);
+    if !db::call(&data.db, move |conn| db::restore_revision(conn, &revision, editor_id, may_publish)).await? {
+        return Err(AppError::NotFound("Item"));
+    }
+    
+    Ok(HttpResponse::Found()
+        .append_header(("Location", location))
+        .finish())
+}
+
+// Trash
+
+pub async fn trash_page(
+    data: web::Data<AppState>,
+    session: Session,
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
+    }
+    
+    let items = db::call(&data.db, |conn| db::get_trash(conn)).await?;
+    
+    let mut context = admin_context(&session);
+    context.insert("items", &items);
+    context.insert("retention_days", &data.trash_retention_days);
+    context.insert("page_title", "Trash");
+    
+    let rendered = data.tera.render("admin/trash.html", &context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
+}
+
+pub async fn restore_trash_item(
+    data: web::Data<AppState>,
+    path: web::Path<(String, i32)>,
+    session: Session,
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
+    }
+    
+    let (kind, id) = path.into_inner();
+    let kind = TrashKind::parse(&kind).ok_or(AppError::NotFound("Item"))?;
+    if !db::call(&data.db, move |conn| db::restore_trashed_item(conn, kind, id)).await? {
+        return Err(AppError::NotFound("Item"));
+    }
+    log::info!("Restored {} {} from the trash", kind.as_str(), id);
+    
+    Ok(HttpResponse::Found()
+        .append_header(("Location", "/admin/trash"))
+        .finish())
+}
+
+pub async fn purge_trash_item(
+    data: web::Data<AppState>,
+    path: web::Path<(String, i32)>,
+    session: Session,
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
+    }
+    
+    let (kind, id) = path.into_inner();
+    let kind = TrashKind::parse(&kind).ok_or(AppError::NotFound("Item"))?;
+    if !db::call(&data.db, move |conn| db::purge_item(conn, kind, id)).await? {
+        return Err(AppError::NotFound("Item"));
+    }
+    log::info!("Purged {} {} from the trash", kind.as_str(), id);
+    
+    Ok(HttpResponse::Found()
+        .append_header(("Location", "/admin/trash"))
+        .finish())
+}
+
+// Media library
+
+// Unused uploads younger than this are kept by "remove unused": an image uploaded
+// from an editor is only referenced once the form it was uploaded from is saved
+const UNUSED_MEDIA_GRACE_HOURS: i64 = 1;
+
+pub async fn media_page(
+    data: web::Data<AppState>,
+    query: web::Query<MediaQuery>,
+    session: Session,
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
+    }
+    
+    let items = db::call(&data.db, |conn| db::get_media(conn)).await?;
+    let total_size: i64 = items.iter().map(|item| item.size + item.variants_size).sum();
+    let unused_count = items.iter().filter(|item| item.usages.is_empty()).count();
+    
+    let mut context = admin_context(&session);
+    context.insert("items", &items);
+    context.insert("total_size", &total_size);
+    context.insert("unused_count", &unused_count);
+    context.insert("removed", &query.removed);
+    context.insert("grace_hours", &UNUSED_MEDIA_GRACE_HOURS);
+    context.insert("page_title", "Media");
+    
+    let rendered = data.tera.render("admin/media.html", &context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
+}
+
+pub async fn delete_media(
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    session: Session,
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
+    }
+    
+    let id = path.into_inner();
+    let image_id = id.clone();
+    if !blobs::delete_image(&data.db, &data.blobs, image_id).await? {
+        return Err(AppError::NotFound("Upload"));
+    }
+    log::info!("Deleted upload {}", id);
+    
+    Ok(HttpResponse::Found()
+        .append_header(("Location", "/admin/media"))
+        .finish())
+}
+
+pub async fn remove_unused_media(
+    data: web::Data<AppState>,
+    session: Session,
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
+    }
+    
+    let cutoff = (chrono::Utc::now() - chrono::Duration::hours(UNUSED_MEDIA_GRACE_HOURS)).format("%Y-%m-%d %H:%M:%S").to_string();
+    let removed = blobs::delete_unused_images(&data.db, &data.blobs, cutoff).await?;
+    log::info!("Removed {} unused uploads", removed);
+    
+    Ok(HttpResponse::Found()
+        .append_header(("Location", format!("/admin/media?removed={}", removed)))
+        .finish())
 }
 
 // Experience Management
 pub async fn experience_page(
     data: web::Data<AppState>,
     session: Session,
-) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
     }
     
-    let conn = data.db.lock().unwrap();
-    let experience = db::get_experience(&conn).unwrap_or_default();
+    let experience = db::call(&data.db, |conn| db::get_experience(conn)).await?;
     
-    let mut context = tera::Context::new();
+    let mut context = admin_context(&session);
     context.insert("experience", &experience);
     context.insert("page_title", "Experience");
     
-    let rendered = data.tera.render("admin/experience.html", &context).unwrap();
-    HttpResponse::Ok().content_type("text/html").body(rendered)
+    let rendered = data.tera.render("admin/experience.html", &context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
 }
 
 pub async fn add_experience_page(
     data: web::Data<AppState>,
     session: Session,
-) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
     }
     
-    let mut context = tera::Context::new();
+    let mut context = admin_context(&session);
     context.insert("page_title", "Add Experience");
     
-    let rendered = data.tera.render("admin/experience_form.html", &context).unwrap();
-    HttpResponse::Ok().content_type("text/html").body(rendered)
+    let rendered = data.tera.render("admin/experience_form.html", &context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
 }
 
 pub async fn add_experience(
     data: web::Data<AppState>,
     form: web::Form<ExperienceForm>,
     session: Session,
-) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
     }
     
-    let conn = data.db.lock().unwrap();
+    let form = form.into_inner();
+    db::call(&data.db, move |conn| db::add_experience(conn, &form)).await?;
+    log::info!("Experience added successfully");
     
-    match db::add_experience(&conn, &form.into_inner()) {
-        Ok(_) => log::info!("Experience added successfully"),
-        Err(e) => log::error!("Failed to add experience: {}", e),
-    }
-    
-    HttpResponse::Found()
+    Ok(HttpResponse::Found()
         .append_header(("Location", "/admin/experience"))
-        .finish()
+        .finish())
 }
 
 pub async fn edit_experience_page(
     data: web::Data<AppState>,
     path: web::Path<i32>,
     session: Session,
-) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
     }
     
-    let conn = data.db.lock().unwrap();
     let id = path.into_inner();
+    let experience = db::call(&data.db, move |conn| db::get_experience_by_id(conn, id).optional()).await?;
     
-    match db::get_experience_by_id(&conn, id) {
-        Ok(exp) => {
-            let mut context = tera::Context::new();
+    match experience {
+        Some(exp) => {
+            let mut context = admin_context(&session);
             context.insert("experience", &exp);
             context.insert("page_title", "Edit Experience");
             
-            let rendered = data.tera.render("admin/experience_form.html", &context).unwrap();
-            HttpResponse::Ok().content_type("text/html").body(rendered)
+            let rendered = data.tera.render("admin/experience_form.html", &context)?;
+            Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
         }
-        Err(_) => HttpResponse::NotFound().body("Experience not found"),
+        None => Err(AppError::NotFound("Experience")),
     }
 }
 
@@ -1256,124 +1581,115 @@ pub async fn update_experience(
     path: web::Path<i32>,
     form: web::Form<ExperienceForm>,
     session: Session,
-) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
     }
     
-    let conn = data.db.lock().unwrap();
     let id = path.into_inner();
-    
-    match db::update_experience(&conn, id, &form.into_inner()) {
-        Ok(_) => log::info!("Experience updated successfully"),
-        Err(e) => log::error!("Failed to update experience: {}", e),
+    let form = form.into_inner();
+    if !db::call(&data.db, move |conn| db::update_experience(conn, id, &form)).await? {
+        return Err(AppError::NotFound("Experience"));
     }
+    log::info!("Experience updated successfully");
     
-    HttpResponse::Found()
+    Ok(HttpResponse::Found()
         .append_header(("Location", "/admin/experience"))
-        .finish()
+        .finish())
 }
 
 pub async fn delete_experience(
     data: web::Data<AppState>,
     path: web::Path<i32>,
     session: Session,
-) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
     }
     
-    let conn = data.db.lock().unwrap();
     let id = path.into_inner();
+    db::call(&data.db, move |conn| db::trash_item(conn, TrashKind::Experience, id)).await?;
+    log::info!("Experience moved to the trash");
     
-    match db::delete_experience(&conn, id) {
-        Ok(_) => log::info!("Experience deleted successfully"),
-        Err(e) => log::error!("Failed to delete experience: {}", e),
-    }
-    
-    HttpResponse::Found()
+    Ok(HttpResponse::Found()
         .append_header(("Location", "/admin/experience"))
-        .finish()
+        .finish())
 }
 
 // Education Management
 pub async fn education_page(
     data: web::Data<AppState>,
     session: Session,
-) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
     }
     
-    let conn = data.db.lock().unwrap();
-    let education = db::get_education(&conn).unwrap_or_default();
+    let education = db::call(&data.db, |conn| db::get_education(conn)).await?;
     
-    let mut context = tera::Context::new();
+    let mut context = admin_context(&session);
     context.insert("education", &education);
     context.insert("page_title", "Education");
     
-    let rendered = data.tera.render("admin/education.html", &context).unwrap();
-    HttpResponse::Ok().content_type("text/html").body(rendered)
+    let rendered = data.tera.render("admin/education.html", &context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
 }
 
 pub async fn add_education_page(
     data: web::Data<AppState>,
     session: Session,
-) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
     }
     
-    let mut context = tera::Context::new();
+    let mut context = admin_context(&session);
     context.insert("page_title", "Add Education");
     
-    let rendered = data.tera.render("admin/education_form.html", &context).unwrap();
-    HttpResponse::Ok().content_type("text/html").body(rendered)
+    let rendered = data.tera.render("admin/education_form.html", &context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
 }
 
 pub async fn add_education(
     data: web::Data<AppState>,
     form: web::Form<EducationForm>,
     session: Session,
-) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
     }
     
-    let conn = data.db.lock().unwrap();
+    let form = form.into_inner();
+    db::call(&data.db, move |conn| db::add_education(conn, &form)).await?;
+    log::info!("Education added successfully");
     
-    match db::add_education(&conn, &form.into_inner()) {
-        Ok(_) => log::info!("Education added successfully"),
-        Err(e) => log::error!("Failed to add education: {}", e),
-    }
-    
-    HttpResponse::Found()
+    Ok(HttpResponse::Found()
         .append_header(("Location", "/admin/education"))
-        .finish()
+        .finish())
 }
 
 pub async fn edit_education_page(
     data: web::Data<AppState>,
     path: web::Path<i32>,
     session: Session,
-) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
     }
     
-    let conn = data.db.lock().unwrap();
     let id = path.into_inner();
+    let education = db::call(&data.db, move |conn| db::get_education_by_id(conn, id).optional()).await?;
     
-    match db::get_education_by_id(&conn, id) {
-        Ok(edu) => {
-            let mut context = tera::Context::new();
+    match education {
+        Some(edu) => {
+            let mut context = admin_context(&session);
             context.insert("education", &edu);
             context.insert("page_title", "Edit Education");
             
-            let rendered = data.tera.render("admin/education_form.html", &context).unwrap();
-            HttpResponse::Ok().content_type("text/html").body(rendered)
+            let rendered = data.tera.render("admin/education_form.html", &context)?;
+            Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
         }
-        Err(_) => HttpResponse::NotFound().body("Education not found"),
+        None => Err(AppError::NotFound("Education")),
     }
 }
 
@@ -1382,42 +1698,260 @@ pub async fn update_education(
     path: web::Path<i32>,
     form: web::Form<EducationForm>,
     session: Session,
-) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
     }
     
-    let conn = data.db.lock().unwrap();
     let id = path.into_inner();
-    
-    match db::update_education(&conn, id, &form.into_inner()) {
-        Ok(_) => log::info!("Education updated successfully"),
-        Err(e) => log::error!("Failed to update education: {}", e),
+    let form = form.into_inner();
+    if !db::call(&data.db, move |conn| db::update_education(conn, id, &form)).await? {
+        return Err(AppError::NotFound("Education"));
     }
+    log::info!("Education updated successfully");
     
-    HttpResponse::Found()
+    Ok(HttpResponse::Found()
         .append_header(("Location", "/admin/education"))
-        .finish()
+        .finish())
 }
 
 pub async fn delete_education(
     data: web::Data<AppState>,
     path: web::Path<i32>,
     session: Session,
-) -> HttpResponse {
-    if let Some(redirect) = require_auth(&session) {
-        return redirect;
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Editor) {
+        return Ok(redirect);
     }
     
-    let conn = data.db.lock().unwrap();
     let id = path.into_inner();
+    db::call(&data.db, move |conn| db::trash_item(conn, TrashKind::Education, id)).await?;
+    log::info!("Education moved to the trash");
+    
+    Ok(HttpResponse::Found()
+        .append_header(("Location", "/admin/education"))
+        .finish())
+}
+
+fn is_constraint_violation(e: &db::DbError) -> bool {
+    matches!(
+        e,
+        db::DbError::Sqlite(rusqlite::Error::SqliteFailure(err, _)) if err.code == rusqlite::ErrorCode::ConstraintViolation
+    )
+}
+
+// Account settings for the logged-in admin
+async fn render_account(
+    data: &web::Data<AppState>,
+    session: &Session,
+    error: Option<&str>,
+    success: bool,
+) -> Result<HttpResponse, AppError> {
+    let profile = db::call(&data.db, |conn| db::get_profile(conn)).await?;
+    
+    let mut context = admin_context(session);
+    context.insert("profile", &profile);
+    context.insert("page_title", "Account");
+    context.insert("min_password_length", &auth::MIN_PASSWORD_LENGTH);
+    context.insert("error", &error.unwrap_or_default());
+    context.insert("success", &success);
     
-    match db::delete_education(&conn, id) {
-        Ok(_) => log::info!("Education deleted successfully"),
-        Err(e) => log::error!("Failed to delete education: {}", e),
+    let rendered = data.tera.render("admin/account.html", &context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
+}
+
+pub async fn account_page(data: web::Data<AppState>, session: Session) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Author) {
+        return Ok(redirect);
     }
     
+    render_account(&data, &session, None, false).await
+}
+
+pub async fn update_account(
+    data: web::Data<AppState>,
+    form: web::Form<AccountForm>,
+    session: Session,
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Author) {
+        return Ok(redirect);
+    }
+    
+    let form = form.into_inner();
+    let username = form.username.trim().to_string();
+    if username.is_empty() {
+        return render_account(&data, &session, Some("Username is required."), false).await;
+    }
+    
+    let new_password = if form.new_password.is_empty() {
+        None
+    } else {
+        if form.new_password != form.confirm_password {
+            return render_account(&data, &session, Some("New passwords do not match."), false).await;
+        }
+        if let Some(error) = auth::password_problem(&form.new_password, &username) {
+            return render_account(&data, &session, Some(&error), false).await;
+        }
+        Some(form.new_password)
+    };
+    
+    let admin_id = auth::current_admin_id(&session).unwrap_or_default();
+    let current_password = form.current_password;
+    let name = username.clone();
+    // bcrypt runs on the blocking pool along with the update
+    let result = db::call(&data.db, move |conn| {
+        let admin = db::get_admin_by_id(conn, admin_id)?;
+        if !verify(&current_password, &admin.password_hash).unwrap_or(false) {
+            return Ok(None);
+        }
+        db::update_admin_credentials(conn, admin_id, &name, new_password.as_deref()).map(Some)
+    }).await;
+    
+    match result {
+        Ok(Some(session_version)) => {
+            log::info!("Admin {} updated their account; other sessions logged out", username);
+            auth::update_session_credentials(&session, &username, session_version)?;
+            render_account(&data, &session, None, true).await
+        }
+        Ok(None) => render_account(&data, &session, Some("Current password is incorrect."), false).await,
+        Err(e) if is_constraint_violation(&e) => {
+            render_account(&data, &session, Some("That username is already taken."), false).await
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+// User management (owners only)
+async fn render_users(
+    data: &web::Data<AppState>,
+    session: &Session,
+    error: Option<&str>,
+) -> Result<HttpResponse, AppError> {
+    let (profile, admins) = db::call(&data.db, |conn| {
+        Ok((db::get_profile(conn)?, db::get_admins(conn)?))
+    }).await?;
+    
+    let mut context = admin_context(session);
+    context.insert("profile", &profile);
+    context.insert("admins", &admins);
+    context.insert("roles", &Role::ALL);
+    context.insert("min_password_length", &auth::MIN_PASSWORD_LENGTH);
+    context.insert("error", &error.unwrap_or_default());
+    context.insert("page_title", "Users");
+    
+    let rendered = data.tera.render("admin/users.html", &context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
+}
+
+fn redirect_to_users() -> HttpResponse {
     HttpResponse::Found()
-        .append_header(("Location", "/admin/education"))
+        .append_header(("Location", "/admin/users"))
         .finish()
 }
+
+pub async fn users_page(data: web::Data<AppState>, session: Session) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Owner) {
+        return Ok(redirect);
+    }
+    
+    render_users(&data, &session, None).await
+}
+
+pub async fn add_user(
+    data: web::Data<AppState>,
+    form: web::Form<NewAdminForm>,
+    session: Session,
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Owner) {
+        return Ok(redirect);
+    }
+    
+    let form = form.into_inner();
+    let username = form.username.trim().to_string();
+    let role = match Role::parse(&form.role) {
+        Some(role) => role,
+        None => return render_users(&data, &session, Some("Unknown role.")).await,
+    };
+    if username.is_empty() {
+        return render_users(&data, &session, Some("Username is required.")).await;
+    }
+    if let Some(error) = auth::password_problem(&form.password, &username) {
+        return render_users(&data, &session, Some(&error)).await;
+    }
+    
+    let name = username.clone();
+    let created = db::call(&data.db, move |conn| db::create_admin(conn, &name, &form.password, role)).await;
+    match created {
+        Ok(()) => {
+            log::info!("Admin user {} created with role {}", username, role.as_str());
+            Ok(redirect_to_users())
+        }
+        Err(e) if is_constraint_violation(&e) => {
+            render_users(&data, &session, Some("That username is already taken.")).await
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn update_user_role(
+    data: web::Data<AppState>,
+    path: web::Path<i32>,
+    form: web::Form<RoleForm>,
+    session: Session,
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Owner) {
+        return Ok(redirect);
+    }
+    
+    let id = path.into_inner();
+    let role = match Role::parse(&form.role) {
+        Some(role) => role,
+        None => return render_users(&data, &session, Some("Unknown role.")).await,
+    };
+    
+    // Never leave the site without an owner
+    let updated = db::call(&data.db, move |conn| {
+        let target = db::get_admin_by_id(conn, id)?;
+        if target.role == Role::Owner && role != Role::Owner && db::count_owners(conn)? <= 1 {
+            return Ok(false);
+        }
+        db::update_admin_role(conn, id, role)?;
+        Ok(true)
+    }).await?;
+    
+    if !updated {
+        return render_users(&data, &session, Some("The last owner can't be demoted.")).await;
+    }
+    log::info!("Admin user {} is now {}", id, role.as_str());
+    Ok(redirect_to_users())
+}
+
+pub async fn delete_user(
+    data: web::Data<AppState>,
+    path: web::Path<i32>,
+    session: Session,
+) -> Result<HttpResponse, AppError> {
+    if let Some(redirect) = require_auth(&session, Role::Owner) {
+        return Ok(redirect);
+    }
+    
+    let id = path.into_inner();
+    if auth::current_admin_id(&session) == Some(id) {
+        return render_users(&data, &session, Some("You can't delete your own account.")).await;
+    }
+    
+    let deleted = db::call(&data.db, move |conn| {
+        let target = db::get_admin_by_id(conn, id)?;
+        if target.role == Role::Owner && db::count_owners(conn)? <= 1 {
+            return Ok(false);
+        }
+        db::delete_admin(conn, id)?;
+        Ok(true)
+    }).await?;
+    
+    if !deleted {
+        return render_users(&data, &session, Some("The last owner can't be deleted.")).await;
+    }
+    log::info!("Admin user {} deleted", id);
+    Ok(redirect_to_users())
+}
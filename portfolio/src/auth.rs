@@ -1,57 +1,381 @@
-use actix_web::{web, HttpResponse};
-use actix_session::Session;
+use actix_web::{web, HttpRequest, HttpResponse};
+use actix_web::body::MessageBody;
+use actix_web::dev::{ServiceRequest, ServiceResponse};
+use actix_web::middleware::Next;
+use actix_session::{Session, SessionExt, SessionInsertError};
 use bcrypt::verify;
 use crate::AppState;
+use crate::config::SessionConfig;
+use crate::csrf;
 use crate::db;
-use crate::models::LoginForm;
+use crate::error::AppError;
+use crate::models::{Admin, LoginForm, Role, SetupForm, TwoFactorForm};
+use crate::two_factor;
 
-pub async fn login_page(data: web::Data<AppState>, session: Session) -> HttpResponse {
+pub const MIN_PASSWORD_LENGTH: usize = 8;
+// Passwords at least this long may use a single kind of character (passphrases)
+const PASSPHRASE_LENGTH: usize = 16;
+// Checked against when the username is unknown, so that a miss costs as much
+// as a wrong password and timing doesn't reveal which usernames exist. It has
+// bcrypt's default cost, like every stored hash.
+const DUMMY_HASH: &str = "$2b$12$eE79fb12aM2Z4CbYfiRXo.Cie5dwRnuW25RN5zSD5a4EwkcyDEnRy";
+const COMMON_PASSWORDS: &[&str] = &[
+    "password", "password1", "password123", "passw0rd", "12345678", "123456789",
+    "1234567890", "qwerty123", "qwertyuiop", "iloveyou", "admin123", "administrator",
+    "letmein1", "welcome1", "changeme", "portfolio",
+];
+
+/// Returns why `password` is too weak for `username`, or `None` if it is acceptable.
+pub fn password_problem(password: &str, username: &str) -> Option<String> {
+    let length = password.chars().count();
+    if length < MIN_PASSWORD_LENGTH {
+        return Some(format!("Password must be at least {} characters.", MIN_PASSWORD_LENGTH));
+    }
+    
+    let lower = password.to_lowercase();
+    let username = username.trim().to_lowercase();
+    if !username.is_empty() && lower.contains(&username) {
+        return Some("Password must not contain the username.".to_string());
+    }
+    if COMMON_PASSWORDS.contains(&lower.as_str()) {
+        return Some("That password is too common.".to_string());
+    }
+    
+    let classes = [
+        password.chars().any(|c| c.is_lowercase()),
+        password.chars().any(|c| c.is_uppercase()),
+        password.chars().any(|c| c.is_ascii_digit()),
+        password.chars().any(|c| !c.is_alphanumeric()),
+    ];
+    if length < PASSPHRASE_LENGTH && classes.iter().filter(|&&present| present).count() < 3 {
+        return Some(format!(
+            "Use at least three of lowercase, uppercase, digits and symbols, or a passphrase of {}+ characters.",
+            PASSPHRASE_LENGTH
+        ));
+    }
+    None
+}
+
+pub async fn login_page(data: web::Data<AppState>, session: Session) -> Result<HttpResponse, AppError> {
     // If already logged in, redirect to admin
     if let Ok(Some(_)) = session.get::<String>("admin") {
-        return HttpResponse::Found()
+        return Ok(HttpResponse::Found()
             .append_header(("Location", "/admin"))
-            .finish();
+            .finish());
     }
     
-    let conn = data.db.lock().unwrap();
-    let profile = db::get_profile(&conn).unwrap();
+    let (admin_exists, profile) = db::call(&data.db, |conn| {
+        Ok((db::admin_exists(conn).unwrap_or(true), db::get_profile(conn)?))
+    }).await?;
+    
+    // Nobody can log in until the first admin has been created
+    if !admin_exists {
+        return Ok(HttpResponse::Found()
+            .append_header(("Location", "/admin/setup"))
+            .finish());
+    }
     
     let mut context = tera::Context::new();
     context.insert("profile", &profile);
     context.insert("page_title", "Admin Login");
-    context.insert("error", &false);
+    context.insert("csrf_token", &csrf::token(&session));
+    context.insert("error", "");
     
-    let rendered = data.tera.render("admin/login.html", &context).unwrap();
-    HttpResponse::Ok().content_type("text/html").body(rendered)
+    let rendered = data.tera.render("admin/login.html", &context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
+}
+
+enum LoginOutcome {
+    Success(Admin),
+    Failed,
+    /// Rejected without checking the password; holds the seconds left on the lockout.
+    Locked(i64),
+}
+
+/// Client address used for throttling. Forwarded headers are only honoured
+/// when the app is configured to sit behind a trusted reverse proxy.
+fn client_ip(req: &HttpRequest, trust_proxy_headers: bool) -> String {
+    let info = req.connection_info();
+    let ip = if trust_proxy_headers {
+        info.realip_remote_addr()
+    } else {
+        info.peer_addr()
+    };
+    ip.unwrap_or("unknown").to_string()
+}
+
+fn lockout_message(remaining_secs: i64) -> String {
+    let minutes = (remaining_secs + 59) / 60;
+    if minutes > 1 {
+        format!("Too many failed attempts. Try again in {} minutes.", minutes)
+    } else {
+        "Too many failed attempts. Try again in a minute.".to_string()
+    }
 }
 
 pub async fn login(
+    req: HttpRequest,
     data: web::Data<AppState>,
     form: web::Form<LoginForm>,
     session: Session,
-) -> HttpResponse {
-    let conn = data.db.lock().unwrap();
-    let profile = db::get_profile(&conn).unwrap();
-    
-    match db::get_admin(&conn, &form.username) {
-        Ok(admin) => {
-            if verify(&form.password, &admin.password_hash).unwrap_or(false) {
-                session.insert("admin", &admin.username).unwrap();
-                return HttpResponse::Found()
-                    .append_header(("Location", "/admin"))
-                    .finish();
+) -> Result<HttpResponse, AppError> {
+    let form = form.into_inner();
+    let ip = client_ip(&req, data.login_limits.trust_proxy_headers);
+    let limits = data.login_limits.clone();
+    
+    // bcrypt verification is CPU-bound, so it runs on the blocking pool too
+    let (profile, outcome) = db::call(&data.db, move |conn| {
+        let profile = db::get_profile(conn)?;
+        let now = chrono::Utc::now().timestamp();
+        // Throttle on a normalised name so case variants share one counter
+        let throttle_key = form.username.trim().to_lowercase();
+        let logged_name: String = form.username.chars().take(64).collect();
+        
+        let remaining = db::login_lockout_remaining(conn, &ip, &throttle_key, now)?;
+        if remaining > 0 {
+            db::record_login_attempt(conn, &ip, &logged_name, "locked")?;
+            return Ok((profile, LoginOutcome::Locked(remaining)));
+        }
+        
+        let admin = match db::get_admin(conn, &form.username) {
+            Ok(admin) if verify(&form.password, &admin.password_hash).unwrap_or(false) => Some(admin),
+            Ok(_) => None,
+            Err(_) => {
+                let _ = verify(&form.password, DUMMY_HASH);
+                None
+            }
+        };
+        
+        let outcome = match admin {
+            // With 2FA on, counters are only cleared once the second step succeeds
+            Some(admin) if admin.totp_secret.is_some() => LoginOutcome::Success(admin),
+            Some(admin) => {
+                db::clear_login_failures(conn, &ip, &throttle_key)?;
+                db::record_login_attempt(conn, &ip, &logged_name, "success")?;
+                LoginOutcome::Success(admin)
             }
+            None => {
+                db::register_login_failure(conn, &ip, &throttle_key, now, &limits)?;
+                db::record_login_attempt(conn, &ip, &logged_name, "failure")?;
+                LoginOutcome::Failed
+            }
+        };
+        Ok((profile, outcome))
+    }).await?;
+    
+    let error = match outcome {
+        LoginOutcome::Success(admin) if admin.totp_secret.is_some() => {
+            // Password is right, but the session only becomes an admin one after the second step
+            session.renew();
+            session.insert(PENDING_2FA_ADMIN_KEY, &admin.username)?;
+            session.insert(PENDING_2FA_AT_KEY, chrono::Utc::now().timestamp())?;
+            return Ok(HttpResponse::Found()
+                .append_header(("Location", "/admin/login/2fa"))
+                .finish());
         }
-        Err(_) => {}
-    }
+        LoginOutcome::Success(admin) => {
+            start_session(&session, &admin)?;
+            return Ok(HttpResponse::Found()
+                .append_header(("Location", "/admin"))
+                .finish());
+        }
+        LoginOutcome::Failed => "Authentication failed. Access denied.".to_string(),
+        LoginOutcome::Locked(remaining) => {
+            log::warn!("Login rejected for locked-out client (retry in {}s)", remaining);
+            lockout_message(remaining)
+        }
+    };
     
     let mut context = tera::Context::new();
     context.insert("profile", &profile);
     context.insert("page_title", "Admin Login");
-    context.insert("error", &true);
+    context.insert("csrf_token", &csrf::token(&session));
+    context.insert("error", &error);
+    
+    let rendered = data.tera.render("admin/login.html", &context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
+}
+
+// Session keys for a login that has passed the password check but still needs a 2FA code
+const PENDING_2FA_ADMIN_KEY: &str = "pending_2fa_admin";
+const PENDING_2FA_AT_KEY: &str = "pending_2fa_at";
+// How long the second step may take before the password has to be entered again
+const PENDING_2FA_TTL_SECS: i64 = 300;
+
+fn pending_two_factor(session: &Session) -> Option<String> {
+    let username = session.get::<String>(PENDING_2FA_ADMIN_KEY).unwrap_or(None)?;
+    let started_at = session.get::<i64>(PENDING_2FA_AT_KEY).unwrap_or(None).unwrap_or(0);
+    if chrono::Utc::now().timestamp() - started_at > PENDING_2FA_TTL_SECS {
+        session.remove(PENDING_2FA_ADMIN_KEY);
+        session.remove(PENDING_2FA_AT_KEY);
+        return None;
+    }
+    Some(username)
+}
+
+/// Checks an authenticator or recovery code for `admin` and consumes it, so
+/// the same code can never be accepted twice.
+pub fn check_second_factor(conn: &rusqlite::Connection, admin: &Admin, code: &str) -> rusqlite::Result<bool> {
+    let secret = match &admin.totp_secret {
+        Some(secret) => secret,
+        None => return Ok(false),
+    };
+    
+    if two_factor::is_totp_code(code) {
+        match two_factor::verify_code(secret, code, admin.totp_last_step) {
+            Some(step) => db::claim_totp_step(conn, admin.id, step),
+            None => Ok(false),
+        }
+    } else {
+        db::use_recovery_code(conn, admin.id, &two_factor::hash_recovery_code(code))
+    }
+}
+
+fn render_two_factor(data: &web::Data<AppState>, session: &Session, error: &str) -> Result<HttpResponse, AppError> {
+    let mut context = tera::Context::new();
+    context.insert("page_title", "Two-Factor Authentication");
+    context.insert("csrf_token", &csrf::token(session));
+    context.insert("error", error);
+    
+    let rendered = data.tera.render("admin/login_2fa.html", &context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
+}
+
+pub async fn two_factor_page(data: web::Data<AppState>, session: Session) -> Result<HttpResponse, AppError> {
+    if pending_two_factor(&session).is_none() {
+        return Ok(HttpResponse::Found()
+            .append_header(("Location", "/admin/login"))
+            .finish());
+    }
+    render_two_factor(&data, &session, "")
+}
+
+pub async fn two_factor_login(
+    req: HttpRequest,
+    data: web::Data<AppState>,
+    form: web::Form<TwoFactorForm>,
+    session: Session,
+) -> Result<HttpResponse, AppError> {
+    let username = match pending_two_factor(&session) {
+        Some(username) => username,
+        None => {
+            return Ok(HttpResponse::Found()
+                .append_header(("Location", "/admin/login"))
+                .finish());
+        }
+    };
+    
+    let ip = client_ip(&req, data.login_limits.trust_proxy_headers);
+    let limits = data.login_limits.clone();
+    let code = form.into_inner().code;
+    let name = username.clone();
+    
+    // Code guesses count against the same per-IP and per-username limits as passwords
+    let outcome = db::call(&data.db, move |conn| {
+        let now = chrono::Utc::now().timestamp();
+        let throttle_key = name.to_lowercase();
+        
+        let remaining = db::login_lockout_remaining(conn, &ip, &throttle_key, now)?;
+        if remaining > 0 {
+            db::record_login_attempt(conn, &ip, &name, "locked")?;
+            return Ok(LoginOutcome::Locked(remaining));
+        }
+        
+        let admin = db::get_admin(conn, &name)?;
+        if check_second_factor(conn, &admin, &code)? {
+            db::clear_login_failures(conn, &ip, &throttle_key)?;
+            db::record_login_attempt(conn, &ip, &name, "success")?;
+            Ok(LoginOutcome::Success(admin))
+        } else {
+            db::register_login_failure(conn, &ip, &throttle_key, now, &limits)?;
+            db::record_login_attempt(conn, &ip, &name, "2fa_failure")?;
+            Ok(LoginOutcome::Failed)
+        }
+    }).await?;
+    
+    match outcome {
+        LoginOutcome::Success(admin) => {
+            session.remove(PENDING_2FA_ADMIN_KEY);
+            session.remove(PENDING_2FA_AT_KEY);
+            start_session(&session, &admin)?;
+            Ok(HttpResponse::Found()
+                .append_header(("Location", "/admin"))
+                .finish())
+        }
+        LoginOutcome::Failed => render_two_factor(&data, &session, "Invalid authentication code."),
+        LoginOutcome::Locked(remaining) => {
+            log::warn!("2FA attempt rejected for locked-out client (retry in {}s)", remaining);
+            render_two_factor(&data, &session, &lockout_message(remaining))
+        }
+    }
+}
+
+fn render_setup(data: &web::Data<AppState>, session: &Session, username: &str, error: Option<&str>) -> Result<HttpResponse, AppError> {
+    let mut context = tera::Context::new();
+    context.insert("page_title", "First-Run Setup");
+    context.insert("csrf_token", &csrf::token(session));
+    context.insert("username", username);
+    context.insert("error", &error.unwrap_or_default());
     
-    let rendered = data.tera.render("admin/login.html", &context).unwrap();
-    HttpResponse::Ok().content_type("text/html").body(rendered)
+    let rendered = data.tera.render("admin/setup.html", &context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
+}
+
+pub async fn setup_page(data: web::Data<AppState>, session: Session) -> Result<HttpResponse, AppError> {
+    let admin_exists = db::call(&data.db, |conn| Ok(db::admin_exists(conn).unwrap_or(true))).await?;
+    
+    // The setup wizard is only reachable while no admin exists
+    if admin_exists {
+        return Ok(HttpResponse::Found()
+            .append_header(("Location", "/admin/login"))
+            .finish());
+    }
+    
+    render_setup(&data, &session, "", None)
+}
+
+pub async fn setup(
+    data: web::Data<AppState>,
+    form: web::Form<SetupForm>,
+    session: Session,
+) -> Result<HttpResponse, AppError> {
+    let username = form.username.trim().to_string();
+    
+    if username.is_empty() {
+        return render_setup(&data, &session, &username, Some("Username is required."));
+    }
+    if let Some(error) = password_problem(&form.password, &username) {
+        return render_setup(&data, &session, &username, Some(&error));
+    }
+    if form.password != form.confirm_password {
+        return render_setup(&data, &session, &username, Some("Passwords do not match."));
+    }
+    
+    let (new_username, password) = (username.clone(), form.into_inner().password);
+    let created = db::call(&data.db, move |conn| {
+        if !db::create_initial_admin(conn, &new_username, &password)? {
+            return Ok(None);
+        }
+        db::get_admin(conn, &new_username).map(Some)
+    }).await;
+    
+    match created {
+        Ok(Some(admin)) => {
+            log::info!("Initial admin created via setup wizard - username: {}", username);
+            start_session(&session, &admin)?;
+            Ok(HttpResponse::Found()
+                .append_header(("Location", "/admin"))
+                .finish())
+        }
+        // Someone else completed setup first; never overwrite their account
+        Ok(None) => Ok(HttpResponse::Found()
+            .append_header(("Location", "/admin/login"))
+            .finish()),
+        Err(e) => {
+            log::error!("Failed to create initial admin: {}", e);
+            render_setup(&data, &session, &username, Some("Could not create the admin account."))
+        }
+    }
 }
 
 pub async fn logout(session: Session) -> HttpResponse {
@@ -62,5 +386,160 @@ pub async fn logout(session: Session) -> HttpResponse {
 }
 
 pub fn is_authenticated(session: &Session) -> bool {
-    session.get::<String>("admin").unwrap_or(None).is_some()
+    current_username(session).is_some()
+}
+
+/// Username of the admin logged in on this session.
+pub fn current_username(session: &Session) -> Option<String> {
+    session.get::<String>("admin").unwrap_or(None)
+}
+
+pub fn current_admin_id(session: &Session) -> Option<i32> {
+    session.get::<i32>("admin_id").unwrap_or(None)
+}
+
+/// Role of the logged-in admin, kept current by `refresh_admin_session`.
+pub fn current_role(session: &Session) -> Option<Role> {
+    if !is_authenticated(session) {
+        return None;
+    }
+    session.get::<Role>("role").unwrap_or(None)
+}
+
+// How often `last_seen` is refreshed; avoids re-issuing the cookie on every request
+const LAST_SEEN_RESOLUTION_SECS: i64 = 60;
+
+/// Marks the session as logged in. The session is renewed and the CSRF token
+/// dropped first so nothing issued before authentication is reused after it.
+pub fn start_session(session: &Session, admin: &Admin) -> Result<(), SessionInsertError> {
+    let now = chrono::Utc::now().timestamp();
+    session.renew();
+    session.remove(csrf::SESSION_KEY);
+    session.insert("admin", &admin.username)?;
+    session.insert("admin_id", admin.id)?;
+    session.insert("role", admin.role)?;
+    session.insert("session_version", admin.session_version)?;
+    session.insert("login_at", now)?;
+    session.insert("last_seen", now)?;
+    Ok(())
+}
+
+/// Updates this session after its admin changed their own credentials, so it
+/// stays logged in while every other session is invalidated.
+pub fn update_session_credentials(session: &Session, username: &str, session_version: i64) -> Result<(), SessionInsertError> {
+    session.renew();
+    session.insert("admin", username)?;
+    session.insert("session_version", session_version)?;
+    Ok(())
+}
+
+fn expire_stale_session(session: &Session, config: &SessionConfig) {
+    if session.get::<String>("admin").unwrap_or(None).is_none() {
+        return;
+    }
+
+    let now = chrono::Utc::now().timestamp();
+    let login_at = session.get::<i64>("login_at").unwrap_or(None).unwrap_or(0);
+    let last_seen = session.get::<i64>("last_seen").unwrap_or(None).unwrap_or(0);
+
+    let expired = now - login_at > config.ttl_secs;
+    let idle = config.idle_timeout_secs > 0 && now - last_seen > config.idle_timeout_secs;
+
+    if expired || idle {
+        log::info!("Admin session ended ({})", if expired { "expired" } else { "idle timeout" });
+        session.purge();
+    } else if now - last_seen >= LAST_SEEN_RESOLUTION_SECS {
+        let _ = session.insert("last_seen", now);
+    }
+}
+
+/// Middleware that logs the admin out once the session TTL or idle timeout
+/// has passed, before any handler sees the request.
+pub async fn enforce_session_timeouts(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    if let Some(data) = req.app_data::<web::Data<AppState>>() {
+        expire_stale_session(&req.get_session(), &data.session_config);
+    }
+    next.call(req).await
+}
+
+/// Middleware that re-reads the logged-in admin on every admin request, so a
+/// role change takes effect immediately, and a deleted account or one whose
+/// credentials changed since this session started is logged out.
+pub async fn refresh_admin_session(
+    req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<impl MessageBody>, actix_web::Error> {
+    let session = req.get_session();
+    if req.path().starts_with("/admin") && is_authenticated(&session) {
+        let data = req.app_data::<web::Data<AppState>>().cloned();
+        match (current_admin_id(&session), data) {
+            (Some(admin_id), Some(data)) => {
+                let admin = db::call(&data.db, move |conn| Ok(db::get_admin_by_id(conn, admin_id).ok())).await.map_err(AppError::from)?;
+                let session_version = session.get::<i64>("session_version").unwrap_or(None).unwrap_or(0);
+                match admin {
+                    Some(admin) if admin.session_version != session_version => {
+                        log::info!("Logging out stale session of {} after a credential change", admin.username);
+                        session.purge();
+                    }
+                    Some(admin) => {
+                        if current_username(&session).as_deref() != Some(admin.username.as_str()) {
+                            session.insert("admin", &admin.username)?;
+                        }
+                        if session.get::<Role>("role").unwrap_or(None) != Some(admin.role) {
+                            session.insert("role", admin.role)?;
+                        }
+                    }
+                    None => {
+                        log::info!("Logging out session of deleted admin {}", admin_id);
+                        session.purge();
+                    }
+                }
+            }
+            // Sessions from before accounts had ids can't be checked; make them log in again
+            (None, _) => session.purge(),
+            (Some(_), None) => {}
+        }
+    }
+    next.call(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn accepts_mixed_passwords_and_long_passphrases() {
+        assert_eq!(password_problem("Tr0ub4dor&3", "ed"), None);
+        assert_eq!(password_problem("correct horse battery staple", "ed"), None);
+    }
+
+    #[test]
+    fn rejects_short_passwords() {
+        let problem = password_problem("Ab1!xyz", "ed").unwrap();
+        assert!(problem.contains("at least 8 characters"), "{}", problem);
+    }
+
+    #[test]
+    fn rejects_passwords_containing_the_username() {
+        let problem = password_problem("xEdward#2024", " Edward ").unwrap();
+        assert!(problem.contains("username"), "{}", problem);
+        // A blank username doesn't match everything
+        assert_eq!(password_problem("Tr0ub4dor&3", "  "), None);
+    }
+
+    #[test]
+    fn rejects_common_passwords_in_any_case() {
+        let problem = password_problem("Password123", "ed").unwrap();
+        assert!(problem.contains("too common"), "{}", problem);
+    }
+
+    #[test]
+    fn short_passwords_need_three_kinds_of_character() {
+        assert!(password_problem("lowercaseonly", "ed").is_some());
+        assert!(password_problem("lowercase123", "ed").is_some());
+        assert_eq!(password_problem("Lowercase123", "ed"), None);
+    }
 }
@@ -0,0 +1,411 @@
+use std::io;
+use std::path::PathBuf;
+use std::sync::Arc;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use crate::config::{BlobBackend, S3Config};
+use crate::db::{self, DbError, DbPool};
+use crate::images;
+
+/// Somewhere to keep the bytes of uploads, by key. The rows describing uploads
+/// (`images`, `image_variants`) hold the keys.
+pub trait BlobStore: Send + Sync {
+    /// Stores `data` under `key`, replacing anything already there.
+    fn put(&self, key: &str, data: &[u8]) -> io::Result<()>;
+    /// The bytes stored under `key`, or `None` if there are none.
+    fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>>;
+    /// Removes `key`; removing a missing key is not an error.
+    fn delete(&self, key: &str) -> io::Result<()>;
+}
+
+pub type SharedBlobStore = Arc<dyn BlobStore>;
+
+/// Opens the store for `backend`. The SQLite store uses connections from `pool`.
+pub fn open(backend: &BlobBackend, pool: &DbPool) -> io::Result<SharedBlobStore> {
+    Ok(match backend {
+        BlobBackend::Sqlite => Arc::new(SqliteBlobStore { pool: pool.clone() }),
+        BlobBackend::Filesystem { dir } => {
+            std::fs::create_dir_all(dir)?;
+            Arc::new(FsBlobStore { dir: dir.clone() })
+        }
+        BlobBackend::S3(config) => Arc::new(S3BlobStore::new(config.clone())),
+    })
+}
+
+/// The key new blobs are stored under: the hex SHA-256 of their content, so
+/// identical uploads share one blob.
+pub fn key_for(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+// SQLite
+
+/// Keeps blobs in the `blobs` table of the database.
+struct SqliteBlobStore {
+    pool: DbPool,
+}
+
+impl SqliteBlobStore {
+    fn conn(&self) -> io::Result<r2d2::PooledConnection<r2d2_sqlite::SqliteConnectionManager>> {
+        self.pool.get().map_err(io::Error::other)
+    }
+}
+
+impl BlobStore for SqliteBlobStore {
+    fn put(&self, key: &str, data: &[u8]) -> io::Result<()> {
+        self.conn()?
+            .execute("INSERT OR REPLACE INTO blobs (key, data) VALUES (?1, ?2)", rusqlite::params![key, data])
+            .map_err(io::Error::other)?;
+        Ok(())
+    }
+
+    fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        use rusqlite::OptionalExtension;
+        self.conn()?
+            .query_row("SELECT data FROM blobs WHERE key = ?1", [key], |row| row.get(0))
+            .optional()
+            .map_err(io::Error::other)
+    }
+
+    fn delete(&self, key: &str) -> io::Result<()> {
+        self.conn()?.execute("DELETE FROM blobs WHERE key = ?1", [key]).map_err(io::Error::other)?;
+        Ok(())
+    }
+}
+
+// Filesystem
+
+/// Keeps blobs as files under a directory, fanned out by the first two characters
+/// of their key (`ab/abcdef...`) so no directory grows too large.
+struct FsBlobStore {
+    dir: PathBuf,
+}
+
+impl FsBlobStore {
+    fn path(&self, key: &str) -> io::Result<PathBuf> {
+        // Keys become file names, so nothing that could climb out of the directory
+        let valid = key.len() >= 3 && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-');
+        if !valid {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("invalid blob key '{}'", key)));
+        }
+        Ok(self.dir.join(&key[..2]).join(key))
+    }
+}
+
+impl BlobStore for FsBlobStore {
+    fn put(&self, key: &str, data: &[u8]) -> io::Result<()> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        // Written aside and renamed into place, so a reader never sees half a file
+        let partial = path.with_extension(format!("{}.partial", uuid::Uuid::new_v4()));
+        std::fs::write(&partial, data)?;
+        std::fs::rename(&partial, &path).inspect_err(|_| {
+            let _ = std::fs::remove_file(&partial);
+        })
+    }
+
+    fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        match std::fs::read(self.path(key)?) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn delete(&self, key: &str) -> io::Result<()> {
+        match std::fs::remove_file(self.path(key)?) {
+            Err(e) if e.kind() != io::ErrorKind::NotFound => Err(e),
+            _ => Ok(()),
+        }
+    }
+}
+
+// S3
+
+/// Keeps blobs as objects in an S3-compatible bucket, addressed path-style
+/// (`{endpoint}/{bucket}/{key}`) as MinIO expects, with requests signed using
+/// AWS Signature Version 4.
+struct S3BlobStore {
+    config: S3Config,
+    host: String,
+    agent: ureq::Agent,
+}
+
+impl S3BlobStore {
+    fn new(config: S3Config) -> Self {
+        let host = config
+            .endpoint
+            .split_once("://")
+            .map_or(config.endpoint.as_str(), |(_, rest)| rest)
+            .split('/')
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        let tls = ureq::tls::TlsConfig::builder()
+            .provider(ureq::tls::TlsProvider::NativeTls)
+            .build();
+        let agent = ureq::Agent::config_builder()
+            .tls_config(tls)
+            .http_status_as_error(false)
+            .timeout_global(Some(std::time::Duration::from_secs(60)))
+            .build()
+            .into();
+        S3BlobStore { config, host, agent }
+    }
+
+    /// Sends a signed request for the object `key`, returning the status and body.
+    fn request(&self, method: &str, key: &str, body: &[u8]) -> io::Result<(u16, Vec<u8>)> {
+        let path = format!("/{}/{}", self.config.bucket, key);
+        let url = format!("{}{}", self.config.endpoint, path);
+        let now = chrono::Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let payload_hash = format!("{:x}", Sha256::digest(body));
+        let authorization = self.authorization(method, &path, &amz_date, &payload_hash);
+
+        let request = ureq::http::Request::builder()
+            .method(method)
+            .uri(&url)
+            .header("x-amz-date", &amz_date)
+            .header("x-amz-content-sha256", &payload_hash)
+            .header("Authorization", authorization)
+            .body(body)
+            .map_err(io::Error::other)?;
+        let response = self.agent.run(request).map_err(io::Error::other)?;
+
+        let status = response.status().as_u16();
+        let data = response
+            .into_body()
+            .with_config()
+            .limit(u64::MAX)
+            .read_to_vec()
+            .map_err(io::Error::other)?;
+        Ok((status, data))
+    }
+
+    /// The `Authorization` header for a request signing the host, date and payload hash.
+    fn authorization(&self, method: &str, path: &str, amz_date: &str, payload_hash: &str) -> String {
+        const SIGNED_HEADERS: &str = "host;x-amz-content-sha256;x-amz-date";
+        let date = &amz_date[..8];
+        let scope = format!("{}/{}/s3/aws4_request", date, self.config.region);
+
+        let canonical_request = format!(
+            "{}\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+            method, path, self.host, payload_hash, amz_date, SIGNED_HEADERS, payload_hash
+        );
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{:x}",
+            amz_date, scope, Sha256::digest(canonical_request.as_bytes())
+        );
+
+        let secret = format!("AWS4{}", self.config.secret_access_key);
+        let mut key = hmac_sha256(secret.as_bytes(), date.as_bytes());
+        for part in [self.config.region.as_str(), "s3", "aws4_request"] {
+            key = hmac_sha256(&key, part.as_bytes());
+        }
+        let signature: String = hmac_sha256(&key, string_to_sign.as_bytes())
+            .iter()
+            .map(|byte| format!("{:02x}", byte))
+            .collect();
+
+        format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.config.access_key_id, scope, SIGNED_HEADERS, signature
+        )
+    }
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn s3_error(method: &str, key: &str, status: u16, body: &[u8]) -> io::Error {
+    io::Error::other(format!(
+        "S3 {} {} failed with status {}: {}",
+        method, key, status, String::from_utf8_lossy(body)
+    ))
+}
+
+impl BlobStore for S3BlobStore {
+    fn put(&self, key: &str, data: &[u8]) -> io::Result<()> {
+        match self.request("PUT", key, data)? {
+            (200..=299, _) => Ok(()),
+            (status, body) => Err(s3_error("PUT", key, status, &body)),
+        }
+    }
+
+    fn get(&self, key: &str) -> io::Result<Option<Vec<u8>>> {
+        match self.request("GET", key, &[])? {
+            (200..=299, data) => Ok(Some(data)),
+            (404, _) => Ok(None),
+            (status, body) => Err(s3_error("GET", key, status, &body)),
+        }
+    }
+
+    fn delete(&self, key: &str) -> io::Result<()> {
+        match self.request("DELETE", key, &[])? {
+            (200..=299, _) | (404, _) => Ok(()),
+            (status, body) => Err(s3_error("DELETE", key, status, &body)),
+        }
+    }
+}
+
+// Uploads
+
+/// One encoding of an upload whose bytes are in the blob store.
+pub struct StoredBlob {
+    pub key: String,
+    pub size: usize,
+    pub content_type: String,
+    pub width: Option<u32>,
+}
+
+/// Writes one encoding to the store under its content hash.
+pub fn put_encoding(store: &dyn BlobStore, content_type: &str, width: Option<u32>, data: &[u8]) -> io::Result<StoredBlob> {
+    let key = key_for(data);
+    store.put(&key, data)?;
+    Ok(StoredBlob { key, size: data.len(), content_type: content_type.to_string(), width })
+}
+
+/// Writes a processed image and its variants to the store, returning the image
+/// and the variants in the order given.
+pub fn put_processed(store: &dyn BlobStore, image: &images::ProcessedImage) -> io::Result<(StoredBlob, Vec<StoredBlob>)> {
+    let original = put_encoding(store, image.original.content_type, Some(image.original.width), &image.original.data)?;
+    let variants = image
+        .variants
+        .iter()
+        .map(|variant| put_encoding(store, variant.content_type, Some(variant.width), &variant.data))
+        .collect::<io::Result<_>>()?;
+    Ok((original, variants))
+}
+
+/// Removes blobs that no row refers to any more. Failures only leave a stray
+/// blob behind, so they are logged rather than returned.
+pub fn delete_unreferenced(store: &dyn BlobStore, keys: &[String]) {
+    for key in keys {
+        if let Err(e) = store.delete(key) {
+            log::warn!("Failed to delete blob {}: {}", key, e);
+        }
+    }
+}
+
+// Blobs are shared between uploads with identical content, so an upload writing a
+// blob and then inserting its row must not interleave with a deletion checking
+// that blob for references and then removing it. Uploads hold this for reading
+// from the write until the row is in; deletions hold it for writing from the
+// check until the blobs are gone.
+static REFERENCES: tokio::sync::RwLock<()> = tokio::sync::RwLock::const_new(());
+
+// Blob I/O runs on the blocking thread pool, outside any database connection
+async fn blocking<T, F>(f: F) -> Result<T, DbError>
+where
+    F: FnOnce() -> io::Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    actix_web::web::block(f)
+        .await
+        .map_err(|_| DbError::Blocking)?
+        .map_err(DbError::Blob)
+}
+
+/// Stores an upload and returns its id. JPEG, PNG and WebP images are scaled
+/// down to the size cap and stored with their responsive variants; other files
+/// are kept as uploaded.
+pub async fn save_image(
+    pool: &DbPool,
+    store: &SharedBlobStore,
+    filename: String,
+    content_type: String,
+    data: Vec<u8>,
+) -> Result<String, DbError> {
+    let _references = REFERENCES.read().await;
+    let store = store.clone();
+    let (original, height, variants) = blocking(move || match images::process(&data) {
+        Some(image) => {
+            let (original, variants) = put_processed(store.as_ref(), &image)?;
+            Ok((original, Some(image.height), variants))
+        }
+        None => Ok((put_encoding(store.as_ref(), &content_type, None, &data)?, None, Vec::new())),
+    })
+    .await?;
+
+    let id = uuid::Uuid::new_v4().to_string();
+    let image_id = id.clone();
+    db::call(pool, move |conn| db::insert_image(conn, &image_id, &filename, &original, height, &variants)).await?;
+    Ok(id)
+}
+
+/// Content type and blob key of what to send for `/images/{id}?w=`; see `images::choose_encoding`.
+pub async fn find_image(
+    pool: &DbPool,
+    id: String,
+    requested_width: Option<u32>,
+    accepts_webp: bool,
+) -> Result<Option<(String, String)>, DbError> {
+    db::call(pool, move |conn| {
+        let encodings = db::get_image_encodings(conn, &id)?;
+        match images::choose_encoding(&encodings, requested_width, accepts_webp) {
+            Some(encoding) => db::get_image_blob(conn, &id, encoding).map(Some),
+            None => Ok(None),
+        }
+    })
+    .await
+}
+
+/// Filename, content type and blob key of an upload as stored, for `/files/{id}`.
+pub async fn find_file(pool: &DbPool, id: String) -> Result<Option<(String, String, String)>, DbError> {
+    use rusqlite::OptionalExtension;
+    db::call(pool, move |conn| db::get_image_file(conn, &id).optional()).await
+}
+
+/// The bytes stored under `key`, or `None` if the store has lost them.
+pub async fn read(store: &SharedBlobStore, key: String) -> Result<Option<Vec<u8>>, DbError> {
+    let store = store.clone();
+    blocking(move || store.get(&key)).await
+}
+
+/// The SHA-256 of a blob's content: its key, unless the key dates from before
+/// blobs were keyed by content (see migration 13).
+pub fn content_hash(key: &str, data: &[u8]) -> String {
+    if is_content_key(key) {
+        key.to_string()
+    } else {
+        key_for(data)
+    }
+}
+
+/// Whether `key` is a content hash, as given by `key_for`.
+pub fn is_content_key(key: &str) -> bool {
+    key.len() == 64 && key.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// Deletes an upload, its variants and any blobs nothing else uses. Returns false if there was no such upload.
+pub async fn delete_image(pool: &DbPool, store: &SharedBlobStore, id: String) -> Result<bool, DbError> {
+    let _references = REFERENCES.write().await;
+    let Some(freed) = db::call(pool, move |conn| db::delete_image(conn, &id)).await? else {
+        return Ok(false);
+    };
+    let store = store.clone();
+    blocking(move || {
+        delete_unreferenced(store.as_ref(), &freed);
+        Ok(())
+    })
+    .await?;
+    Ok(true)
+}
+
+/// Deletes the uploads nothing refers to, see `db::delete_unused_media`. Returns how many went.
+pub async fn delete_unused_images(pool: &DbPool, store: &SharedBlobStore, uploaded_before: String) -> Result<usize, DbError> {
+    let _references = REFERENCES.write().await;
+    let (removed, freed) = db::call(pool, move |conn| db::delete_unused_media(conn, &uploaded_before)).await?;
+    let store = store.clone();
+    blocking(move || {
+        delete_unreferenced(store.as_ref(), &freed);
+        Ok(())
+    })
+    .await?;
+    Ok(removed)
+}
@@ -0,0 +1,192 @@
+use crate::auth;
+use crate::blobs;
+use crate::config::BlobBackend;
+use crate::db;
+use crate::images;
+use crate::migrations;
+
+const USAGE: &str = "Usage:
+  portfolio                      Start the web server
+  portfolio migrate              Apply pending schema migrations and exit
+  portfolio migrate --dry-run    Show which migrations would be applied without writing anything
+  portfolio migrate status       List all migrations and when they were applied
+  portfolio create-admin <name>  Create the first admin account (password is read from stdin)
+  portfolio disable-2fa <name>   Turn off two-factor authentication for an admin who lost their device
+  portfolio process-images       Resize and generate variants for images uploaded before processing existed;
+                                 stop the server first, as it removes blobs the old encodings no longer need
+  portfolio move-blobs <from> <to>  Move uploaded files between blob stores (sqlite, fs, s3); stop the server first";
+
+fn to_io_error(e: rusqlite::Error) -> std::io::Error {
+    std::io::Error::other(e)
+}
+
+/// Runs a maintenance subcommand if one was given on the command line.
+/// Returns `None` when the server should start normally.
+pub fn run(args: &[String]) -> Option<std::io::Result<()>> {
+    let command = args.first()?;
+    let rest = &args[1..];
+
+    let result = match command.as_str() {
+        "migrate" => migrate(rest),
+        "create-admin" => create_admin(rest),
+        "disable-2fa" => disable_two_factor(rest),
+        "process-images" => process_images(),
+        "move-blobs" => move_blobs(rest),
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            Ok(())
+        }
+        other => {
+            eprintln!("Unknown command: {}\n\n{}", other, USAGE);
+            Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "unknown command"))
+        }
+    };
+    Some(result)
+}
+
+fn migrate(args: &[String]) -> std::io::Result<()> {
+    let mut conn = db::open_db().map_err(to_io_error)?;
+
+    match args.first().map(String::as_str) {
+        None => {
+            let applied = migrations::run(&mut conn, false).map_err(to_io_error)?;
+            if applied.is_empty() {
+                println!("Database schema is up to date.");
+            } else {
+                println!("Applied {} migration(s): {:?}", applied.len(), applied);
+            }
+        }
+        Some("--dry-run") => {
+            let pending = migrations::run(&mut conn, true).map_err(to_io_error)?;
+            if pending.is_empty() {
+                println!("Database schema is up to date.");
+            } else {
+                println!("Would apply {} migration(s): {:?}", pending.len(), pending);
+            }
+        }
+        Some("status") => {
+            for m in migrations::status(&conn).map_err(to_io_error)? {
+                match m.applied_at {
+                    Some(at) => println!("  [x] {:>4}  {:<32} applied {}", m.version, m.name, at),
+                    None => println!("  [ ] {:>4}  {:<32} pending", m.version, m.name),
+                }
+            }
+        }
+        Some(other) => {
+            eprintln!("Unknown migrate option: {}\n\n{}", other, USAGE);
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "unknown option"));
+        }
+    }
+
+    Ok(())
+}
+
+fn create_admin(args: &[String]) -> std::io::Result<()> {
+    let username = match args.first() {
+        Some(name) if !name.trim().is_empty() => name.trim(),
+        _ => {
+            eprintln!("Missing username\n\n{}", USAGE);
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "missing username"));
+        }
+    };
+
+    let mut password = String::new();
+    std::io::stdin().read_line(&mut password)?;
+    let password = password.trim_end_matches(['\r', '\n']);
+    if let Some(problem) = auth::password_problem(password, username) {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, problem));
+    }
+
+    let mut conn = db::open_db().map_err(to_io_error)?;
+    db::init_db(&mut conn).map_err(to_io_error)?;
+    if db::create_initial_admin(&conn, username, password).map_err(to_io_error)? {
+        println!("Admin account '{}' created.", username);
+        Ok(())
+    } else {
+        Err(std::io::Error::other("an admin account already exists; credentials were not changed"))
+    }
+}
+
+fn disable_two_factor(args: &[String]) -> std::io::Result<()> {
+    let username = match args.first() {
+        Some(name) => name,
+        None => {
+            eprintln!("Missing username\n\n{}", USAGE);
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "missing username"));
+        }
+    };
+
+    let mut conn = db::open_db().map_err(to_io_error)?;
+    migrations::run(&mut conn, false).map_err(to_io_error)?;
+    let admin = db::get_admin(&conn, username)
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::NotFound, format!("no admin named '{}'", username)))?;
+    db::disable_totp(&conn, admin.id).map_err(to_io_error)?;
+    println!("Two-factor authentication disabled for '{}'.", username);
+    Ok(())
+}
+
+/// A connection pool on an up-to-date schema, for commands that touch the blob store.
+fn open_pool() -> std::io::Result<db::DbPool> {
+    let pool = db::create_pool().map_err(std::io::Error::other)?;
+    let mut conn = pool.get().map_err(std::io::Error::other)?;
+    migrations::run(&mut conn, false).map_err(to_io_error)?;
+    Ok(pool)
+}
+
+fn process_images() -> std::io::Result<()> {
+    let pool = open_pool()?;
+    let store = blobs::open(&BlobBackend::from_env()?, &pool)?;
+    let conn = pool.get().map_err(std::io::Error::other)?;
+    let ids = db::get_unprocessed_image_ids(&conn).map_err(to_io_error)?;
+    let mut processed = 0;
+    for id in &ids {
+        let (_, _, key) = db::get_image_file(&conn, id).map_err(to_io_error)?;
+        let image = store.get(&key)?.and_then(|data| images::process(&data));
+        let Some(image) = image else {
+            println!("  skipped {} (not a readable JPEG, PNG or WebP)", id);
+            continue;
+        };
+        let (original, variants) = blobs::put_processed(store.as_ref(), &image)?;
+        let freed = db::replace_image_encodings(&conn, id, &original, Some(image.height), &variants).map_err(to_io_error)?;
+        blobs::delete_unreferenced(store.as_ref(), &freed);
+        processed += 1;
+    }
+    println!("Processed {} of {} image(s).", processed, ids.len());
+    Ok(())
+}
+
+fn move_blobs(args: &[String]) -> std::io::Result<()> {
+    let (Some(from), Some(to)) = (args.first(), args.get(1)) else {
+        eprintln!("Missing store names\n\n{}", USAGE);
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "missing store names"));
+    };
+    let from = BlobBackend::named(from)?;
+    let to = BlobBackend::named(to)?;
+    if from.name() == to.name() {
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidInput, "source and destination are the same store"));
+    }
+
+    let pool = open_pool()?;
+    let source = blobs::open(&from, &pool)?;
+    let destination = blobs::open(&to, &pool)?;
+    let conn = pool.get().map_err(std::io::Error::other)?;
+    let keys = db::get_blob_keys(&conn).map_err(to_io_error)?;
+    let mut moved = 0;
+    for key in &keys {
+        // Already moved by an earlier, interrupted run
+        let Some(data) = source.get(key)? else {
+            continue;
+        };
+        // Blobs copied from before the blob store existed get content-hash keys on the way
+        let new_key = blobs::key_for(&data);
+        destination.put(&new_key, &data)?;
+        if &new_key != key {
+            db::rename_blob_key(&conn, key, &new_key).map_err(to_io_error)?;
+        }
+        source.delete(key)?;
+        moved += 1;
+    }
+    println!("Moved {} of {} blob(s) from {} to {}.", moved, keys.len(), from.name(), to.name());
+    println!("Set BLOB_STORE={} before starting the server again.", to.name());
+    Ok(())
+}
@@ -0,0 +1,267 @@
+use actix_web::cookie::{Key, SameSite};
+use base64::{engine::general_purpose::STANDARD, Engine};
+
+fn env_bool(name: &str, default: bool) -> bool {
+    match std::env::var(name) {
+        Ok(v) => matches!(v.trim().to_ascii_lowercase().as_str(), "1" | "true" | "yes" | "on"),
+        Err(_) => default,
+    }
+}
+
+fn env_i64(name: &str, default: i64) -> i64 {
+    std::env::var(name)
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(default)
+}
+
+/// Cookie and lifetime settings for the admin session.
+#[derive(Debug, Clone)]
+pub struct SessionConfig {
+    pub cookie_secure: bool,
+    pub cookie_http_only: bool,
+    pub cookie_same_site: SameSite,
+    /// Absolute lifetime of a login, in seconds.
+    pub ttl_secs: i64,
+    /// Inactivity after which the admin is logged out, in seconds. `0` disables it.
+    pub idle_timeout_secs: i64,
+}
+
+impl SessionConfig {
+    pub fn from_env() -> Self {
+        let cookie_same_site = match std::env::var("COOKIE_SAME_SITE")
+            .unwrap_or_default()
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "strict" => SameSite::Strict,
+            "none" => SameSite::None,
+            _ => SameSite::Lax,
+        };
+
+        SessionConfig {
+            cookie_secure: env_bool("COOKIE_SECURE", false),
+            cookie_http_only: env_bool("COOKIE_HTTP_ONLY", true),
+            cookie_same_site,
+            ttl_secs: env_i64("SESSION_TTL_HOURS", 24).max(1) * 3600,
+            idle_timeout_secs: env_i64("SESSION_IDLE_TIMEOUT_MINUTES", 60).max(0) * 60,
+        }
+    }
+}
+
+/// Brute-force limits for the admin login form.
+#[derive(Debug, Clone)]
+pub struct LoginLimits {
+    /// Failures allowed per IP or username before backoff starts.
+    pub free_attempts: i64,
+    /// Delay after the first failure past `free_attempts`; doubles with each further failure.
+    pub base_delay_secs: i64,
+    /// Longest lockout a run of failures can earn, in seconds.
+    pub max_lockout_secs: i64,
+    /// A failure counter is forgotten after this long without new failures, in seconds.
+    pub reset_after_secs: i64,
+    /// Take the client IP from `X-Forwarded-For`/`Forwarded`; only safe behind a trusted proxy.
+    pub trust_proxy_headers: bool,
+}
+
+impl LoginLimits {
+    pub fn from_env() -> Self {
+        LoginLimits {
+            free_attempts: env_i64("LOGIN_FREE_ATTEMPTS", 5).max(1),
+            base_delay_secs: env_i64("LOGIN_BACKOFF_BASE_SECONDS", 2).max(1),
+            max_lockout_secs: env_i64("LOGIN_LOCKOUT_MAX_MINUTES", 15).max(1) * 60,
+            reset_after_secs: 24 * 3600,
+            trust_proxy_headers: env_bool("TRUST_PROXY_HEADERS", false),
+        }
+    }
+
+    /// How long to lock out a client that has just reached `failures` consecutive failures.
+    pub fn lockout_secs(&self, failures: i64) -> i64 {
+        let over = failures - self.free_attempts;
+        if over <= 0 {
+            return 0;
+        }
+        // Cap the exponent so the shift can't overflow; the max lockout clamps long before that
+        let delay = self.base_delay_secs.saturating_mul(1 << (over - 1).min(30));
+        delay.min(self.max_lockout_secs)
+    }
+}
+
+/// Public base URL of the site from `SITE_URL`, without a trailing slash.
+/// Used for absolute links in feeds and the sitemap; when unset the request's host is used.
+pub fn site_url() -> Option<String> {
+    std::env::var("SITE_URL")
+        .ok()
+        .map(|url| url.trim().trim_end_matches('/').to_string())
+        .filter(|url| !url.is_empty())
+}
+
+/// Lifetime of a draft preview link from `PREVIEW_LINK_HOURS`, in seconds.
+pub fn preview_link_ttl_secs() -> i64 {
+    env_i64("PREVIEW_LINK_HOURS", 72).max(1) * 3600
+}
+
+/// How many days deleted items stay in the trash from `TRASH_RETENTION_DAYS`;
+/// `None` (set to 0) keeps them until they are purged by hand.
+pub fn trash_retention_days() -> Option<i64> {
+    Some(env_i64("TRASH_RETENTION_DAYS", 30)).filter(|days| *days > 0)
+}
+
+/// Connection settings for an S3-compatible bucket (AWS S3, MinIO, R2, ...).
+#[derive(Debug, Clone)]
+pub struct S3Config {
+    /// Base URL of the service, e.g. `https://s3.eu-west-1.amazonaws.com` or `http://localhost:9000`.
+    pub endpoint: String,
+    pub bucket: String,
+    pub region: String,
+    pub access_key_id: String,
+    pub secret_access_key: String,
+}
+
+/// Where the bytes of uploads are kept.
+#[derive(Debug, Clone)]
+pub enum BlobBackend {
+    /// The `blobs` table of the database itself.
+    Sqlite,
+    /// Files under a directory, named by their content hash.
+    Filesystem { dir: std::path::PathBuf },
+    S3(S3Config),
+}
+
+impl BlobBackend {
+    /// The backend chosen with `BLOB_STORE` (default `sqlite`).
+    pub fn from_env() -> std::io::Result<Self> {
+        Self::named(&std::env::var("BLOB_STORE").unwrap_or_else(|_| "sqlite".to_string()))
+    }
+
+    /// The backend called `name` (`sqlite`, `fs` or `s3`), configured from the environment:
+    /// `BLOB_DIR` for `fs`, and `S3_ENDPOINT`, `S3_BUCKET`, `S3_REGION`,
+    /// `S3_ACCESS_KEY_ID` and `S3_SECRET_ACCESS_KEY` for `s3`.
+    pub fn named(name: &str) -> std::io::Result<Self> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "sqlite" => Ok(BlobBackend::Sqlite),
+            "fs" => {
+                let dir = std::env::var("BLOB_DIR")
+                    .map(std::path::PathBuf::from)
+                    .unwrap_or_else(|_| data_dir().join("blobs"));
+                Ok(BlobBackend::Filesystem { dir })
+            }
+            "s3" => {
+                let required = |var: &str| {
+                    std::env::var(var)
+                        .ok()
+                        .filter(|value| !value.trim().is_empty())
+                        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, format!("{} must be set for BLOB_STORE=s3", var)))
+                };
+                Ok(BlobBackend::S3(S3Config {
+                    endpoint: required("S3_ENDPOINT")?.trim().trim_end_matches('/').to_string(),
+                    bucket: required("S3_BUCKET")?,
+                    region: std::env::var("S3_REGION").unwrap_or_else(|_| "us-east-1".to_string()),
+                    access_key_id: required("S3_ACCESS_KEY_ID")?,
+                    secret_access_key: required("S3_SECRET_ACCESS_KEY")?,
+                }))
+            }
+            other => Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidInput,
+                format!("unknown blob store '{}' (expected sqlite, fs or s3)", other),
+            )),
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            BlobBackend::Sqlite => "sqlite",
+            BlobBackend::Filesystem { .. } => "fs",
+            BlobBackend::S3(_) => "s3",
+        }
+    }
+}
+
+// Directory holding the database, where generated files go so they land on the same persistent volume
+fn data_dir() -> std::path::PathBuf {
+    let db_path = std::env::var("DATABASE_URL").unwrap_or_else(|_| "portfolio.db".to_string());
+    std::path::Path::new(&db_path)
+        .parent()
+        .map(std::path::Path::to_path_buf)
+        .unwrap_or_default()
+}
+
+fn key_file_path() -> std::path::PathBuf {
+    if let Ok(path) = std::env::var("SESSION_KEY_FILE") {
+        return path.into();
+    }
+    data_dir().join("session.key")
+}
+
+fn decode_key(encoded: &str) -> std::io::Result<Key> {
+    let bytes = STANDARD
+        .decode(encoded.trim())
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    Key::try_from(bytes.as_slice())
+        .map_err(|_| std::io::Error::new(std::io::ErrorKind::InvalidData, "session key must be at least 64 bytes"))
+}
+
+/// Loads the cookie signing key from `SESSION_KEY` (base64, at least 64 bytes).
+/// Falls back to a key file, generating and persisting one on first start so
+/// that sessions survive restarts.
+pub fn load_session_key() -> std::io::Result<Key> {
+    if let Ok(encoded) = std::env::var("SESSION_KEY") {
+        log::info!("Using session key from SESSION_KEY");
+        return decode_key(&encoded);
+    }
+
+    let path = key_file_path();
+    if path.exists() {
+        log::info!("Using session key from {}", path.display());
+        return decode_key(&std::fs::read_to_string(&path)?);
+    }
+
+    let key = Key::generate();
+    std::fs::write(&path, STANDARD.encode(key.master()))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(0o600))?;
+    }
+    log::info!("Generated new session key at {}", path.display());
+    Ok(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limits() -> LoginLimits {
+        LoginLimits {
+            free_attempts: 5,
+            base_delay_secs: 2,
+            max_lockout_secs: 15 * 60,
+            reset_after_secs: 24 * 3600,
+            trust_proxy_headers: false,
+        }
+    }
+
+    #[test]
+    fn free_attempts_are_not_locked_out() {
+        let limits = limits();
+        assert_eq!(limits.lockout_secs(0), 0);
+        assert_eq!(limits.lockout_secs(5), 0);
+    }
+
+    #[test]
+    fn lockout_doubles_after_free_attempts() {
+        let limits = limits();
+        assert_eq!(limits.lockout_secs(6), 2);
+        assert_eq!(limits.lockout_secs(7), 4);
+        assert_eq!(limits.lockout_secs(8), 8);
+        assert_eq!(limits.lockout_secs(13), 256);
+    }
+
+    #[test]
+    fn lockout_is_capped_without_overflowing() {
+        let limits = limits();
+        assert_eq!(limits.lockout_secs(15), 15 * 60);
+        assert_eq!(limits.lockout_secs(100), 15 * 60);
+        assert_eq!(limits.lockout_secs(i64::MAX), 15 * 60);
+    }
+}
@@ -0,0 +1,224 @@
+use actix_session::{Session, SessionExt};
+use actix_web::HttpMessage;
+use actix_web::body::{EitherBody, MessageBody};
+use actix_web::dev::{Payload, ServiceRequest, ServiceResponse};
+use actix_web::error::{ErrorForbidden, ErrorPayloadTooLarge, PayloadError};
+use actix_web::http::{header, Method};
+use actix_web::middleware::Next;
+use actix_web::web::{Bytes, BytesMut};
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use futures_util::{Stream, StreamExt};
+use rand::RngCore;
+use std::pin::Pin;
+use crate::uploads;
+
+/// Session key holding the token; also the name of the hidden form field.
+pub const SESSION_KEY: &str = "csrf_token";
+/// Header used by AJAX requests instead of the form field.
+pub const HEADER_NAME: &str = "X-CSRF-Token";
+
+// Largest urlencoded body buffered while looking for the token. Multipart forms
+// are not buffered: their token is read from the first part, and forms put it there.
+const MAX_FORM_BODY: usize = uploads::MAX_TEXT_BYTES;
+// How far into a multipart body the token part may start
+const MAX_MULTIPART_PREFIX: usize = 64 * 1024;
+
+/// Returns the CSRF token for this session, creating one on first use.
+pub fn token(session: &Session) -> String {
+    if let Ok(Some(token)) = session.get::<String>(SESSION_KEY) {
+        return token;
+    }
+
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    let token = URL_SAFE_NO_PAD.encode(bytes);
+    if let Err(e) = session.insert(SESSION_KEY, &token) {
+        log::error!("Failed to store CSRF token in session: {}", e);
+    }
+    token
+}
+
+pub fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0u8, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+async fn read_body(payload: &mut Payload, limit: usize) -> Result<Bytes, actix_web::Error> {
+    let mut body = BytesMut::new();
+    while let Some(chunk) = payload.next().await {
+        let chunk = chunk?;
+        if body.len() + chunk.len() > limit {
+            return Err(ErrorPayloadTooLarge("Request body is too large"));
+        }
+        body.extend_from_slice(&chunk);
+    }
+    Ok(body.freeze())
+}
+
+/// Takes the submitted token from the request body, leaving the body for the
+/// handler. Urlencoded forms are read whole; multipart forms only until the
+/// `csrf_token` part, and the rest streams through untouched.
+async fn token_from_body(req: &mut ServiceRequest) -> Result<Option<String>, actix_web::Error> {
+    let content_type = req
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .unwrap_or_default()
+        .to_string();
+    let mut payload = req.take_payload();
+
+    if content_type.starts_with("application/x-www-form-urlencoded") {
+        let body = read_body(&mut payload, MAX_FORM_BODY).await?;
+        let token = serde_urlencoded::from_bytes::<Vec<(String, String)>>(&body)
+            .ok()
+            .and_then(|fields| fields.into_iter().find(|(name, _)| name == SESSION_KEY))
+            .map(|(_, value)| value);
+        req.set_payload(Payload::from(body));
+        return Ok(token);
+    }
+
+    let Some(boundary) = content_type
+        .starts_with("multipart/form-data")
+        .then(|| multipart_boundary(&content_type))
+        .flatten()
+    else {
+        return Ok(None);
+    };
+
+    let mut prefix = BytesMut::new();
+    let mut token = None;
+    while token.is_none() && prefix.len() < MAX_MULTIPART_PREFIX {
+        match payload.next().await {
+            Some(chunk) => {
+                prefix.extend_from_slice(&chunk?);
+                token = token_from_multipart_prefix(&prefix, &boundary);
+            }
+            None => break,
+        }
+    }
+    let prefix = prefix.freeze();
+    let body: Pin<Box<dyn Stream<Item = Result<Bytes, PayloadError>>>> = Box::pin(futures_util::stream::once(async move { Ok(prefix) }).chain(payload));
+    req.set_payload(Payload::from(body));
+    Ok(token)
+}
+
+fn multipart_boundary(content_type: &str) -> Option<String> {
+    content_type
+        .split(';')
+        .filter_map(|param| param.trim().strip_prefix("boundary="))
+        .map(|boundary| boundary.trim_matches('"').to_string())
+        .find(|boundary| !boundary.is_empty())
+}
+
+/// Finds the `csrf_token` part in the start of a multipart body. Returns `None`
+/// until that part has arrived in full, or if the body ends without one.
+fn token_from_multipart_prefix(body: &[u8], boundary: &str) -> Option<String> {
+    let delimiter = format!("--{}", boundary);
+    let part_end = format!("\r\n--{}", boundary);
+    let start = find(body, delimiter.as_bytes())?;
+    let mut rest = &body[start + delimiter.len()..];
+    // Each part: CRLF, headers, a blank line, the content, then CRLF and the next delimiter
+    while !rest.starts_with(b"--") {
+        let headers_end = find(rest, b"\r\n\r\n")?;
+        let headers = std::str::from_utf8(&rest[..headers_end]).ok()?;
+        let content = &rest[headers_end + 4..];
+        let content_end = find(content, part_end.as_bytes())?;
+        if is_token_part(headers) {
+            return String::from_utf8(content[..content_end].to_vec()).ok();
+        }
+        rest = &content[content_end + part_end.len()..];
+    }
+    None
+}
+
+fn is_token_part(headers: &str) -> bool {
+    let field = format!("name=\"{}\"", SESSION_KEY);
+    headers.lines().any(|line| {
+        line.to_ascii_lowercase().starts_with("content-disposition:")
+            && line.split(';').skip(1).any(|param| param.trim() == field)
+    })
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+/// Middleware that rejects any POST under `/admin` whose token does not match
+/// the one stored in the session. The token is taken from the `X-CSRF-Token`
+/// header when present, otherwise from the `csrf_token` form field; the body
+/// is handed on to the handler unchanged.
+pub async fn verify_csrf(
+    mut req: ServiceRequest,
+    next: Next<impl MessageBody>,
+) -> Result<ServiceResponse<EitherBody<impl MessageBody>>, actix_web::Error> {
+    let path = req.path();
+    if req.method() != Method::POST || !(path == "/admin" || path.starts_with("/admin/")) {
+        return next.call(req).await.map(ServiceResponse::map_into_left_body);
+    }
+
+    let expected = req.get_session().get::<String>(SESSION_KEY).unwrap_or(None);
+    let header_token = req
+        .headers()
+        .get(HEADER_NAME)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string);
+
+    let submitted = match header_token {
+        Some(token) => Some(token),
+        None => token_from_body(&mut req).await?,
+    };
+
+    match (expected, submitted) {
+        (Some(expected), Some(submitted)) if constant_time_eq(expected.as_bytes(), submitted.as_bytes()) => {
+            next.call(req).await.map(ServiceResponse::map_into_left_body)
+        }
+        _ => {
+            log::warn!("Rejected POST {}: missing or invalid CSRF token", req.path());
+            // Returned as a response rather than an error so the error page renders it
+            let error = ErrorForbidden("Invalid or missing CSRF token. Reload the page and try again.");
+            Ok(req.error_response(error).map_into_right_body())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BOUNDARY: &str = "----form42";
+
+    fn part(name: &str, value: &str) -> String {
+        format!("--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n", BOUNDARY, name, value)
+    }
+
+    #[test]
+    fn finds_token_in_first_part() {
+        let body = format!("{}{}--{}--\r\n", part("csrf_token", "abc"), part("title", "Hello"), BOUNDARY);
+        assert_eq!(token_from_multipart_prefix(body.as_bytes(), BOUNDARY).as_deref(), Some("abc"));
+    }
+
+    #[test]
+    fn finds_token_after_other_parts() {
+        let body = format!("{}{}--{}--\r\n", part("title", "name=\"csrf_token\""), part("csrf_token", "abc"), BOUNDARY);
+        assert_eq!(token_from_multipart_prefix(body.as_bytes(), BOUNDARY).as_deref(), Some("abc"));
+    }
+
+    #[test]
+    fn waits_for_the_whole_token_part() {
+        let body = part("csrf_token", "abcdef");
+        let cut = body.find("def").unwrap();
+        assert_eq!(token_from_multipart_prefix(&body.as_bytes()[..cut], BOUNDARY), None);
+    }
+
+    #[test]
+    fn ignores_similar_field_names() {
+        let body = format!("{}--{}--\r\n", part("csrf_token_old", "abc"), BOUNDARY);
+        assert_eq!(token_from_multipart_prefix(body.as_bytes(), BOUNDARY), None);
+    }
+
+    #[test]
+    fn reads_boundary_from_content_type() {
+        assert_eq!(multipart_boundary("multipart/form-data; boundary=abc").as_deref(), Some("abc"));
+        assert_eq!(multipart_boundary("multipart/form-data; boundary=\"a b\"").as_deref(), Some("a b"));
+        assert_eq!(multipart_boundary("multipart/form-data"), None);
+    }
+}
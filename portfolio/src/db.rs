@@ -1,152 +1,105 @@
-use rusqlite::{Connection, Result};
-use bcrypt::{hash, DEFAULT_COST};
+use rusqlite::{Connection, OptionalExtension, Result};
+use r2d2_sqlite::SqliteConnectionManager;
 use crate::models::{EducationForm, EmailSettings, EmailSettingsForm, SiteContentItem};
+use crate::blobs::StoredBlob;
+use crate::config::LoginLimits;
+use crate::images;
+use crate::migrations;
+
+pub type DbPool = r2d2::Pool<SqliteConnectionManager>;
+
+/// Errors from running a query on the connection pool.
+#[derive(Debug)]
+pub enum DbError {
+    Pool(r2d2::Error),
+    Sqlite(rusqlite::Error),
+    /// Reading or writing the blob store holding upload bytes failed.
+    Blob(std::io::Error),
+    Blocking,
+}
 
-pub fn init_db() -> Result<Connection> {
-    let db_path = std::env::var("DATABASE_URL").unwrap_or_else(|_| "portfolio.db".to_string());
-    log::info!("Using database at: {}", db_path);
-    let conn = Connection::open(&db_path)?;
-    
-    // Create tables
+impl std::fmt::Display for DbError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DbError::Pool(e) => write!(f, "database pool error: {}", e),
+            DbError::Sqlite(e) => write!(f, "database error: {}", e),
+            DbError::Blob(e) => write!(f, "blob store error: {}", e),
+            DbError::Blocking => write!(f, "database task was cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for DbError {}
+
+impl From<r2d2::Error> for DbError {
+    fn from(e: r2d2::Error) -> Self {
+        DbError::Pool(e)
+    }
+}
+
+impl From<rusqlite::Error> for DbError {
+    fn from(e: rusqlite::Error) -> Self {
+        DbError::Sqlite(e)
+    }
+}
+
+fn database_path() -> String {
+    std::env::var("DATABASE_URL").unwrap_or_else(|_| "portfolio.db".to_string())
+}
+
+// WAL lets readers proceed while a write is in progress; the busy timeout
+// makes concurrent writers wait for each other instead of failing.
+fn configure_connection(conn: &mut Connection) -> Result<()> {
     conn.execute_batch(
-        "
-        -- Profile table
-        CREATE TABLE IF NOT EXISTS profile (
-            id INTEGER PRIMARY KEY,
-            name TEXT NOT NULL,
-            title TEXT NOT NULL,
-            bio TEXT NOT NULL,
-            email TEXT NOT NULL,
-            phone TEXT,
-            location TEXT,
-            github_url TEXT,
-            linkedin_url TEXT,
-            twitter_url TEXT,
-            resume_url TEXT,
-            avatar_url TEXT
-        );
-
-        -- Skills table
-        CREATE TABLE IF NOT EXISTS skills (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            name TEXT NOT NULL,
-            category TEXT NOT NULL,
-            proficiency INTEGER DEFAULT 80,
-            icon TEXT,
-            icon_url TEXT
-        );
-
-        -- Projects table
-        CREATE TABLE IF NOT EXISTS projects (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            title TEXT NOT NULL,
-            slug TEXT NOT NULL UNIQUE,
-            description TEXT NOT NULL,
-            content TEXT,
-            image_url TEXT,
-            demo_url TEXT,
-            github_url TEXT,
-            technologies TEXT,
-            featured INTEGER DEFAULT 0,
-            created_at TEXT NOT NULL
-        );
-
-        -- Blogs table
-        CREATE TABLE IF NOT EXISTS blogs (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            title TEXT NOT NULL,
-            slug TEXT NOT NULL UNIQUE,
-            excerpt TEXT NOT NULL,
-            content TEXT NOT NULL,
-            image_url TEXT,
-            tags TEXT,
-            published INTEGER DEFAULT 0,
-            created_at TEXT NOT NULL,
-            updated_at TEXT NOT NULL
-        );
-
-        -- Experience table
-        CREATE TABLE IF NOT EXISTS experience (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            company TEXT NOT NULL,
-            position TEXT NOT NULL,
-            description TEXT,
-            start_date TEXT NOT NULL,
-            end_date TEXT,
-            current INTEGER DEFAULT 0
-        );
-
-        -- Education table
-        CREATE TABLE IF NOT EXISTS education (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            institution TEXT NOT NULL,
-            degree TEXT NOT NULL,
-            field TEXT NOT NULL,
-            start_date TEXT NOT NULL,
-            end_date TEXT,
-            description TEXT
-        );
-
-        -- Messages table (contact form)
-        CREATE TABLE IF NOT EXISTS messages (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            name TEXT NOT NULL,
-            email TEXT NOT NULL,
-            subject TEXT NOT NULL,
-            message TEXT NOT NULL,
-            read INTEGER DEFAULT 0,
-            created_at TEXT NOT NULL
-        );
-
-        -- Admin table
-        CREATE TABLE IF NOT EXISTS admin (
-            id INTEGER PRIMARY KEY,
-            username TEXT NOT NULL UNIQUE,
-            password_hash TEXT NOT NULL
-        );
-
-        -- Images table for storing uploaded images
-        CREATE TABLE IF NOT EXISTS images (
-            id TEXT PRIMARY KEY,
-            filename TEXT NOT NULL,
-            content_type TEXT NOT NULL,
-            data BLOB NOT NULL,
-            created_at TEXT NOT NULL
-        );
-
-        -- Services table
-        CREATE TABLE IF NOT EXISTS services (
-            id INTEGER PRIMARY KEY AUTOINCREMENT,
-            name TEXT NOT NULL,
-            description TEXT,
-            image_url TEXT,
-            icon TEXT,
-            order_index INTEGER DEFAULT 0
-        );
-
-        -- Email Settings table
-        CREATE TABLE IF NOT EXISTS email_settings (
-            id INTEGER PRIMARY KEY CHECK (id = 1),
-            smtp_server TEXT NOT NULL DEFAULT '',
-            smtp_port INTEGER NOT NULL DEFAULT 587,
-            smtp_username TEXT NOT NULL DEFAULT '',
-            smtp_password TEXT NOT NULL DEFAULT '',
-            notification_email TEXT NOT NULL DEFAULT '',
-            enabled INTEGER NOT NULL DEFAULT 0
-        );
-
-        -- Site Content table for dynamic text
-        CREATE TABLE IF NOT EXISTS site_content (
-            key TEXT PRIMARY KEY,
-            value TEXT NOT NULL DEFAULT '',
-            section TEXT NOT NULL DEFAULT 'general',
-            description TEXT DEFAULT ''
-        );
-        "
-    )?;
+        "PRAGMA journal_mode = WAL;
+         PRAGMA synchronous = NORMAL;
+         PRAGMA busy_timeout = 5000;
+         PRAGMA foreign_keys = ON;",
+    )
+}
+
+pub fn open_db() -> Result<Connection> {
+    let db_path = database_path();
+    log::info!("Using database at: {}", db_path);
+    let mut conn = Connection::open(&db_path)?;
+    configure_connection(&mut conn)?;
+    Ok(conn)
+}
+
+pub fn create_pool() -> std::result::Result<DbPool, r2d2::Error> {
+    let db_path = database_path();
+    let pool_size = std::env::var("DATABASE_POOL_SIZE")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(8);
+    log::info!("Using database at: {} (pool size {})", db_path, pool_size);
+
+    let manager = SqliteConnectionManager::file(&db_path).with_init(configure_connection);
+    r2d2::Pool::builder().max_size(pool_size).build(manager)
+}
+
+/// Runs `f` with a pooled connection on the blocking thread pool, so SQLite
+/// work never stalls the async workers.
+pub async fn call<F, T>(pool: &DbPool, f: F) -> std::result::Result<T, DbError>
+where
+    F: FnOnce(&mut Connection) -> Result<T> + Send + 'static,
+    T: Send + 'static,
+{
+    let pool = pool.clone();
+    actix_web::web::block(move || {
+        let mut conn = pool.get()?;
+        f(&mut conn).map_err(DbError::from)
+    })
+    .await
+    .map_err(|_| DbError::Blocking)?
+}
+
+pub fn init_db(conn: &mut Connection) -> Result<()> {
+    // Bring the schema up to date before touching any tables
+    migrations::run(conn, false)?;
 
     // Insert default site content
-    init_default_site_content(&conn)?;
+    init_default_site_content(conn)?;
 
     // Insert default profile if not exists
     let count: i32 = conn.query_row(
@@ -167,29 +120,9 @@ pub fn init_db() -> Result<Connection> {
         )?;
     }
 
-    // Insert default admin if not exists, or update existing admin credentials
-    let admin_count: i32 = conn.query_row(
-        "SELECT COUNT(*) FROM admin",
-        [],
-        |row| row.get(0)
-    )?;
-
-    if admin_count == 0 {
-        let password_hash = hash("Melucifer2022", DEFAULT_COST).expect("Failed to hash password");
-        conn.execute(
-            "INSERT INTO admin (id, username, password_hash) VALUES (1, 'aiya', ?1)",
-            [&password_hash],
-        )?;
-        log::info!("Default admin created - username: aiya");
-    } else {
-        // Update existing admin credentials (remove this block after first deployment)
-        let password_hash = hash("Melucifer2022", DEFAULT_COST).expect("Failed to hash password");
-        conn.execute(
-            "UPDATE admin SET username = 'aiya', password_hash = ?1 WHERE id = 1",
-            [&password_hash],
-        )?;
-        log::info!("Admin credentials updated - username: aiya");
-    }
+    // The first admin is created through /admin/setup, the `create-admin`
+    // command, or ADMIN_USERNAME/ADMIN_PASSWORD for headless deployments.
+    bootstrap_admin_from_env(conn)?;
 
     // Insert sample skills if empty
     let skills_count: i32 = conn.query_row(
@@ -233,22 +166,22 @@ pub fn init_db() -> Result<Connection> {
         let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
         
         conn.execute(
-            "INSERT INTO projects (title, slug, description, content, image_url, demo_url, github_url, technologies, featured, created_at)
+            "INSERT INTO projects (title, slug, description, content, image_url, demo_url, github_url, technologies, featured, created_at, updated_at)
              VALUES ('Portfolio Website', 'portfolio-website', 
              'A modern portfolio website built with Rust Actix Web and Tailwind CSS',
              '## Overview\n\nThis portfolio website showcases my projects, skills, and blog posts. Built with modern technologies for optimal performance.\n\n## Features\n\n- Responsive dark theme design\n- Admin panel for content management\n- Blog with markdown support\n- Project showcase\n- Contact form',
              '/static/images/portfolio.jpg', 'https://example.com', 'https://github.com/mdjobayerarafat/portfolio',
-             'Rust, Actix Web, SQLite, Tailwind CSS, Tera', 1, ?1)",
+             'Rust, Actix Web, SQLite, Tailwind CSS, Tera', 1, ?1, ?1)",
             [&now],
         )?;
 
         conn.execute(
-            "INSERT INTO projects (title, slug, description, content, image_url, demo_url, github_url, technologies, featured, created_at)
+            "INSERT INTO projects (title, slug, description, content, image_url, demo_url, github_url, technologies, featured, created_at, updated_at)
              VALUES ('AI Chat Application', 'ai-chat-app', 
              'An intelligent chat application powered by machine learning',
              '## Overview\n\nA real-time chat application with AI-powered responses and natural language processing capabilities.\n\n## Features\n\n- Real-time messaging\n- AI-powered responses\n- Natural language understanding\n- Multi-language support',
              '/static/images/ai-chat.jpg', 'https://example.com/chat', 'https://github.com/mdjobayerarafat/ai-chat',
-             'Python, FastAPI, React, TensorFlow, WebSocket', 1, ?1)",
+             'Python, FastAPI, React, TensorFlow, WebSocket', 1, ?1, ?1)",
             [&now],
         )?;
     }
@@ -281,6 +214,10 @@ pub fn init_db() -> Result<Connection> {
             [&now, &now],
         )?;
     }
+    
+    if projects_count == 0 || blogs_count == 0 {
+        retag_all(conn)?;
+    }
 
     // Insert sample experience
     let exp_count: i32 = conn.query_row(
@@ -351,7 +288,7 @@ pub fn init_db() -> Result<Connection> {
         )?;
     }
 
-    Ok(conn)
+    Ok(())
 }
 
 use crate::models::*;
@@ -394,7 +331,7 @@ pub fn update_profile(conn: &Connection, profile: &ProfileForm) -> Result<()> {
 }
 
 pub fn get_skills(conn: &Connection) -> Result<Vec<Skill>> {
-    let mut stmt = conn.prepare("SELECT id, name, category, proficiency, icon, COALESCE(icon_url, '') FROM skills ORDER BY category, name")?;
+    let mut stmt = conn.prepare("SELECT id, name, category, proficiency, icon, COALESCE(icon_url, '') FROM skills WHERE deleted_at IS NULL ORDER BY category, name")?;
     let skills = stmt.query_map([], |row| {
         Ok(Skill {
             id: row.get(0)?,
@@ -416,272 +353,495 @@ pub fn add_skill(conn: &Connection, skill: &SkillForm) -> Result<()> {
     Ok(())
 }
 
-pub fn delete_skill(conn: &Connection, id: i32) -> Result<()> {
-    conn.execute("DELETE FROM skills WHERE id = ?1", [id])?;
-    Ok(())
+// Columns read by `project_from_row`; trashed projects are left out
+const PROJECT_SELECT: &str = "SELECT id, title, slug, description, content, image_url, demo_url, github_url, technologies,
+        featured, created_at, seo_title, seo_description, updated_at
+    FROM projects WHERE deleted_at IS NULL";
+
+fn project_from_row(row: &rusqlite::Row) -> Result<Project> {
+    Ok(Project {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        slug: row.get(2)?,
+        description: row.get(3)?,
+        content: row.get(4)?,
+        image_url: row.get(5)?,
+        demo_url: row.get(6)?,
+        github_url: row.get(7)?,
+        technologies: row.get(8)?,
+        featured: row.get(9)?,
+        created_at: row.get(10)?,
+        seo_title: row.get(11)?,
+        seo_description: row.get(12)?,
+        updated_at: row.get(13)?,
+    })
 }
 
 pub fn get_projects(conn: &Connection) -> Result<Vec<Project>> {
-    let mut stmt = conn.prepare("SELECT id, title, slug, description, content, image_url, demo_url, github_url, technologies, featured, created_at FROM projects ORDER BY created_at DESC")?;
-    let projects = stmt.query_map([], |row| {
-        Ok(Project {
-            id: row.get(0)?,
-            title: row.get(1)?,
-            slug: row.get(2)?,
-            description: row.get(3)?,
-            content: row.get(4)?,
-            image_url: row.get(5)?,
-            demo_url: row.get(6)?,
-            github_url: row.get(7)?,
-            technologies: row.get(8)?,
-            featured: row.get(9)?,
-            created_at: row.get(10)?,
-        })
-    })?;
+    let mut stmt = conn.prepare(&format!("{} ORDER BY created_at DESC", PROJECT_SELECT))?;
+    let projects = stmt.query_map([], project_from_row)?;
+    projects.collect()
+}
+
+pub fn get_projects_page(conn: &Connection, limit: i64, offset: i64) -> Result<Vec<Project>> {
+    let mut stmt = conn.prepare(&format!("{} ORDER BY created_at DESC LIMIT ?1 OFFSET ?2", PROJECT_SELECT))?;
+    let projects = stmt.query_map([limit, offset], project_from_row)?;
     projects.collect()
 }
 
+pub fn count_projects(conn: &Connection) -> Result<i64> {
+    conn.query_row("SELECT COUNT(*) FROM projects WHERE deleted_at IS NULL", [], |row| row.get(0))
+}
+
 pub fn get_featured_projects(conn: &Connection) -> Result<Vec<Project>> {
-    let mut stmt = conn.prepare("SELECT id, title, slug, description, content, image_url, demo_url, github_url, technologies, featured, created_at FROM projects WHERE featured = 1 ORDER BY created_at DESC LIMIT 4")?;
-    let projects = stmt.query_map([], |row| {
-        Ok(Project {
-            id: row.get(0)?,
-            title: row.get(1)?,
-            slug: row.get(2)?,
-            description: row.get(3)?,
-            content: row.get(4)?,
-            image_url: row.get(5)?,
-            demo_url: row.get(6)?,
-            github_url: row.get(7)?,
-            technologies: row.get(8)?,
-            featured: row.get(9)?,
-            created_at: row.get(10)?,
-        })
-    })?;
+    let mut stmt = conn.prepare(&format!("{} AND featured = 1 ORDER BY created_at DESC LIMIT 4", PROJECT_SELECT))?;
+    let projects = stmt.query_map([], project_from_row)?;
     projects.collect()
 }
 
 pub fn get_project_by_slug(conn: &Connection, slug: &str) -> Result<Project> {
-    conn.query_row(
-        "SELECT id, title, slug, description, content, image_url, demo_url, github_url, technologies, featured, created_at FROM projects WHERE slug = ?1",
-        [slug],
-        |row| {
-            Ok(Project {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                slug: row.get(2)?,
-                description: row.get(3)?,
-                content: row.get(4)?,
-                image_url: row.get(5)?,
-                demo_url: row.get(6)?,
-                github_url: row.get(7)?,
-                technologies: row.get(8)?,
-                featured: row.get(9)?,
-                created_at: row.get(10)?,
-            })
-        },
-    )
+    conn.query_row(&format!("{} AND slug = ?1", PROJECT_SELECT), [slug], project_from_row)
 }
 
 pub fn get_project_by_id(conn: &Connection, id: i32) -> Result<Project> {
+    conn.query_row(&format!("{} AND id = ?1", PROJECT_SELECT), [id], project_from_row)
+}
+
+/// Whether `table` has a row `id` that isn't in the trash.
+fn exists_outside_trash(conn: &Connection, table: &str, id: i32) -> Result<bool> {
     conn.query_row(
-        "SELECT id, title, slug, description, content, image_url, demo_url, github_url, technologies, featured, created_at FROM projects WHERE id = ?1",
+        &format!("SELECT EXISTS (SELECT 1 FROM {} WHERE id = ?1 AND deleted_at IS NULL)", table),
         [id],
-        |row| {
-            Ok(Project {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                slug: row.get(2)?,
-                description: row.get(3)?,
-                content: row.get(4)?,
-                image_url: row.get(5)?,
-                demo_url: row.get(6)?,
-                github_url: row.get(7)?,
-                technologies: row.get(8)?,
-                featured: row.get(9)?,
-                created_at: row.get(10)?,
-            })
-        },
+        |row| row.get(0),
     )
 }
 
-pub fn add_project(conn: &Connection, project: &ProjectForm) -> Result<()> {
-    let slug = slug::slugify(&project.title);
+/// A slug for `title` that no other row of `table` has, trashed rows included so
+/// they can be restored: the title's slug, or it with `-2`, `-3`, ... appended.
+/// `id` is the row being saved, which may keep its own slug.
+fn unique_slug(conn: &Connection, table: &str, title: &str, id: Option<i32>) -> Result<String> {
+    let base = slug::slugify(title);
+    let sql = format!("SELECT EXISTS (SELECT 1 FROM {} WHERE slug = ?1 AND id IS NOT ?2)", table);
+    let mut slug = base.clone();
+    let mut suffix = 2;
+    while conn.query_row(&sql, rusqlite::params![slug, id], |row| row.get::<_, bool>(0))? {
+        slug = format!("{}-{}", base, suffix);
+        suffix += 1;
+    }
+    Ok(slug)
+}
+
+pub fn add_project(conn: &Connection, project: &ProjectForm, editor_id: Option<i32>) -> Result<()> {
     let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
     let featured: i32 = if project.featured.is_some() { 1 } else { 0 };
     
-    conn.execute(
-        "INSERT INTO projects (title, slug, description, content, image_url, demo_url, github_url, technologies, featured, created_at)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10)",
+    let tx = conn.unchecked_transaction()?;
+    let slug = unique_slug(&tx, "projects", &project.title, None)?;
+    tx.execute(
+        "INSERT INTO projects (title, slug, description, content, image_url, demo_url, github_url, technologies, featured, created_at,
+                               seo_title, seo_description, updated_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?10)",
         rusqlite::params![
             &project.title, &slug, &project.description, &project.content,
             &project.image_url, &project.demo_url, &project.github_url,
-            &project.technologies, featured, &now,
+            &project.technologies, featured, &now, &project.seo_title, &project.seo_description,
         ],
     )?;
-    Ok(())
+    let id = tx.last_insert_rowid() as i32;
+    replace_project_tags(&tx, id as i64, &project.technologies)?;
+    record_revision(&tx, RevisionKind::Project, id, &project_snapshot(&tx, id)?, "Created", editor_id)?;
+    tx.commit()
+}
+
+/// Saves changes to a project. Returns false when there is no such project outside the trash.
+pub fn update_project(conn: &Connection, id: i32, project: &ProjectForm, editor_id: Option<i32>) -> Result<bool> {
+    save_project(conn, id, project, editor_id, "")
 }
 
-pub fn update_project(conn: &Connection, id: i32, project: &ProjectForm) -> Result<()> {
-    let slug = slug::slugify(&project.title);
+fn save_project(conn: &Connection, id: i32, project: &ProjectForm, editor_id: Option<i32>, note: &str) -> Result<bool> {
     let featured: i32 = if project.featured.is_some() { 1 } else { 0 };
     
-    conn.execute(
+    let tx = conn.unchecked_transaction()?;
+    if !exists_outside_trash(&tx, "projects", id)? {
+        return Ok(false);
+    }
+    let slug = unique_slug(&tx, "projects", &project.title, Some(id))?;
+    record_baseline(&tx, RevisionKind::Project, id, || project_snapshot(&tx, id))?;
+    tx.execute(
         "UPDATE projects SET title = ?1, slug = ?2, description = ?3, content = ?4, 
-         image_url = ?5, demo_url = ?6, github_url = ?7, technologies = ?8, featured = ?9 
-         WHERE id = ?10",
+         image_url = ?5, demo_url = ?6, github_url = ?7, technologies = ?8, featured = ?9,
+         seo_title = ?10, seo_description = ?11
+         WHERE id = ?12 AND deleted_at IS NULL",
         rusqlite::params![
             &project.title, &slug, &project.description, &project.content,
             &project.image_url, &project.demo_url, &project.github_url,
-            &project.technologies, featured, id,
+            &project.technologies, featured, &project.seo_title, &project.seo_description, id,
         ],
     )?;
-    Ok(())
+    replace_project_tags(&tx, id as i64, &project.technologies)?;
+    record_revision(&tx, RevisionKind::Project, id, &project_snapshot(&tx, id)?, note, editor_id)?;
+    tx.commit()?;
+    Ok(true)
 }
 
-pub fn delete_project(conn: &Connection, id: i32) -> Result<()> {
-    conn.execute("DELETE FROM projects WHERE id = ?1", [id])?;
-    Ok(())
+fn project_snapshot(conn: &Connection, id: i32) -> Result<String> {
+    let project = get_project_by_id(conn, id)?;
+    to_snapshot(&ProjectForm {
+        title: project.title,
+        description: project.description,
+        content: project.content,
+        image_url: project.image_url,
+        demo_url: project.demo_url,
+        github_url: project.github_url,
+        technologies: project.technologies,
+        featured: project.featured.then(|| "1".to_string()),
+        seo_title: project.seo_title,
+        seo_description: project.seo_description,
+    })
+}
+
+// Columns read by `blog_from_row`, with the author's name joined in; trashed posts are left out
+const BLOG_SELECT: &str = "SELECT b.id, b.title, b.slug, b.excerpt, b.content, b.image_url, b.tags, b.published,
+        b.created_at, b.updated_at, b.author_id, a.username, b.seo_title, b.seo_description, b.publish_at
+    FROM blogs b LEFT JOIN admin a ON a.id = b.author_id
+    WHERE b.deleted_at IS NULL";
+
+// Whether post `b` is public: not trashed, published, and not scheduled for later
+const BLOG_IS_LIVE: &str =
+    "b.deleted_at IS NULL AND b.published = 1 AND (b.publish_at IS NULL OR b.publish_at <= datetime('now'))";
+
+fn blog_from_row(row: &rusqlite::Row) -> Result<Blog> {
+    Ok(Blog {
+        id: row.get(0)?,
+        title: row.get(1)?,
+        slug: row.get(2)?,
+        excerpt: row.get(3)?,
+        content: row.get(4)?,
+        image_url: row.get(5)?,
+        tags: row.get(6)?,
+        published: row.get(7)?,
+        created_at: row.get(8)?,
+        updated_at: row.get(9)?,
+        author_id: row.get(10)?,
+        author: row.get(11)?,
+        seo_title: row.get(12)?,
+        seo_description: row.get(13)?,
+        publish_at: row.get(14)?,
+    })
 }
 
 pub fn get_blogs(conn: &Connection) -> Result<Vec<Blog>> {
-    let mut stmt = conn.prepare("SELECT id, title, slug, excerpt, content, image_url, tags, published, created_at, updated_at FROM blogs ORDER BY created_at DESC")?;
-    let blogs = stmt.query_map([], |row| {
-        Ok(Blog {
-            id: row.get(0)?,
-            title: row.get(1)?,
-            slug: row.get(2)?,
-            excerpt: row.get(3)?,
-            content: row.get(4)?,
-            image_url: row.get(5)?,
-            tags: row.get(6)?,
-            published: row.get(7)?,
-            created_at: row.get(8)?,
-            updated_at: row.get(9)?,
-        })
-    })?;
+    let mut stmt = conn.prepare(&format!("{} ORDER BY b.created_at DESC", BLOG_SELECT))?;
+    let blogs = stmt.query_map([], blog_from_row)?;
     blogs.collect()
 }
 
 pub fn get_published_blogs(conn: &Connection) -> Result<Vec<Blog>> {
-    let mut stmt = conn.prepare("SELECT id, title, slug, excerpt, content, image_url, tags, published, created_at, updated_at FROM blogs WHERE published = 1 ORDER BY created_at DESC")?;
-    let blogs = stmt.query_map([], |row| {
-        Ok(Blog {
-            id: row.get(0)?,
-            title: row.get(1)?,
-            slug: row.get(2)?,
-            excerpt: row.get(3)?,
-            content: row.get(4)?,
-            image_url: row.get(5)?,
-            tags: row.get(6)?,
-            published: row.get(7)?,
-            created_at: row.get(8)?,
-            updated_at: row.get(9)?,
-        })
-    })?;
+    let mut stmt = conn.prepare(&format!("{} AND {} ORDER BY b.created_at DESC", BLOG_SELECT, BLOG_IS_LIVE))?;
+    let blogs = stmt.query_map([], blog_from_row)?;
     blogs.collect()
 }
 
-pub fn get_recent_blogs(conn: &Connection, limit: i32) -> Result<Vec<Blog>> {
-    let mut stmt = conn.prepare("SELECT id, title, slug, excerpt, content, image_url, tags, published, created_at, updated_at FROM blogs WHERE published = 1 ORDER BY created_at DESC LIMIT ?1")?;
-    let blogs = stmt.query_map([limit], |row| {
-        Ok(Blog {
-            id: row.get(0)?,
-            title: row.get(1)?,
-            slug: row.get(2)?,
-            excerpt: row.get(3)?,
-            content: row.get(4)?,
-            image_url: row.get(5)?,
-            tags: row.get(6)?,
-            published: row.get(7)?,
-            created_at: row.get(8)?,
-            updated_at: row.get(9)?,
-        })
-    })?;
+pub fn get_published_blogs_page(conn: &Connection, limit: i64, offset: i64) -> Result<Vec<Blog>> {
+    let mut stmt = conn.prepare(&format!(
+        "{} AND {} ORDER BY b.created_at DESC LIMIT ?1 OFFSET ?2",
+        BLOG_SELECT, BLOG_IS_LIVE
+    ))?;
+    let blogs = stmt.query_map([limit, offset], blog_from_row)?;
     blogs.collect()
 }
 
-pub fn get_blog_by_slug(conn: &Connection, slug: &str) -> Result<Blog> {
+pub fn count_published_blogs(conn: &Connection) -> Result<i64> {
+    conn.query_row(&format!("SELECT COUNT(*) FROM blogs b WHERE {}", BLOG_IS_LIVE), [], |row| row.get(0))
+}
+
+/// One page of all posts, drafts included; `author_id` limits it to one author's posts.
+pub fn get_blogs_page(conn: &Connection, author_id: Option<i32>, limit: i64, offset: i64) -> Result<Vec<Blog>> {
+    let mut stmt = conn.prepare(&format!(
+        "{} AND (?1 IS NULL OR b.author_id = ?1) ORDER BY b.created_at DESC LIMIT ?2 OFFSET ?3",
+        BLOG_SELECT
+    ))?;
+    let blogs = stmt.query_map(rusqlite::params![author_id, limit, offset], blog_from_row)?;
+    blogs.collect()
+}
+
+pub fn count_blogs(conn: &Connection, author_id: Option<i32>) -> Result<i64> {
     conn.query_row(
-        "SELECT id, title, slug, excerpt, content, image_url, tags, published, created_at, updated_at FROM blogs WHERE slug = ?1",
-        [slug],
-        |row| {
-            Ok(Blog {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                slug: row.get(2)?,
-                excerpt: row.get(3)?,
-                content: row.get(4)?,
-                image_url: row.get(5)?,
-                tags: row.get(6)?,
-                published: row.get(7)?,
-                created_at: row.get(8)?,
-                updated_at: row.get(9)?,
-            })
-        },
+        "SELECT COUNT(*) FROM blogs WHERE deleted_at IS NULL AND (?1 IS NULL OR author_id = ?1)",
+        [author_id],
+        |row| row.get(0),
     )
 }
 
+pub fn get_recent_blogs(conn: &Connection, limit: i32) -> Result<Vec<Blog>> {
+    let mut stmt = conn.prepare(&format!("{} AND {} ORDER BY b.created_at DESC LIMIT ?1", BLOG_SELECT, BLOG_IS_LIVE))?;
+    let blogs = stmt.query_map([limit], blog_from_row)?;
+    blogs.collect()
+}
+
+/// Looks up a post for its public page; drafts and posts scheduled for later are not found.
+pub fn get_blog_by_slug(conn: &Connection, slug: &str) -> Result<Blog> {
+    conn.query_row(&format!("{} AND b.slug = ?1 AND {}", BLOG_SELECT, BLOG_IS_LIVE), [slug], blog_from_row)
+}
+
 pub fn get_blog_by_id(conn: &Connection, id: i32) -> Result<Blog> {
-    conn.query_row(
-        "SELECT id, title, slug, excerpt, content, image_url, tags, published, created_at, updated_at FROM blogs WHERE id = ?1",
-        [id],
-        |row| {
-            Ok(Blog {
-                id: row.get(0)?,
-                title: row.get(1)?,
-                slug: row.get(2)?,
-                excerpt: row.get(3)?,
-                content: row.get(4)?,
-                image_url: row.get(5)?,
-                tags: row.get(6)?,
-                published: row.get(7)?,
-                created_at: row.get(8)?,
-                updated_at: row.get(9)?,
-            })
-        },
-    )
+    conn.query_row(&format!("{} AND b.id = ?1", BLOG_SELECT), [id], blog_from_row)
 }
 
-pub fn add_blog(conn: &Connection, blog: &BlogForm) -> Result<()> {
-    let slug = slug::slugify(&blog.title);
+pub fn add_blog(conn: &Connection, blog: &BlogForm, author_id: Option<i32>) -> Result<()> {
     let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
     let published: i32 = if blog.published.is_some() { 1 } else { 0 };
     
-    conn.execute(
-        "INSERT INTO blogs (title, slug, excerpt, content, image_url, tags, published, created_at, updated_at)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9)",
+    let tx = conn.unchecked_transaction()?;
+    let slug = unique_slug(&tx, "blogs", &blog.title, None)?;
+    tx.execute(
+        "INSERT INTO blogs (title, slug, excerpt, content, image_url, tags, published, created_at, updated_at, author_id,
+                            seo_title, seo_description, publish_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)",
         rusqlite::params![
             &blog.title, &slug, &blog.excerpt, &blog.content,
-            &blog.image_url, &blog.tags, published, &now, &now,
+            &blog.image_url, &blog.tags, published, &now, &now, author_id,
+            &blog.seo_title, &blog.seo_description, &blog.publish_at,
         ],
     )?;
-    Ok(())
+    let id = tx.last_insert_rowid() as i32;
+    replace_blog_tags(&tx, id as i64, &blog.tags)?;
+    record_revision(&tx, RevisionKind::Blog, id, &blog_snapshot(&tx, id)?, "Created", author_id)?;
+    tx.commit()
+}
+
+/// Saves changes to a post. Returns false when there is no such post outside the trash.
+pub fn update_blog(conn: &Connection, id: i32, blog: &BlogForm, editor_id: Option<i32>) -> Result<bool> {
+    save_blog(conn, id, blog, editor_id, "")
 }
 
-pub fn update_blog(conn: &Connection, id: i32, blog: &BlogForm) -> Result<()> {
-    let slug = slug::slugify(&blog.title);
+fn save_blog(conn: &Connection, id: i32, blog: &BlogForm, editor_id: Option<i32>, note: &str) -> Result<bool> {
     let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
     let published: i32 = if blog.published.is_some() { 1 } else { 0 };
     
-    conn.execute(
+    let tx = conn.unchecked_transaction()?;
+    if !exists_outside_trash(&tx, "blogs", id)? {
+        return Ok(false);
+    }
+    let slug = unique_slug(&tx, "blogs", &blog.title, Some(id))?;
+    record_baseline(&tx, RevisionKind::Blog, id, || blog_snapshot(&tx, id))?;
+    tx.execute(
         "UPDATE blogs SET title = ?1, slug = ?2, excerpt = ?3, content = ?4, 
-         image_url = ?5, tags = ?6, published = ?7, updated_at = ?8 WHERE id = ?9",
+         image_url = ?5, tags = ?6, published = ?7, updated_at = ?8, seo_title = ?9, seo_description = ?10,
+         publish_at = ?11
+         WHERE id = ?12 AND deleted_at IS NULL",
         rusqlite::params![
             &blog.title, &slug, &blog.excerpt, &blog.content,
-            &blog.image_url, &blog.tags, published, &now, id,
+            &blog.image_url, &blog.tags, published, &now, &blog.seo_title, &blog.seo_description,
+            &blog.publish_at, id,
         ],
     )?;
+    replace_blog_tags(&tx, id as i64, &blog.tags)?;
+    record_revision(&tx, RevisionKind::Blog, id, &blog_snapshot(&tx, id)?, note, editor_id)?;
+    tx.commit()?;
+    Ok(true)
+}
+
+fn blog_snapshot(conn: &Connection, id: i32) -> Result<String> {
+    let blog = get_blog_by_id(conn, id)?;
+    to_snapshot(&BlogForm {
+        title: blog.title,
+        excerpt: blog.excerpt,
+        content: blog.content,
+        image_url: blog.image_url,
+        tags: blog.tags,
+        published: blog.published.then(|| "1".to_string()),
+        seo_title: blog.seo_title,
+        seo_description: blog.seo_description,
+        publish_at: blog.publish_at,
+    })
+}
+
+/// Makes every scheduled post whose time has come live: it is dated by its
+/// publish time and its schedule cleared. Returns the posts that went live.
+pub fn publish_due_blogs(conn: &Connection) -> Result<Vec<Blog>> {
+    let tx = conn.unchecked_transaction()?;
+    let ids = tx
+        .prepare(
+            "UPDATE blogs SET created_at = publish_at, publish_at = NULL
+             WHERE deleted_at IS NULL AND published = 1 AND publish_at IS NOT NULL AND publish_at <= datetime('now')
+             RETURNING id",
+        )?
+        .query_map([], |row| row.get(0))?
+        .collect::<Result<Vec<i32>>>()?;
+    let blogs = ids.into_iter().map(|id| get_blog_by_id(&tx, id)).collect::<Result<Vec<_>>>()?;
+    tx.commit()?;
+    Ok(blogs)
+}
+
+// Tag functions
+
+/// Splits a comma-separated tag list, dropping blanks and repeats that differ only in case.
+fn parse_tags(input: &str) -> Vec<&str> {
+    let mut tags: Vec<&str> = Vec::new();
+    for tag in input.split(',').map(str::trim).filter(|tag| !tag.is_empty()) {
+        if !tags.iter().any(|seen| seen.eq_ignore_ascii_case(tag)) {
+            tags.push(tag);
+        }
+    }
+    tags
+}
+
+/// Looks up a tag ignoring case, creating it if needed. Returns its id and stored spelling.
+fn ensure_tag(conn: &Connection, name: &str) -> Result<(i64, String)> {
+    conn.execute("INSERT INTO tags (name) VALUES (?1) ON CONFLICT (name) DO NOTHING", [name])?;
+    conn.query_row("SELECT id, name FROM tags WHERE name = ?1", [name], |row| Ok((row.get(0)?, row.get(1)?)))
+}
+
+fn delete_unused_tags(conn: &Connection) -> Result<()> {
+    conn.execute(
+        "DELETE FROM tags WHERE id NOT IN (SELECT tag_id FROM blog_tags)
+                            AND id NOT IN (SELECT tag_id FROM project_tags)",
+        [],
+    )?;
     Ok(())
 }
 
-pub fn delete_blog(conn: &Connection, id: i32) -> Result<()> {
-    conn.execute("DELETE FROM blogs WHERE id = ?1", [id])?;
+/// Points a post at the tags in `input` and rewrites its `tags` string to match.
+/// Callers run this inside the transaction that saved the post.
+fn replace_blog_tags(conn: &Connection, blog_id: i64, input: &str) -> Result<()> {
+    conn.execute("DELETE FROM blog_tags WHERE blog_id = ?1", [blog_id])?;
+    let mut names = Vec::new();
+    for tag in parse_tags(input) {
+        let (tag_id, name) = ensure_tag(conn, tag)?;
+        conn.execute("INSERT INTO blog_tags (blog_id, tag_id) VALUES (?1, ?2)", [blog_id, tag_id])?;
+        names.push(name);
+    }
+    conn.execute("UPDATE blogs SET tags = ?2 WHERE id = ?1", rusqlite::params![blog_id, names.join(", ")])?;
+    delete_unused_tags(conn)
+}
+
+/// Same as `replace_blog_tags`, for a project's technologies.
+fn replace_project_tags(conn: &Connection, project_id: i64, input: &str) -> Result<()> {
+    conn.execute("DELETE FROM project_tags WHERE project_id = ?1", [project_id])?;
+    let mut names = Vec::new();
+    for tag in parse_tags(input) {
+        let (tag_id, name) = ensure_tag(conn, tag)?;
+        conn.execute("INSERT INTO project_tags (project_id, tag_id) VALUES (?1, ?2)", [project_id, tag_id])?;
+        names.push(name);
+    }
+    conn.execute(
+        "UPDATE projects SET technologies = ?2 WHERE id = ?1",
+        rusqlite::params![project_id, names.join(", ")],
+    )?;
+    delete_unused_tags(conn)
+}
+
+/// Fills the join tables from the tag strings of every post and project.
+/// Used after inserting the sample content, which bypasses `add_blog`/`add_project`.
+fn retag_all(conn: &Connection) -> Result<()> {
+    let blogs: Vec<(i64, String)> = conn
+        .prepare("SELECT id, tags FROM blogs")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<_>>()?;
+    for (id, tags) in blogs {
+        replace_blog_tags(conn, id, &tags)?;
+    }
+    let projects: Vec<(i64, String)> = conn
+        .prepare("SELECT id, technologies FROM projects")?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<_>>()?;
+    for (id, technologies) in projects {
+        replace_project_tags(conn, id, &technologies)?;
+    }
     Ok(())
 }
 
+/// Returns the stored spelling of a tag, matching `name` ignoring case.
+pub fn find_tag(conn: &Connection, name: &str) -> Result<Option<String>> {
+    conn.query_row("SELECT name FROM tags WHERE name = ?1", [name], |row| row.get(0))
+        .optional()
+}
+
+fn tag_counts(conn: &Connection, sql: &str) -> Result<Vec<TagCount>> {
+    let mut stmt = conn.prepare(sql)?;
+    let tags = stmt.query_map([], |row| {
+        Ok(TagCount {
+            name: row.get(0)?,
+            count: row.get(1)?,
+            level: 1,
+        })
+    })?;
+    let mut tags = tags.collect::<Result<Vec<_>>>()?;
+    let max = tags.iter().map(|tag| tag.count).max().unwrap_or(1);
+    for tag in &mut tags {
+        tag.level = 1 + (tag.count * 3) / max;
+    }
+    Ok(tags)
+}
+
+/// Tags of published posts with how many posts use each, alphabetically.
+pub fn get_blog_tag_counts(conn: &Connection) -> Result<Vec<TagCount>> {
+    tag_counts(
+        conn,
+        &format!(
+            "SELECT t.name, COUNT(*) FROM tags t
+             JOIN blog_tags bt ON bt.tag_id = t.id
+             JOIN blogs b ON b.id = bt.blog_id
+             WHERE {}
+             GROUP BY t.id ORDER BY t.name",
+            BLOG_IS_LIVE
+        ),
+    )
+}
+
+/// Technologies used by projects with how many projects use each, alphabetically.
+pub fn get_project_tech_counts(conn: &Connection) -> Result<Vec<TagCount>> {
+    tag_counts(
+        conn,
+        "SELECT t.name, COUNT(*) FROM tags t
+         JOIN project_tags pt ON pt.tag_id = t.id
+         JOIN projects p ON p.id = pt.project_id
+         WHERE p.deleted_at IS NULL
+         GROUP BY t.id ORDER BY t.name",
+    )
+}
+
+const BLOG_HAS_TAG: &str = "b.id IN (SELECT bt.blog_id FROM blog_tags bt JOIN tags t ON t.id = bt.tag_id WHERE t.name = ?1)";
+
+pub fn get_published_blogs_by_tag_page(conn: &Connection, tag: &str, limit: i64, offset: i64) -> Result<Vec<Blog>> {
+    let mut stmt = conn.prepare(&format!(
+        "{} AND {} AND {} ORDER BY b.created_at DESC LIMIT ?2 OFFSET ?3",
+        BLOG_SELECT, BLOG_IS_LIVE, BLOG_HAS_TAG
+    ))?;
+    let blogs = stmt.query_map(rusqlite::params![tag, limit, offset], blog_from_row)?;
+    blogs.collect()
+}
+
+pub fn count_published_blogs_by_tag(conn: &Connection, tag: &str) -> Result<i64> {
+    conn.query_row(
+        &format!("SELECT COUNT(*) FROM blogs b WHERE {} AND {}", BLOG_IS_LIVE, BLOG_HAS_TAG),
+        [tag],
+        |row| row.get(0),
+    )
+}
+
+const PROJECT_HAS_TECH: &str = "id IN (SELECT pt.project_id FROM project_tags pt JOIN tags t ON t.id = pt.tag_id WHERE t.name = ?1)";
+
+pub fn get_projects_by_tech_page(conn: &Connection, tech: &str, limit: i64, offset: i64) -> Result<Vec<Project>> {
+    let mut stmt = conn.prepare(&format!(
+        "{} AND {} ORDER BY created_at DESC LIMIT ?2 OFFSET ?3",
+        PROJECT_SELECT, PROJECT_HAS_TECH
+    ))?;
+    let projects = stmt.query_map(rusqlite::params![tech, limit, offset], project_from_row)?;
+    projects.collect()
+}
+
+pub fn count_projects_by_tech(conn: &Connection, tech: &str) -> Result<i64> {
+    conn.query_row(
+        &format!("SELECT COUNT(*) FROM projects WHERE deleted_at IS NULL AND {}", PROJECT_HAS_TECH),
+        [tech],
+        |row| row.get(0),
+    )
+}
+
 pub fn get_experience(conn: &Connection) -> Result<Vec<Experience>> {
-    let mut stmt = conn.prepare("SELECT id, company, position, description, start_date, end_date, current FROM experience ORDER BY start_date DESC")?;
+    let mut stmt = conn.prepare("SELECT id, company, position, description, start_date, end_date, current FROM experience WHERE deleted_at IS NULL ORDER BY start_date DESC")?;
     let experience = stmt.query_map([], |row| {
         Ok(Experience {
             id: row.get(0)?,
@@ -698,7 +858,7 @@ pub fn get_experience(conn: &Connection) -> Result<Vec<Experience>> {
 
 pub fn get_experience_by_id(conn: &Connection, id: i32) -> Result<Experience> {
     conn.query_row(
-        "SELECT id, company, position, description, start_date, end_date, current FROM experience WHERE id = ?1",
+        "SELECT id, company, position, description, start_date, end_date, current FROM experience WHERE id = ?1 AND deleted_at IS NULL",
         rusqlite::params![id],
         |row| {
             Ok(Experience {
@@ -723,22 +883,19 @@ pub fn add_experience(conn: &Connection, exp: &ExperienceForm) -> Result<()> {
     Ok(())
 }
 
-pub fn update_experience(conn: &Connection, id: i32, exp: &ExperienceForm) -> Result<()> {
+/// Returns false when there is no such entry outside the trash.
+pub fn update_experience(conn: &Connection, id: i32, exp: &ExperienceForm) -> Result<bool> {
     let is_current = exp.current.as_ref().map(|v| v == "true").unwrap_or(false);
-    conn.execute(
-        "UPDATE experience SET company = ?1, position = ?2, description = ?3, start_date = ?4, end_date = ?5, current = ?6 WHERE id = ?7",
+    let changed = conn.execute(
+        "UPDATE experience SET company = ?1, position = ?2, description = ?3, start_date = ?4, end_date = ?5, current = ?6
+         WHERE id = ?7 AND deleted_at IS NULL",
         rusqlite::params![exp.company, exp.position, exp.description, exp.start_date, exp.end_date, is_current, id],
     )?;
-    Ok(())
-}
-
-pub fn delete_experience(conn: &Connection, id: i32) -> Result<()> {
-    conn.execute("DELETE FROM experience WHERE id = ?1", rusqlite::params![id])?;
-    Ok(())
+    Ok(changed > 0)
 }
 
 pub fn get_education(conn: &Connection) -> Result<Vec<Education>> {
-    let mut stmt = conn.prepare("SELECT id, institution, degree, field, start_date, end_date, description FROM education ORDER BY start_date DESC")?;
+    let mut stmt = conn.prepare("SELECT id, institution, degree, field, start_date, end_date, description FROM education WHERE deleted_at IS NULL ORDER BY start_date DESC")?;
     let education = stmt.query_map([], |row| {
         Ok(Education {
             id: row.get(0)?,
@@ -755,7 +912,7 @@ pub fn get_education(conn: &Connection) -> Result<Vec<Education>> {
 
 pub fn get_education_by_id(conn: &Connection, id: i32) -> Result<Education> {
     conn.query_row(
-        "SELECT id, institution, degree, field, start_date, end_date, description FROM education WHERE id = ?1",
+        "SELECT id, institution, degree, field, start_date, end_date, description FROM education WHERE id = ?1 AND deleted_at IS NULL",
         rusqlite::params![id],
         |row| {
             Ok(Education {
@@ -779,17 +936,14 @@ pub fn add_education(conn: &Connection, edu: &EducationForm) -> Result<()> {
     Ok(())
 }
 
-pub fn update_education(conn: &Connection, id: i32, edu: &EducationForm) -> Result<()> {
-    conn.execute(
-        "UPDATE education SET institution = ?1, degree = ?2, field = ?3, start_date = ?4, end_date = ?5, description = ?6 WHERE id = ?7",
+/// Returns false when there is no such entry outside the trash.
+pub fn update_education(conn: &Connection, id: i32, edu: &EducationForm) -> Result<bool> {
+    let changed = conn.execute(
+        "UPDATE education SET institution = ?1, degree = ?2, field = ?3, start_date = ?4, end_date = ?5, description = ?6
+         WHERE id = ?7 AND deleted_at IS NULL",
         rusqlite::params![edu.institution, edu.degree, edu.field, edu.start_date, edu.end_date, edu.description, id],
     )?;
-    Ok(())
-}
-
-pub fn delete_education(conn: &Connection, id: i32) -> Result<()> {
-    conn.execute("DELETE FROM education WHERE id = ?1", rusqlite::params![id])?;
-    Ok(())
+    Ok(changed > 0)
 }
 
 pub fn add_message(conn: &Connection, message: &ContactForm) -> Result<()> {
@@ -801,102 +955,633 @@ pub fn add_message(conn: &Connection, message: &ContactForm) -> Result<()> {
     Ok(())
 }
 
+// Columns read by `message_from_row`; trashed messages are left out
+const MESSAGE_SELECT: &str = "SELECT id, name, email, subject, message, read, created_at FROM messages WHERE deleted_at IS NULL";
+
+fn message_from_row(row: &rusqlite::Row) -> Result<Message> {
+    Ok(Message {
+        id: row.get(0)?,
+        name: row.get(1)?,
+        email: row.get(2)?,
+        subject: row.get(3)?,
+        message: row.get(4)?,
+        read: row.get(5)?,
+        created_at: row.get(6)?,
+    })
+}
+
 pub fn get_messages(conn: &Connection) -> Result<Vec<Message>> {
-    let mut stmt = conn.prepare("SELECT id, name, email, subject, message, read, created_at FROM messages ORDER BY created_at DESC")?;
-    let messages = stmt.query_map([], |row| {
-        Ok(Message {
-            id: row.get(0)?,
-            name: row.get(1)?,
-            email: row.get(2)?,
-            subject: row.get(3)?,
-            message: row.get(4)?,
-            read: row.get(5)?,
-            created_at: row.get(6)?,
-        })
-    })?;
+    let mut stmt = conn.prepare(&format!("{} ORDER BY created_at DESC", MESSAGE_SELECT))?;
+    let messages = stmt.query_map([], message_from_row)?;
+    messages.collect()
+}
+
+pub fn get_messages_page(conn: &Connection, limit: i64, offset: i64) -> Result<Vec<Message>> {
+    let mut stmt = conn.prepare(&format!("{} ORDER BY created_at DESC LIMIT ?1 OFFSET ?2", MESSAGE_SELECT))?;
+    let messages = stmt.query_map([limit, offset], message_from_row)?;
     messages.collect()
 }
 
+pub fn count_messages(conn: &Connection) -> Result<i64> {
+    conn.query_row("SELECT COUNT(*) FROM messages WHERE deleted_at IS NULL", [], |row| row.get(0))
+}
+
 pub fn get_unread_message_count(conn: &Connection) -> Result<i32> {
     conn.query_row(
-        "SELECT COUNT(*) FROM messages WHERE read = 0",
+        "SELECT COUNT(*) FROM messages WHERE read = 0 AND deleted_at IS NULL",
         [],
         |row| row.get(0)
     )
 }
 
-pub fn delete_message(conn: &Connection, id: i32) -> Result<()> {
-    conn.execute("DELETE FROM messages WHERE id = ?1", [id])?;
-    Ok(())
+const ADMIN_SELECT: &str = "SELECT id, username, password_hash, totp_secret, totp_last_step, role, session_version FROM admin";
+
+fn admin_from_row(row: &rusqlite::Row) -> Result<Admin> {
+    let role: String = row.get(5)?;
+    Ok(Admin {
+        id: row.get(0)?,
+        username: row.get(1)?,
+        password_hash: row.get(2)?,
+        totp_secret: row.get(3)?,
+        totp_last_step: row.get(4)?,
+        // The CHECK constraint keeps this valid; fall back to the least privilege regardless
+        role: Role::parse(&role).unwrap_or(Role::Author),
+        session_version: row.get(6)?,
+    })
 }
 
 pub fn get_admin(conn: &Connection, username: &str) -> Result<Admin> {
+    conn.query_row(&format!("{} WHERE username = ?1", ADMIN_SELECT), [username], admin_from_row)
+}
+
+pub fn get_admin_by_id(conn: &Connection, id: i32) -> Result<Admin> {
+    conn.query_row(&format!("{} WHERE id = ?1", ADMIN_SELECT), [id], admin_from_row)
+}
+
+pub fn get_admins(conn: &Connection) -> Result<Vec<Admin>> {
+    let mut stmt = conn.prepare(&format!("{} ORDER BY id", ADMIN_SELECT))?;
+    let admins = stmt.query_map([], admin_from_row)?;
+    admins.collect()
+}
+
+/// Adds another admin account. Fails with a constraint error if the username is taken.
+pub fn create_admin(conn: &Connection, username: &str, password: &str, role: Role) -> Result<()> {
+    use bcrypt::{hash, DEFAULT_COST};
+    let password_hash = hash(password, DEFAULT_COST).expect("Failed to hash password");
+    conn.execute(
+        "INSERT INTO admin (username, password_hash, role) VALUES (?1, ?2, ?3)",
+        rusqlite::params![username, password_hash, role.as_str()],
+    )?;
+    Ok(())
+}
+
+pub fn update_admin_role(conn: &Connection, id: i32, role: Role) -> Result<()> {
+    conn.execute("UPDATE admin SET role = ?2 WHERE id = ?1", rusqlite::params![id, role.as_str()])?;
+    Ok(())
+}
+
+pub fn delete_admin(conn: &Connection, id: i32) -> Result<()> {
+    conn.execute("DELETE FROM admin WHERE id = ?1", [id])?;
+    Ok(())
+}
+
+pub fn count_owners(conn: &Connection) -> Result<i64> {
+    conn.query_row("SELECT COUNT(*) FROM admin WHERE role = 'owner'", [], |row| row.get(0))
+}
+
+pub fn admin_exists(conn: &Connection) -> Result<bool> {
+    let count: i32 = conn.query_row("SELECT COUNT(*) FROM admin", [], |row| row.get(0))?;
+    Ok(count > 0)
+}
+
+/// Creates the initial admin account. Returns `false` without touching the
+/// table if an admin already exists, so existing credentials are never reset.
+pub fn create_initial_admin(conn: &Connection, username: &str, password: &str) -> Result<bool> {
+    use bcrypt::{hash, DEFAULT_COST};
+    let password_hash = hash(password, DEFAULT_COST).expect("Failed to hash password");
+    let inserted = conn.execute(
+        "INSERT INTO admin (username, password_hash) SELECT ?1, ?2 WHERE NOT EXISTS (SELECT 1 FROM admin)",
+        rusqlite::params![username, password_hash],
+    )?;
+    Ok(inserted > 0)
+}
+
+fn bootstrap_admin_from_env(conn: &Connection) -> Result<()> {
+    if admin_exists(conn)? {
+        return Ok(());
+    }
+
+    match (std::env::var("ADMIN_USERNAME"), std::env::var("ADMIN_PASSWORD")) {
+        (Ok(username), Ok(password)) if !username.trim().is_empty() && !password.is_empty() => {
+            let username = username.trim();
+            // The same rule as /admin/setup, so the environment is no way around it
+            if let Some(problem) = crate::auth::password_problem(&password, username) {
+                log::error!(
+                    "ADMIN_PASSWORD was rejected, so no admin was created: {} Fix it or visit /admin/setup.",
+                    problem
+                );
+            } else if create_initial_admin(conn, username, &password)? {
+                log::info!("Initial admin created from environment - username: {}", username);
+            }
+        }
+        _ => log::warn!("No admin account exists yet - visit /admin/setup to create one"),
+    }
+    Ok(())
+}
+
+/// Changes an admin's username and, if given, password. Bumps the session
+/// version so every other session of this admin is logged out; returns the new version.
+pub fn update_admin_credentials(conn: &Connection, id: i32, new_username: &str, new_password: Option<&str>) -> Result<i64> {
+    use bcrypt::{hash, DEFAULT_COST};
+    let tx = conn.unchecked_transaction()?;
+    if let Some(new_password) = new_password {
+        let password_hash = hash(new_password, DEFAULT_COST).expect("Failed to hash password");
+        tx.execute(
+            "UPDATE admin SET password_hash = ?2 WHERE id = ?1",
+            rusqlite::params![id, password_hash],
+        )?;
+    }
+    let session_version = tx.query_row(
+        "UPDATE admin SET username = ?2, session_version = session_version + 1 WHERE id = ?1 RETURNING session_version",
+        rusqlite::params![id, new_username],
+        |row| row.get(0),
+    )?;
+    tx.commit()?;
+    Ok(session_version)
+}
+
+// Two-factor functions
+
+fn insert_recovery_codes(conn: &Connection, admin_id: i32, code_hashes: &[String]) -> Result<()> {
+    conn.execute("DELETE FROM admin_recovery_codes WHERE admin_id = ?1", [admin_id])?;
+    for code_hash in code_hashes {
+        conn.execute(
+            "INSERT INTO admin_recovery_codes (admin_id, code_hash) VALUES (?1, ?2)",
+            rusqlite::params![admin_id, code_hash],
+        )?;
+    }
+    Ok(())
+}
+
+/// Turns on TOTP for an admin and replaces any recovery codes they had.
+/// `step` is the time step of the code used to confirm enrollment.
+pub fn enable_totp(conn: &Connection, admin_id: i32, secret: &str, step: i64, code_hashes: &[String]) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(
+        "UPDATE admin SET totp_secret = ?2, totp_last_step = ?3 WHERE id = ?1",
+        rusqlite::params![admin_id, secret, step],
+    )?;
+    insert_recovery_codes(&tx, admin_id, code_hashes)?;
+    tx.commit()
+}
+
+pub fn disable_totp(conn: &Connection, admin_id: i32) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("UPDATE admin SET totp_secret = NULL, totp_last_step = 0 WHERE id = ?1", [admin_id])?;
+    tx.execute("DELETE FROM admin_recovery_codes WHERE admin_id = ?1", [admin_id])?;
+    tx.commit()
+}
+
+pub fn replace_recovery_codes(conn: &Connection, admin_id: i32, code_hashes: &[String]) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    insert_recovery_codes(&tx, admin_id, code_hashes)?;
+    tx.commit()
+}
+
+/// Records the time step of an accepted code. Returns `false` if that step
+/// (or a later one) was already used, i.e. the code is being replayed.
+pub fn claim_totp_step(conn: &Connection, admin_id: i32, step: i64) -> Result<bool> {
+    let updated = conn.execute(
+        "UPDATE admin SET totp_last_step = ?2 WHERE id = ?1 AND totp_last_step < ?2",
+        rusqlite::params![admin_id, step],
+    )?;
+    Ok(updated > 0)
+}
+
+/// Marks a recovery code as used. Returns `false` if it doesn't exist or was used before.
+pub fn use_recovery_code(conn: &Connection, admin_id: i32, code_hash: &str) -> Result<bool> {
+    let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let updated = conn.execute(
+        "UPDATE admin_recovery_codes SET used_at = ?3
+         WHERE admin_id = ?1 AND code_hash = ?2 AND used_at IS NULL",
+        rusqlite::params![admin_id, code_hash, now],
+    )?;
+    Ok(updated > 0)
+}
+
+pub fn count_recovery_codes_left(conn: &Connection, admin_id: i32) -> Result<i64> {
     conn.query_row(
-        "SELECT id, username, password_hash FROM admin WHERE username = ?1",
-        [username],
-        |row| {
-            Ok(Admin {
-                id: row.get(0)?,
-                username: row.get(1)?,
-                password_hash: row.get(2)?,
-            })
-        },
+        "SELECT COUNT(*) FROM admin_recovery_codes WHERE admin_id = ?1 AND used_at IS NULL",
+        [admin_id],
+        |row| row.get(0),
     )
 }
 
-pub fn update_admin_credentials(conn: &Connection, new_username: &str, new_password: &str) -> Result<()> {
-    use bcrypt::{hash, DEFAULT_COST};
-    let password_hash = hash(new_password, DEFAULT_COST).expect("Failed to hash password");
+// Login throttling functions
+
+// Only the most recent attempts are kept so a flood of requests can't grow the log unbounded
+const LOGIN_ATTEMPTS_KEPT: i64 = 5000;
+
+/// Seconds until the IP and the username may both try to log in again; `0` if neither is locked.
+pub fn login_lockout_remaining(conn: &Connection, ip: &str, username: &str, now: i64) -> Result<i64> {
+    let locked_until: Option<i64> = conn.query_row(
+        "SELECT MAX(locked_until) FROM login_throttle
+         WHERE (scope = 'ip' AND key = ?1) OR (scope = 'username' AND key = ?2)",
+        rusqlite::params![ip, username],
+        |row| row.get(0),
+    )?;
+    Ok((locked_until.unwrap_or(0) - now).max(0))
+}
+
+/// Counts a failed login against both the IP and the username, locking
+/// either out once it has run past its free attempts.
+pub fn register_login_failure(conn: &Connection, ip: &str, username: &str, now: i64, limits: &LoginLimits) -> Result<()> {
+    for (scope, key) in [("ip", ip), ("username", username)] {
+        let failures: i64 = conn.query_row(
+            "INSERT INTO login_throttle (scope, key, failures, last_failure_at) VALUES (?1, ?2, 1, ?3)
+             ON CONFLICT (scope, key) DO UPDATE SET
+                 failures = CASE WHEN last_failure_at < ?4 THEN 1 ELSE failures + 1 END,
+                 last_failure_at = ?3
+             RETURNING failures",
+            rusqlite::params![scope, key, now, now - limits.reset_after_secs],
+            |row| row.get(0),
+        )?;
+        
+        let lockout = limits.lockout_secs(failures);
+        if lockout > 0 {
+            conn.execute(
+                "UPDATE login_throttle SET locked_until = ?3 WHERE scope = ?1 AND key = ?2",
+                rusqlite::params![scope, key, now + lockout],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+pub fn clear_login_failures(conn: &Connection, ip: &str, username: &str) -> Result<()> {
     conn.execute(
-        "UPDATE admin SET username = ?1, password_hash = ?2 WHERE id = 1",
-        rusqlite::params![new_username, password_hash],
+        "DELETE FROM login_throttle WHERE (scope = 'ip' AND key = ?1) OR (scope = 'username' AND key = ?2)",
+        rusqlite::params![ip, username],
     )?;
     Ok(())
 }
 
-// Image functions
-pub fn save_image(conn: &Connection, id: &str, filename: &str, content_type: &str, data: &[u8]) -> Result<()> {
+pub fn record_login_attempt(conn: &Connection, ip: &str, username: &str, outcome: &str) -> Result<()> {
     let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
     conn.execute(
-        "INSERT INTO images (id, filename, content_type, data, created_at) VALUES (?1, ?2, ?3, ?4, ?5)",
-        rusqlite::params![id, filename, content_type, data, now],
+        "INSERT INTO login_attempts (ip, username, outcome, created_at) VALUES (?1, ?2, ?3, ?4)",
+        rusqlite::params![ip, username, outcome, now],
+    )?;
+    conn.execute(
+        "DELETE FROM login_attempts WHERE id <= last_insert_rowid() - ?1",
+        [LOGIN_ATTEMPTS_KEPT],
+    )?;
+    Ok(())
+}
+
+pub fn get_failed_login_attempts(conn: &Connection, limit: i32) -> Result<Vec<LoginAttempt>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, ip, username, outcome, created_at FROM login_attempts
+         WHERE outcome != 'success' ORDER BY id DESC LIMIT ?1"
     )?;
+    let attempts = stmt.query_map([limit], |row| {
+        Ok(LoginAttempt {
+            id: row.get(0)?,
+            ip: row.get(1)?,
+            username: row.get(2)?,
+            outcome: row.get(3)?,
+            created_at: row.get(4)?,
+        })
+    })?;
+    attempts.collect()
+}
+
+pub fn count_failed_logins_since(conn: &Connection, since: &str) -> Result<i64> {
+    conn.query_row(
+        "SELECT COUNT(*) FROM login_attempts WHERE outcome != 'success' AND created_at >= ?1",
+        [since],
+        |row| row.get(0),
+    )
+}
+
+// Image functions
+// The bytes of uploads live in the blob store (see `blobs`); these rows describe
+// them and hold their blob keys.
+
+/// Records an upload whose encodings are already in the blob store. `height`
+/// is known for processed images, along with the width of each encoding.
+pub fn insert_image(
+    conn: &Connection,
+    id: &str,
+    filename: &str,
+    original: &StoredBlob,
+    height: Option<u32>,
+    variants: &[StoredBlob],
+) -> Result<()> {
+    let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(
+        "INSERT INTO images (id, filename, content_type, blob_key, size, created_at, width, height)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+        rusqlite::params![id, filename, original.content_type, original.key, original.size, now, original.width, height],
+    )?;
+    insert_image_variants(&tx, id, variants)?;
+    tx.commit()
+}
+
+fn insert_image_variants(conn: &Connection, id: &str, variants: &[StoredBlob]) -> Result<()> {
+    let mut stmt = conn.prepare(
+        "INSERT INTO image_variants (image_id, width, content_type, blob_key, size) VALUES (?1, ?2, ?3, ?4, ?5)",
+    )?;
+    for variant in variants {
+        stmt.execute(rusqlite::params![id, variant.width, variant.content_type, variant.key, variant.size])?;
+    }
     Ok(())
 }
 
-pub fn get_image(conn: &Connection, id: &str) -> Result<(String, Vec<u8>)> {
+/// Ids of images stored before uploads were processed, for `portfolio process-images`.
+pub fn get_unprocessed_image_ids(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT id FROM images
+         WHERE width IS NULL AND content_type IN ('image/jpeg', 'image/jpg', 'image/png', 'image/webp')
+         ORDER BY created_at",
+    )?;
+    let ids = stmt.query_map([], |row| row.get(0))?;
+    ids.collect()
+}
+
+/// Points an image at newly processed encodings, replacing its variants.
+/// Returns the blob keys nothing refers to any more.
+pub fn replace_image_encodings(
+    conn: &Connection,
+    id: &str,
+    original: &StoredBlob,
+    height: Option<u32>,
+    variants: &[StoredBlob],
+) -> Result<Vec<String>> {
+    let tx = conn.unchecked_transaction()?;
+    let previous = image_blob_keys(&tx, id)?;
+    tx.execute(
+        "UPDATE images SET content_type = ?2, blob_key = ?3, size = ?4, width = ?5, height = ?6 WHERE id = ?1",
+        rusqlite::params![id, original.content_type, original.key, original.size, original.width, height],
+    )?;
+    tx.execute("DELETE FROM image_variants WHERE image_id = ?1", [id])?;
+    insert_image_variants(&tx, id, variants)?;
+    let freed = unreferenced_blob_keys(&tx, previous)?;
+    tx.commit()?;
+    Ok(freed)
+}
+
+/// What is stored for an image: the image itself and each of its variants, without the bytes.
+pub fn get_image_encodings(conn: &Connection, id: &str) -> Result<Vec<images::StoredEncoding>> {
+    let mut stmt = conn.prepare(
+        "SELECT width, content_type, 1 FROM images WHERE id = ?1
+         UNION ALL
+         SELECT width, content_type, 0 FROM image_variants WHERE image_id = ?1",
+    )?;
+    let encodings = stmt.query_map([id], |row| {
+        Ok(images::StoredEncoding {
+            width: row.get(0)?,
+            content_type: row.get(1)?,
+            is_original: row.get(2)?,
+        })
+    })?;
+    encodings.collect()
+}
+
+/// Widths stored for each uploaded image among `urls`, narrowest first, keyed by
+/// URL. Images hosted elsewhere or of unknown width are left out.
+pub fn get_image_widths(conn: &Connection, urls: &[String]) -> Result<std::collections::HashMap<String, Vec<u32>>> {
+    let mut stmt = conn.prepare(
+        "SELECT width FROM images WHERE id = ?1 AND width IS NOT NULL
+         UNION
+         SELECT width FROM image_variants WHERE image_id = ?1
+         ORDER BY width",
+    )?;
+    let mut widths = std::collections::HashMap::new();
+    for url in urls {
+        let Some(id) = images::upload_id(url) else { continue };
+        let found = stmt.query_map([id], |row| row.get(0))?.collect::<Result<Vec<u32>>>()?;
+        if !found.is_empty() {
+            widths.insert(url.clone(), found);
+        }
+    }
+    Ok(widths)
+}
+
+/// Content type and blob key of one encoding picked from `get_image_encodings`.
+pub fn get_image_blob(conn: &Connection, id: &str, encoding: &images::StoredEncoding) -> Result<(String, String)> {
+    if encoding.is_original {
+        return get_image_file(conn, id).map(|(_, content_type, key)| (content_type, key));
+    }
     conn.query_row(
-        "SELECT content_type, data FROM images WHERE id = ?1",
-        [id],
-        |row| {
-            let content_type: String = row.get(0)?;
-            let data: Vec<u8> = row.get(1)?;
-            Ok((content_type, data))
-        },
+        "SELECT content_type, blob_key FROM image_variants WHERE image_id = ?1 AND width = ?2 AND content_type = ?3",
+        rusqlite::params![id, encoding.width, encoding.content_type],
+        |row| Ok((row.get(0)?, row.get(1)?)),
     )
 }
 
-pub fn get_image_with_filename(conn: &Connection, id: &str) -> Result<(String, String, Vec<u8>)> {
+/// Filename, content type and blob key of an upload as stored.
+pub fn get_image_file(conn: &Connection, id: &str) -> Result<(String, String, String)> {
     conn.query_row(
-        "SELECT filename, content_type, data FROM images WHERE id = ?1",
+        "SELECT filename, content_type, blob_key FROM images WHERE id = ?1",
         [id],
-        |row| {
-            let filename: String = row.get(0)?;
-            let content_type: String = row.get(1)?;
-            let data: Vec<u8> = row.get(2)?;
-            Ok((filename, content_type, data))
-        },
+        |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
     )
 }
 
-pub fn delete_image(conn: &Connection, id: &str) -> Result<()> {
-    conn.execute("DELETE FROM images WHERE id = ?1", [id])?;
-    Ok(())
+fn image_blob_keys(conn: &Connection, id: &str) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT blob_key FROM images WHERE id = ?1
+         UNION
+         SELECT blob_key FROM image_variants WHERE image_id = ?1",
+    )?;
+    let keys = stmt.query_map([id], |row| row.get(0))?;
+    keys.collect()
+}
+
+/// Which of `keys` no image or variant refers to; identical uploads share a blob.
+fn unreferenced_blob_keys(conn: &Connection, keys: Vec<String>) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT EXISTS (SELECT 1 FROM images WHERE blob_key = ?1)
+             OR EXISTS (SELECT 1 FROM image_variants WHERE blob_key = ?1)",
+    )?;
+    let mut unreferenced = Vec::new();
+    for key in keys {
+        let used: bool = stmt.query_row([&key], |row| row.get(0))?;
+        if !used {
+            unreferenced.push(key);
+        }
+    }
+    Ok(unreferenced)
+}
+
+/// Deletes an upload along with its variants. Returns `None` if there was no
+/// such upload, otherwise the blob keys nothing refers to any more.
+pub fn delete_image(conn: &Connection, id: &str) -> Result<Option<Vec<String>>> {
+    let tx = conn.unchecked_transaction()?;
+    let keys = image_blob_keys(&tx, id)?;
+    if tx.execute("DELETE FROM images WHERE id = ?1", [id])? == 0 {
+        return Ok(None);
+    }
+    let freed = unreferenced_blob_keys(&tx, keys)?;
+    tx.commit()?;
+    Ok(Some(freed))
+}
+
+/// Every blob key in use, for `portfolio move-blobs`.
+pub fn get_blob_keys(conn: &Connection) -> Result<Vec<String>> {
+    let mut stmt = conn.prepare(
+        "SELECT blob_key FROM images
+         UNION
+         SELECT blob_key FROM image_variants",
+    )?;
+    let keys = stmt.query_map([], |row| row.get(0))?;
+    keys.collect()
+}
+
+/// Points every image and variant stored under `old_key` at `new_key`.
+pub fn rename_blob_key(conn: &Connection, old_key: &str, new_key: &str) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("UPDATE images SET blob_key = ?2 WHERE blob_key = ?1", [old_key, new_key])?;
+    tx.execute("UPDATE image_variants SET blob_key = ?2 WHERE blob_key = ?1", [old_key, new_key])?;
+    tx.commit()
+}
+
+// Media library functions
+
+// Everything that can point at an upload: what it is, its title, where it is edited,
+// the text its upload URLs are found in, and whether it is in the trash. Revisions
+// don't count, or nothing that was ever used could be removed.
+const MEDIA_REFERENCES: &str = "
+    SELECT 'Profile', name, '/admin/profile',
+           COALESCE(avatar_url, '') || ' ' || COALESCE(resume_url, '') || ' ' || bio, 0
+    FROM profile
+    UNION ALL
+    SELECT 'Project', title, '/admin/projects/edit/' || id,
+           COALESCE(image_url, '') || ' ' || description || ' ' || COALESCE(content, ''), deleted_at IS NOT NULL
+    FROM projects
+    UNION ALL
+    SELECT 'Blog', title, '/admin/blogs/edit/' || id,
+           COALESCE(image_url, '') || ' ' || excerpt || ' ' || content, deleted_at IS NOT NULL
+    FROM blogs
+    UNION ALL
+    SELECT 'Service', name, '/admin/services/edit/' || id,
+           COALESCE(image_url, '') || ' ' || COALESCE(description, ''), deleted_at IS NOT NULL
+    FROM services
+    UNION ALL
+    SELECT 'Skill icon', name, '/admin/skills', COALESCE(icon_url, ''), deleted_at IS NOT NULL
+    FROM skills
+    UNION ALL
+    SELECT 'Experience', position || ' at ' || company, '/admin/experience/edit/' || id,
+           COALESCE(description, ''), deleted_at IS NOT NULL
+    FROM experience
+    UNION ALL
+    SELECT 'Education', degree || ', ' || institution, '/admin/education/edit/' || id,
+           COALESCE(description, ''), deleted_at IS NOT NULL
+    FROM education
+    UNION ALL
+    SELECT 'Site content', key, '/admin/site-content', value, 0
+    FROM site_content";
+
+/// Ids of the uploads linked from `text` as `/images/{id}` or `/files/{id}`.
+fn upload_ids(text: &str) -> impl Iterator<Item = &str> {
+    ["/images/", "/files/"].into_iter().flat_map(move |prefix| {
+        text.match_indices(prefix).map(move |(start, _)| {
+            let rest = &text[start + prefix.len()..];
+            let end = rest.find(|c: char| !c.is_ascii_alphanumeric() && c != '-').unwrap_or(rest.len());
+            &rest[..end]
+        })
+    })
+}
+
+/// Where each upload is used, keyed by upload id.
+pub fn get_media_usages(conn: &Connection) -> Result<std::collections::HashMap<String, Vec<MediaUsage>>> {
+    let mut stmt = conn.prepare(MEDIA_REFERENCES)?;
+    let mut rows = stmt.query([])?;
+    let mut usages: std::collections::HashMap<String, Vec<MediaUsage>> = std::collections::HashMap::new();
+    while let Some(row) = rows.next()? {
+        let text: String = row.get(3)?;
+        let ids: std::collections::BTreeSet<&str> = upload_ids(&text).filter(|id| !id.is_empty()).collect();
+        if ids.is_empty() {
+            continue;
+        }
+        
+        let in_trash: bool = row.get(4)?;
+        let usage = MediaUsage {
+            kind: row.get(0)?,
+            title: row.get(1)?,
+            // Trashed items can't be edited until they are restored
+            edit_url: if in_trash { "/admin/trash".to_string() } else { row.get(2)? },
+            in_trash,
+        };
+        for id in ids {
+            usages.entry(id.to_string()).or_default().push(usage.clone());
+        }
+    }
+    Ok(usages)
+}
+
+/// Every upload, newest first, with where it is used.
+pub fn get_media(conn: &Connection) -> Result<Vec<MediaItem>> {
+    let mut usages = get_media_usages(conn)?;
+    let mut stmt = conn.prepare(
+        "SELECT id, filename, content_type, COALESCE(size, 0),
+                (SELECT COALESCE(SUM(v.size), 0) FROM image_variants v WHERE v.image_id = images.id),
+                width, height, created_at
+         FROM images
+         ORDER BY created_at DESC",
+    )?;
+    let items = stmt.query_map([], |row| {
+        let id: String = row.get(0)?;
+        let content_type: String = row.get(2)?;
+        let is_image = content_type.starts_with("image/");
+        Ok(MediaItem {
+            url: format!("{}/{}", if is_image { "/images" } else { "/files" }, id),
+            usages: usages.remove(&id).unwrap_or_default(),
+            id,
+            filename: row.get(1)?,
+            content_type,
+            is_image,
+            size: row.get(3)?,
+            variants_size: row.get(4)?,
+            width: row.get(5)?,
+            height: row.get(6)?,
+            created_at: row.get(7)?,
+        })
+    })?;
+    items.collect()
+}
+
+/// Deletes the uploads nothing refers to, leaving alone those uploaded at or after
+/// `uploaded_before` (their form may not have been saved yet). Returns how many
+/// went and the blob keys nothing refers to any more.
+pub fn delete_unused_media(conn: &Connection, uploaded_before: &str) -> Result<(usize, Vec<String>)> {
+    let tx = conn.unchecked_transaction()?;
+    let used = get_media_usages(&tx)?;
+    let candidates: Vec<String> = {
+        let mut stmt = tx.prepare("SELECT id FROM images WHERE created_at < ?1")?;
+        let ids = stmt.query_map([uploaded_before], |row| row.get(0))?;
+        ids.collect::<Result<_>>()?
+    };
+    
+    let mut removed = 0;
+    let mut keys = Vec::new();
+    for id in candidates.iter().filter(|id| !used.contains_key(id.as_str())) {
+        keys.extend(image_blob_keys(&tx, id)?);
+        removed += tx.execute("DELETE FROM images WHERE id = ?1", [id])?;
+    }
+    keys.sort();
+    keys.dedup();
+    let freed = unreferenced_blob_keys(&tx, keys)?;
+    tx.commit()?;
+    Ok((removed, freed))
 }
 
 // Service functions
 pub fn get_services(conn: &Connection) -> Result<Vec<Service>> {
-    let mut stmt = conn.prepare("SELECT id, name, description, image_url, icon, order_index FROM services ORDER BY order_index")?;
+    let mut stmt = conn.prepare("SELECT id, name, description, image_url, icon, order_index FROM services WHERE deleted_at IS NULL ORDER BY order_index")?;
     let services = stmt.query_map([], |row| {
         Ok(Service {
             id: row.get(0)?,
@@ -912,7 +1597,7 @@ pub fn get_services(conn: &Connection) -> Result<Vec<Service>> {
 
 pub fn get_service(conn: &Connection, id: i32) -> Result<Service> {
     conn.query_row(
-        "SELECT id, name, description, image_url, icon, order_index FROM services WHERE id = ?1",
+        "SELECT id, name, description, image_url, icon, order_index FROM services WHERE id = ?1 AND deleted_at IS NULL",
         [id],
         |row| {
             Ok(Service {
@@ -935,17 +1620,14 @@ pub fn add_service(conn: &Connection, form: &ServiceForm) -> Result<i32> {
     Ok(conn.last_insert_rowid() as i32)
 }
 
-pub fn update_service(conn: &Connection, id: i32, form: &ServiceForm) -> Result<()> {
-    conn.execute(
-        "UPDATE services SET name = ?1, description = ?2, image_url = ?3, icon = ?4, order_index = ?5 WHERE id = ?6",
+/// Returns false when there is no such service outside the trash.
+pub fn update_service(conn: &Connection, id: i32, form: &ServiceForm) -> Result<bool> {
+    let changed = conn.execute(
+        "UPDATE services SET name = ?1, description = ?2, image_url = ?3, icon = ?4, order_index = ?5
+         WHERE id = ?6 AND deleted_at IS NULL",
         rusqlite::params![form.name, form.description, form.image_url, form.icon, form.order_index, id],
     )?;
-    Ok(())
-}
-
-pub fn delete_service(conn: &Connection, id: i32) -> Result<()> {
-    conn.execute("DELETE FROM services WHERE id = ?1", [id])?;
-    Ok(())
+    Ok(changed > 0)
 }
 
 // Email Settings functions
@@ -1126,6 +1808,9 @@ fn init_default_site_content(conn: &Connection) -> Result<()> {
         ("nav_projects", "Projects", "nav", "Projects navigation link"),
         ("nav_blog", "Blog", "nav", "Blog navigation link"),
         ("nav_contact", "Contact", "nav", "Contact navigation link"),
+        
+        // Search Engines
+        ("robots_disallow", "", "seo", "Extra paths hidden from crawlers in robots.txt (comma-separated; /admin is always hidden)"),
     ];
     
     for (key, value, section, description) in defaults {
@@ -1145,10 +1830,8 @@ pub fn get_site_content(conn: &Connection) -> Result<std::collections::HashMap<S
     })?;
     
     let mut content = std::collections::HashMap::new();
-    for row in rows {
-        if let Ok((key, value)) = row {
-            content.insert(key, value);
-        }
+    for (key, value) in rows.flatten() {
+        content.insert(key, value);
     }
     Ok(content)
 }
@@ -1165,28 +1848,397 @@ pub fn get_site_content_by_section(conn: &Connection) -> Result<std::collections
     })?;
     
     let mut by_section: std::collections::HashMap<String, Vec<SiteContentItem>> = std::collections::HashMap::new();
-    for row in rows {
-        if let Ok(item) = row {
-            by_section.entry(item.section.clone()).or_insert_with(Vec::new).push(item);
-        }
+    for item in rows.flatten() {
+        by_section.entry(item.section.clone()).or_default().push(item);
     }
     Ok(by_section)
 }
 
-pub fn update_site_content(conn: &Connection, key: &str, value: &str) -> Result<()> {
-    conn.execute(
-        "UPDATE site_content SET value = ?1 WHERE key = ?2",
-        rusqlite::params![value, key],
-    )?;
-    Ok(())
+pub fn update_site_content_batch(
+    conn: &Connection,
+    updates: &std::collections::HashMap<String, String>,
+    editor_id: Option<i32>,
+) -> Result<()> {
+    save_site_content(conn, updates, editor_id, "")
 }
 
-pub fn update_site_content_batch(conn: &Connection, updates: &std::collections::HashMap<String, String>) -> Result<()> {
+fn save_site_content(
+    conn: &Connection,
+    updates: &std::collections::HashMap<String, String>,
+    editor_id: Option<i32>,
+    note: &str,
+) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    record_baseline(&tx, RevisionKind::SiteContent, 0, || site_content_snapshot(&tx))?;
     for (key, value) in updates {
-        conn.execute(
+        tx.execute(
             "UPDATE site_content SET value = ?1 WHERE key = ?2",
             rusqlite::params![value, key],
         )?;
     }
+    record_revision(&tx, RevisionKind::SiteContent, 0, &site_content_snapshot(&tx)?, note, editor_id)?;
+    tx.commit()
+}
+
+fn site_content_snapshot(conn: &Connection) -> Result<String> {
+    // Sorted so consecutive snapshots line up key by key
+    let values: std::collections::BTreeMap<String, String> = get_site_content(conn)?.into_iter().collect();
+    to_snapshot(&values)
+}
+
+// Revision functions
+
+fn to_snapshot(value: &impl serde::Serialize) -> Result<String> {
+    serde_json::to_string(value).map_err(|e| rusqlite::Error::ToSqlConversionFailure(Box::new(e)))
+}
+
+fn from_snapshot<T: serde::de::DeserializeOwned>(snapshot: &str) -> Result<T> {
+    serde_json::from_str(snapshot)
+        .map_err(|e| rusqlite::Error::FromSqlConversionFailure(3, rusqlite::types::Type::Text, Box::new(e)))
+}
+
+/// Puts the item back the way it was in `revision`, recording that as a new revision.
+/// Without `may_publish` a restored post is saved as an unscheduled draft. Returns
+/// false when the item is in the trash or gone.
+pub fn restore_revision(conn: &Connection, revision: &Revision, editor_id: Option<i32>, may_publish: bool) -> Result<bool> {
+    let note = format!("Restored revision #{}", revision.id);
+    match revision.kind {
+        RevisionKind::Blog => {
+            let mut blog: BlogForm = from_snapshot(&revision.snapshot)?;
+            if !may_publish {
+                blog.published = None;
+                blog.publish_at = None;
+            }
+            save_blog(conn, revision.entity_id, &blog, editor_id, &note)
+        }
+        RevisionKind::Project => {
+            let project: ProjectForm = from_snapshot(&revision.snapshot)?;
+            save_project(conn, revision.entity_id, &project, editor_id, &note)
+        }
+        RevisionKind::SiteContent => {
+            let values: std::collections::HashMap<String, String> = from_snapshot(&revision.snapshot)?;
+            save_site_content(conn, &values, editor_id, &note)?;
+            Ok(true)
+        }
+    }
+}
+
+fn record_revision(
+    conn: &Connection,
+    kind: RevisionKind,
+    entity_id: i32,
+    snapshot: &str,
+    note: &str,
+    author_id: Option<i32>,
+) -> Result<()> {
+    let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    conn.execute(
+        "INSERT INTO revisions (entity, entity_id, snapshot, note, author_id, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        rusqlite::params![kind.as_str(), entity_id, snapshot, note, author_id, now],
+    )?;
+    Ok(())
+}
+
+/// Items saved before revisions existed (or seeded at first start) have no history yet;
+/// keep their current state as the first revision before it is overwritten.
+fn record_baseline(conn: &Connection, kind: RevisionKind, entity_id: i32, snapshot: impl FnOnce() -> Result<String>) -> Result<()> {
+    let has_history: bool = conn.query_row(
+        "SELECT EXISTS (SELECT 1 FROM revisions WHERE entity = ?1 AND entity_id = ?2)",
+        rusqlite::params![kind.as_str(), entity_id],
+        |row| row.get(0),
+    )?;
+    if has_history {
+        return Ok(());
+    }
+    record_revision(conn, kind, entity_id, &snapshot()?, "Earlier version", None)
+}
+
+fn delete_revisions(conn: &Connection, kind: RevisionKind, entity_id: i32) -> Result<()> {
+    conn.execute(
+        "DELETE FROM revisions WHERE entity = ?1 AND entity_id = ?2",
+        rusqlite::params![kind.as_str(), entity_id],
+    )?;
     Ok(())
 }
+
+const REVISION_SELECT: &str = "SELECT r.id, r.entity, r.entity_id, r.snapshot, r.note, a.username, r.created_at
+    FROM revisions r LEFT JOIN admin a ON a.id = r.author_id";
+
+fn revision_from_row(row: &rusqlite::Row) -> Result<Revision> {
+    let entity: String = row.get(1)?;
+    Ok(Revision {
+        id: row.get(0)?,
+        kind: RevisionKind::parse(&entity).ok_or_else(|| {
+            rusqlite::Error::FromSqlConversionFailure(1, rusqlite::types::Type::Text, format!("unknown revision entity {}", entity).into())
+        })?,
+        entity_id: row.get(2)?,
+        snapshot: row.get(3)?,
+        note: row.get(4)?,
+        author: row.get(5)?,
+        created_at: row.get(6)?,
+    })
+}
+
+/// The most recent revisions of an item, newest first.
+pub fn get_revisions(conn: &Connection, kind: RevisionKind, entity_id: i32, limit: i64) -> Result<Vec<Revision>> {
+    let mut stmt = conn.prepare(&format!(
+        "{} WHERE r.entity = ?1 AND r.entity_id = ?2 ORDER BY r.id DESC LIMIT ?3",
+        REVISION_SELECT
+    ))?;
+    let revisions = stmt.query_map(rusqlite::params![kind.as_str(), entity_id, limit], revision_from_row)?;
+    revisions.collect()
+}
+
+pub fn get_revision(conn: &Connection, id: i64) -> Result<Revision> {
+    conn.query_row(&format!("{} WHERE r.id = ?1", REVISION_SELECT), [id], revision_from_row)
+}
+
+/// Marks the start of a matched term in search snippets; replaced with markup after escaping.
+pub const SNIPPET_START: &str = "\u{2}";
+/// Marks the end of a matched term in search snippets.
+pub const SNIPPET_END: &str = "\u{3}";
+
+/// Turns free text into an FTS5 query: every word must match, as a prefix,
+/// and quoting keeps operators and punctuation from being parsed as syntax.
+fn fts_query(input: &str) -> Option<String> {
+    let terms: Vec<String> = input
+        .split_whitespace()
+        .map(|term| format!("\"{}\"*", term.replace('"', "\"\"")))
+        .collect();
+    if terms.is_empty() { None } else { Some(terms.join(" ")) }
+}
+
+fn search_result_from_row(row: &rusqlite::Row) -> Result<SearchResult> {
+    Ok(SearchResult {
+        kind: row.get(0)?,
+        id: row.get(1)?,
+        title: row.get(2)?,
+        slug: row.get(3)?,
+        snippet: row.get(4)?,
+        published: row.get(5)?,
+        created_at: row.get(6)?,
+    })
+}
+
+/// Ranked search over blog posts and projects. Drafts are only included with
+/// `include_drafts`, for the admin panel.
+pub fn search_content(conn: &Connection, query: &str, include_drafts: bool, limit: i64) -> Result<Vec<SearchResult>> {
+    let Some(query) = fts_query(query) else {
+        return Ok(Vec::new());
+    };
+    
+    // Titles weigh most, then summaries and tags, then the body
+    let mut stmt = conn.prepare(&format!(
+        "SELECT 'blog', b.id, b.title, b.slug, snippet(blogs_fts, -1, ?2, ?3, '…', 24),
+                b.published, b.created_at, bm25(blogs_fts, 10.0, 4.0, 1.0, 4.0) AS score
+         FROM blogs_fts JOIN blogs b ON b.id = blogs_fts.rowid
+         WHERE blogs_fts MATCH ?1 AND b.deleted_at IS NULL AND (?4 OR ({}))
+         UNION ALL
+         SELECT 'project', p.id, p.title, p.slug, snippet(projects_fts, -1, ?2, ?3, '…', 24),
+                1, p.created_at, bm25(projects_fts, 10.0, 4.0, 1.0, 4.0) AS score
+         FROM projects_fts JOIN projects p ON p.id = projects_fts.rowid
+         WHERE projects_fts MATCH ?1 AND p.deleted_at IS NULL
+         ORDER BY score
+         LIMIT ?5",
+        BLOG_IS_LIVE
+    ))?;
+    let results = stmt.query_map(
+        rusqlite::params![query, SNIPPET_START, SNIPPET_END, include_drafts, limit],
+        search_result_from_row,
+    )?;
+    results.collect()
+}
+
+/// Ranked search over contact messages, for the admin panel.
+pub fn search_messages(conn: &Connection, query: &str, limit: i64) -> Result<Vec<SearchResult>> {
+    let Some(query) = fts_query(query) else {
+        return Ok(Vec::new());
+    };
+    
+    let mut stmt = conn.prepare(
+        "SELECT 'message', m.id, m.subject || ' (' || m.name || ')', '',
+                snippet(messages_fts, -1, ?2, ?3, '…', 24), 1, m.created_at
+         FROM messages_fts JOIN messages m ON m.id = messages_fts.rowid
+         WHERE messages_fts MATCH ?1 AND m.deleted_at IS NULL
+         ORDER BY bm25(messages_fts, 2.0, 2.0, 4.0, 1.0)
+         LIMIT ?4",
+    )?;
+    let results = stmt.query_map(
+        rusqlite::params![query, SNIPPET_START, SNIPPET_END, limit],
+        search_result_from_row,
+    )?;
+    results.collect()
+}
+
+// Trash functions
+
+/// Table holding each kind of trashable item, and the SQL that names an item on the trash page.
+fn trash_source(kind: TrashKind) -> (&'static str, &'static str) {
+    match kind {
+        TrashKind::Project => ("projects", "title"),
+        TrashKind::Blog => ("blogs", "title"),
+        TrashKind::Service => ("services", "name"),
+        TrashKind::Experience => ("experience", "position || ' at ' || company"),
+        TrashKind::Education => ("education", "degree || ', ' || institution"),
+        TrashKind::Skill => ("skills", "name"),
+        TrashKind::Message => ("messages", "subject || ' (' || name || ')'"),
+    }
+}
+
+/// Moves an item to the trash, hiding it everywhere. Returns false when there is no
+/// such item outside the trash.
+pub fn trash_item(conn: &Connection, kind: TrashKind, id: i32) -> Result<bool> {
+    let (table, _) = trash_source(kind);
+    let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    let changed = conn.execute(
+        &format!("UPDATE {} SET deleted_at = ?1 WHERE id = ?2 AND deleted_at IS NULL", table),
+        rusqlite::params![now, id],
+    )?;
+    Ok(changed > 0)
+}
+
+/// Takes an item back out of the trash. Returns false when it isn't in the trash.
+pub fn restore_trashed_item(conn: &Connection, kind: TrashKind, id: i32) -> Result<bool> {
+    let (table, _) = trash_source(kind);
+    let changed = conn.execute(
+        &format!("UPDATE {} SET deleted_at = NULL WHERE id = ?1 AND deleted_at IS NOT NULL", table),
+        [id],
+    )?;
+    Ok(changed > 0)
+}
+
+/// Deletes a trashed item for good. Returns false when it isn't in the trash.
+pub fn purge_item(conn: &Connection, kind: TrashKind, id: i32) -> Result<bool> {
+    let (table, _) = trash_source(kind);
+    let tx = conn.unchecked_transaction()?;
+    let purged = tx.execute(&format!("DELETE FROM {} WHERE id = ?1 AND deleted_at IS NOT NULL", table), [id])? > 0;
+    if purged {
+        purge_related(&tx, kind, id)?;
+    }
+    tx.commit()?;
+    Ok(purged)
+}
+
+/// Purges everything that has been in the trash for more than `days` days.
+/// Returns how many items were purged.
+pub fn purge_expired_trash(conn: &Connection, days: i64) -> Result<usize> {
+    let cutoff = format!("-{} days", days);
+    let tx = conn.unchecked_transaction()?;
+    let mut purged = 0;
+    for kind in TrashKind::ALL {
+        let (table, _) = trash_source(kind);
+        let ids = tx
+            .prepare(&format!(
+                "DELETE FROM {} WHERE deleted_at IS NOT NULL AND deleted_at <= datetime('now', ?1) RETURNING id",
+                table
+            ))?
+            .query_map([&cutoff], |row| row.get(0))?
+            .collect::<Result<Vec<i32>>>()?;
+        for &id in &ids {
+            purge_related(&tx, kind, id)?;
+        }
+        purged += ids.len();
+    }
+    tx.commit()?;
+    Ok(purged)
+}
+
+/// Cleans up what a purged item leaves behind: the history of a post or project and
+/// tags nothing uses any more. Tag links go with the row itself.
+fn purge_related(conn: &Connection, kind: TrashKind, id: i32) -> Result<()> {
+    match kind {
+        TrashKind::Blog => {
+            delete_revisions(conn, RevisionKind::Blog, id)?;
+            delete_unused_tags(conn)
+        }
+        TrashKind::Project => {
+            delete_revisions(conn, RevisionKind::Project, id)?;
+            delete_unused_tags(conn)
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Everything in the trash, most recently deleted first.
+pub fn get_trash(conn: &Connection) -> Result<Vec<TrashItem>> {
+    let sources: Vec<String> = TrashKind::ALL
+        .iter()
+        .map(|kind| {
+            let (table, title) = trash_source(*kind);
+            format!(
+                "SELECT '{}', id, {}, deleted_at FROM {} WHERE deleted_at IS NOT NULL",
+                kind.as_str(), title, table
+            )
+        })
+        .collect();
+    let mut stmt = conn.prepare(&format!("{} ORDER BY deleted_at DESC", sources.join(" UNION ALL ")))?;
+    let items = stmt.query_map([], |row| {
+        let kind: String = row.get(0)?;
+        Ok(TrashItem {
+            kind: TrashKind::parse(&kind).ok_or_else(|| {
+                rusqlite::Error::FromSqlConversionFailure(0, rusqlite::types::Type::Text, format!("unknown trash kind {}", kind).into())
+            })?,
+            id: row.get(1)?,
+            title: row.get::<_, Option<String>>(2)?.unwrap_or_default(),
+            deleted_at: row.get(3)?,
+        })
+    })?;
+    items.collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unique_slug_skips_taken_and_trashed_slugs() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE TABLE blogs (id INTEGER PRIMARY KEY, slug TEXT NOT NULL UNIQUE, deleted_at TEXT);
+             INSERT INTO blogs (id, slug, deleted_at) VALUES (1, 'hello-world', '2024-01-01 00:00:00');
+             INSERT INTO blogs (id, slug) VALUES (2, 'hello-world-2');",
+        )
+        .unwrap();
+
+        assert_eq!(unique_slug(&conn, "blogs", "Hello, World!", None).unwrap(), "hello-world-3");
+        assert_eq!(unique_slug(&conn, "blogs", "Hello World 2", Some(2)).unwrap(), "hello-world-2");
+        assert_eq!(unique_slug(&conn, "blogs", "Something else", None).unwrap(), "something-else");
+    }
+
+    #[test]
+    fn parse_tags_trims_and_drops_blanks() {
+        assert_eq!(parse_tags(" rust ,, web,  "), vec!["rust", "web"]);
+        assert!(parse_tags(" , ,").is_empty());
+    }
+
+    #[test]
+    fn parse_tags_keeps_the_first_spelling_of_a_repeat() {
+        assert_eq!(parse_tags("Rust, rust, SQLite, RUST, sqlite"), vec!["Rust", "SQLite"]);
+    }
+
+    #[test]
+    fn fts_query_quotes_each_word_as_a_prefix() {
+        assert_eq!(fts_query("  rust  web "), Some("\"rust\"* \"web\"*".to_string()));
+        assert_eq!(fts_query("say \"hi\""), Some("\"say\"* \"\"\"hi\"\"\"*".to_string()));
+        assert_eq!(fts_query(" \t "), None);
+    }
+
+    #[test]
+    fn fts_query_keeps_operators_from_being_parsed() {
+        let conn = Connection::open_in_memory().unwrap();
+        conn.execute_batch(
+            "CREATE VIRTUAL TABLE docs USING fts5(body);
+             INSERT INTO docs (body) VALUES ('rust AND sqlite: a NEAR miss (really)');",
+        )
+        .unwrap();
+
+        for input in ["AND", "NEAR(", "sqlite:", "(really", "body:rust", "rust OR", "\"", "*"] {
+            let query = fts_query(input).unwrap();
+            let result = conn.query_row("SELECT count(*) FROM docs WHERE docs MATCH ?1", [&query], |row| row.get::<_, i64>(0));
+            assert!(result.is_ok(), "{} gave {:?}", input, result);
+        }
+        let query = fts_query("rus sql").unwrap();
+        let count: i64 = conn.query_row("SELECT count(*) FROM docs WHERE docs MATCH ?1", [&query], |row| row.get(0)).unwrap();
+        assert_eq!(count, 1);
+    }
+}
@@ -0,0 +1,167 @@
+use actix_session::SessionInsertError;
+use actix_web::body::MessageBody;
+use actix_web::dev::ServiceResponse;
+use actix_web::error::BlockingError;
+use actix_web::http::{header, StatusCode};
+use actix_web::middleware::{ErrorHandlerResponse, ErrorHandlers};
+use actix_web::{web, HttpRequest, HttpResponse, ResponseError};
+use crate::AppState;
+use crate::db::{self, DbError};
+use crate::models::Profile;
+use crate::uploads::UploadError;
+
+/// Errors a request handler can fail with.
+#[derive(Debug)]
+pub enum AppError {
+    /// The named thing (e.g. `"Project"`) does not exist.
+    NotFound(&'static str),
+    Forbidden,
+    Database(DbError),
+    Template(tera::Error),
+    Session(SessionInsertError),
+    /// An uploaded form was refused.
+    Upload(UploadError),
+    /// A `web::block` task was cancelled.
+    Blocking,
+}
+
+impl std::fmt::Display for AppError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            AppError::NotFound(what) => write!(f, "{} not found", what),
+            AppError::Forbidden => write!(f, "You don't have permission to do that."),
+            AppError::Database(e) => write!(f, "{}", e),
+            AppError::Template(e) => write!(f, "template error: {:?}", e),
+            AppError::Session(e) => write!(f, "session error: {}", e),
+            AppError::Upload(e) => write!(f, "{}", e),
+            AppError::Blocking => write!(f, "background task was cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for AppError {}
+
+impl ResponseError for AppError {
+    fn status_code(&self) -> StatusCode {
+        match self {
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Forbidden => StatusCode::FORBIDDEN,
+            AppError::Upload(UploadError::TooLarge { .. }) => StatusCode::PAYLOAD_TOO_LARGE,
+            AppError::Upload(UploadError::Unsupported { .. }) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            AppError::Upload(_) => StatusCode::BAD_REQUEST,
+            _ => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn error_response(&self) -> HttpResponse {
+        let status = self.status_code();
+        if status.is_server_error() {
+            log::error!("{}", self);
+        }
+        // The page itself is rendered by `error_pages`
+        HttpResponse::build(status).finish()
+    }
+}
+
+impl From<DbError> for AppError {
+    fn from(e: DbError) -> Self {
+        AppError::Database(e)
+    }
+}
+
+impl From<tera::Error> for AppError {
+    fn from(e: tera::Error) -> Self {
+        AppError::Template(e)
+    }
+}
+
+impl From<BlockingError> for AppError {
+    fn from(_: BlockingError) -> Self {
+        AppError::Blocking
+    }
+}
+
+impl From<UploadError> for AppError {
+    fn from(e: UploadError) -> Self {
+        AppError::Upload(e)
+    }
+}
+
+impl From<SessionInsertError> for AppError {
+    fn from(e: SessionInsertError) -> Self {
+        AppError::Session(e)
+    }
+}
+
+/// Middleware that replaces the body of every 4xx/5xx response with the
+/// `error.html` page. JSON responses are left alone for the admin's fetch calls.
+pub fn error_pages<B: MessageBody + 'static>() -> ErrorHandlers<B> {
+    ErrorHandlers::new().default_handler(render_error_page)
+}
+
+fn render_error_page<B: MessageBody + 'static>(res: ServiceResponse<B>) -> actix_web::Result<ErrorHandlerResponse<B>> {
+    let is_json = res
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("application/json"));
+    if is_json {
+        return Ok(ErrorHandlerResponse::Response(res.map_into_left_body()));
+    }
+
+    let status = res.status();
+    // Only client errors explain themselves; server errors were logged above
+    let message = res
+        .response()
+        .error()
+        .filter(|_| status.is_client_error())
+        .map(|e| e.to_string());
+    let (req, _) = res.into_parts();
+
+    Ok(ErrorHandlerResponse::Future(Box::pin(async move {
+        let response = match error_page(&req, status, message).await {
+            Some(html) => HttpResponse::build(status).content_type("text/html").body(html),
+            None => HttpResponse::build(status)
+                .content_type("text/plain")
+                .body(status.canonical_reason().unwrap_or("Error")),
+        };
+        Ok(ServiceResponse::new(req, response).map_into_right_body())
+    })))
+}
+
+async fn error_page(req: &HttpRequest, status: StatusCode, message: Option<String>) -> Option<String> {
+    let data = req.app_data::<web::Data<AppState>>()?;
+
+    // The database may be why we are here, so fall back to an empty profile
+    let (profile, content) = db::call(&data.db, |conn| {
+        Ok((db::get_profile(conn)?, db::get_site_content(conn).unwrap_or_default()))
+    })
+    .await
+    .unwrap_or_else(|_| (Profile::default(), Default::default()));
+
+    let title = match status {
+        StatusCode::NOT_FOUND => "Page Not Found",
+        StatusCode::FORBIDDEN => "Access Denied",
+        _ => status.canonical_reason().unwrap_or("Error"),
+    };
+    let message = message.unwrap_or_else(|| match status {
+        StatusCode::NOT_FOUND => "The page you're looking for doesn't exist or has been moved.".to_string(),
+        s if s.is_server_error() => "Something went wrong on our end. Please try again later.".to_string(),
+        _ => "The request could not be completed.".to_string(),
+    });
+
+    let mut context = tera::Context::new();
+    context.insert("profile", &profile);
+    context.insert("content", &content);
+    context.insert("page_title", title);
+    context.insert("status", &status.as_u16());
+    context.insert("message", &message);
+
+    match data.tera.render("error.html", &context) {
+        Ok(html) => Some(html),
+        Err(e) => {
+            log::error!("Failed to render error page: {:?}", e);
+            None
+        }
+    }
+}
@@ -0,0 +1,136 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use crate::AppState;
+use crate::db;
+use crate::error::AppError;
+use crate::models::Blog;
+use crate::routes::{base_url, markdown_to_html, parse_timestamp};
+
+// Newest posts included in a feed
+const FEED_LIMIT: usize = 50;
+
+#[derive(Debug, Deserialize)]
+pub struct FeedQuery {
+    pub tag: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+struct FeedEntry {
+    title: String,
+    url: String,
+    excerpt: String,
+    content_html: String,
+    categories: Vec<String>,
+    /// The site owner's name; `Blog::author` is an admin login name and stays private.
+    author: String,
+    published: String,
+    updated: String,
+    pub_date: String,
+}
+
+fn split_tags(tags: &str) -> Vec<String> {
+    tags.split(',')
+        .map(str::trim)
+        .filter(|tag| !tag.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn has_tag(blog: &Blog, tag: &str) -> bool {
+    split_tags(&blog.tags).iter().any(|t| t.eq_ignore_ascii_case(tag))
+}
+
+/// Builds the context shared by the RSS and Atom templates. `path` is the
+/// feed's own path, used for its self link.
+async fn feed_context(
+    data: &web::Data<AppState>,
+    req: &HttpRequest,
+    query: FeedQuery,
+    path: &str,
+) -> Result<tera::Context, AppError> {
+    let (profile, blogs) = db::call(&data.db, |conn| {
+        Ok((db::get_profile(conn)?, db::get_published_blogs(conn)?))
+    }).await?;
+    
+    let site_url = base_url(data, req);
+    let tag = query.tag.map(|tag| tag.trim().to_string()).filter(|tag| !tag.is_empty());
+    let feed_url = match &tag {
+        Some(tag) => format!(
+            "{}{}?{}",
+            site_url,
+            path,
+            serde_urlencoded::to_string([("tag", tag)]).unwrap_or_default()
+        ),
+        None => format!("{}{}", site_url, path),
+    };
+    
+    let blogs: Vec<Blog> = blogs
+        .into_iter()
+        .filter(|blog| tag.as_deref().is_none_or(|tag| has_tag(blog, tag)))
+        .collect();
+    
+    let entries: Vec<FeedEntry> = blogs
+        .iter()
+        .take(FEED_LIMIT)
+        .map(|blog| {
+            let created = parse_timestamp(&blog.created_at);
+            let updated = parse_timestamp(&blog.updated_at).max(created);
+            FeedEntry {
+                title: blog.title.clone(),
+                url: format!("{}/blogs/{}", site_url, blog.slug),
+                excerpt: blog.excerpt.clone(),
+                content_html: markdown_to_html(&blog.content),
+                categories: split_tags(&blog.tags),
+                author: profile.name.clone(),
+                published: created.to_rfc3339(),
+                updated: updated.to_rfc3339(),
+                pub_date: created.to_rfc2822(),
+            }
+        })
+        .collect();
+    
+    // The feed changes whenever its newest-edited entry does
+    let updated = blogs
+        .iter()
+        .map(|blog| parse_timestamp(&blog.updated_at).max(parse_timestamp(&blog.created_at)))
+        .max()
+        .unwrap_or_else(Utc::now);
+    
+    let title = match &tag {
+        Some(tag) => format!("{} - Blog: {}", profile.name, tag),
+        None => format!("{} - Blog", profile.name),
+    };
+    
+    let mut context = tera::Context::new();
+    context.insert("profile", &profile);
+    context.insert("feed_title", &title);
+    context.insert("site_url", &site_url);
+    context.insert("feed_url", &feed_url);
+    context.insert("entries", &entries);
+    context.insert("updated", &updated.to_rfc3339());
+    context.insert("last_build_date", &updated.to_rfc2822());
+    Ok(context)
+}
+
+pub async fn rss(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    query: web::Query<FeedQuery>,
+) -> Result<HttpResponse, AppError> {
+    let context = feed_context(&data, &req, query.into_inner(), "/feed.xml").await?;
+    
+    let rendered = data.tera.render("feed.xml", &context)?;
+    Ok(HttpResponse::Ok().content_type("application/rss+xml; charset=utf-8").body(rendered))
+}
+
+pub async fn atom(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    query: web::Query<FeedQuery>,
+) -> Result<HttpResponse, AppError> {
+    let context = feed_context(&data, &req, query.into_inner(), "/atom.xml").await?;
+    
+    let rendered = data.tera.render("atom.xml", &context)?;
+    Ok(HttpResponse::Ok().content_type("application/atom+xml; charset=utf-8").body(rendered))
+}
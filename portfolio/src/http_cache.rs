@@ -0,0 +1,127 @@
+use actix_web::http::header::{EntityTag, ETag, HttpDate, IfModifiedSince, IfNoneMatch, LastModified};
+use actix_web::{HttpMessage, HttpRequest, HttpResponse, HttpResponseBuilder};
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+
+/// What a response can be revalidated against: its entity tag and, for pages,
+/// when what it was rendered from last changed.
+pub struct Validators {
+    pub etag: EntityTag,
+    pub last_modified: Option<DateTime<Utc>>,
+}
+
+impl Validators {
+    /// A strong validator for bytes served exactly as stored, from their content hash.
+    pub fn content(hash: &str) -> Self {
+        Validators { etag: EntityTag::new_strong(hash.to_string()), last_modified: None }
+    }
+
+    /// A weak validator for a rendered page. `inputs` is everything besides the
+    /// timestamp the page depends on; only a digest of it ends up in the tag.
+    pub fn page(updated_at: DateTime<Utc>, inputs: &[&[u8]]) -> Self {
+        let mut digest = Sha256::new();
+        digest.update(updated_at.timestamp().to_be_bytes());
+        for input in inputs {
+            digest.update((input.len() as u64).to_be_bytes());
+            digest.update(input);
+        }
+        let tag = format!("{:x}", digest.finalize())[..20].to_string();
+        Validators { etag: EntityTag::new_weak(tag), last_modified: Some(updated_at) }
+    }
+
+    /// Whether the client's cached copy is still current. `If-None-Match` wins
+    /// when both conditions are sent, as RFC 9110 asks.
+    pub fn is_fresh(&self, req: &HttpRequest) -> bool {
+        if let Some(if_none_match) = req.get_header::<IfNoneMatch>() {
+            return match if_none_match {
+                IfNoneMatch::Any => true,
+                IfNoneMatch::Items(tags) => tags.iter().any(|tag| tag.weak_eq(&self.etag)),
+            };
+        }
+        match (self.last_modified, req.get_header::<IfModifiedSince>()) {
+            (Some(last_modified), Some(IfModifiedSince(since))) => http_date(last_modified) <= since,
+            _ => false,
+        }
+    }
+
+    /// Adds the validators to a response.
+    pub fn apply(&self, response: &mut HttpResponseBuilder) {
+        response.insert_header(ETag(self.etag.clone()));
+        if let Some(last_modified) = self.last_modified {
+            response.insert_header(LastModified(http_date(last_modified)));
+        }
+    }
+
+    /// A `304 Not Modified` carrying the validators; it should also repeat the
+    /// `Cache-Control` and `Vary` headers of the full response.
+    pub fn not_modified(&self) -> HttpResponseBuilder {
+        let mut response = HttpResponse::NotModified();
+        self.apply(&mut response);
+        response
+    }
+}
+
+fn http_date(time: DateTime<Utc>) -> HttpDate {
+    HttpDate::from(std::time::SystemTime::from(time))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use actix_web::test::TestRequest;
+    use chrono::TimeZone;
+
+    fn page() -> Validators {
+        Validators::page(Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap(), &[b"/blogs"])
+    }
+
+    fn request(headers: &[(&str, &str)]) -> HttpRequest {
+        headers
+            .iter()
+            .fold(TestRequest::default(), |req, &header| req.insert_header(header))
+            .to_http_request()
+    }
+
+    #[test]
+    fn unconditional_requests_are_not_fresh() {
+        assert!(!page().is_fresh(&request(&[])));
+    }
+
+    #[test]
+    fn matches_entity_tags_weakly() {
+        let page = page();
+        let tag = page.etag.tag().to_string();
+        assert!(page.is_fresh(&request(&[("If-None-Match", &format!("W/\"{}\"", tag))])));
+        assert!(page.is_fresh(&request(&[("If-None-Match", &format!("\"other\", \"{}\"", tag))])));
+        assert!(page.is_fresh(&request(&[("If-None-Match", "*")])));
+        assert!(!page.is_fresh(&request(&[("If-None-Match", "W/\"other\"")])));
+    }
+
+    #[test]
+    fn pages_from_different_inputs_differ() {
+        let other = Validators::page(Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap(), &[b"/projects"]);
+        let tag = format!("W/\"{}\"", other.etag.tag());
+        assert!(!page().is_fresh(&request(&[("If-None-Match", &tag)])));
+    }
+
+    #[test]
+    fn compares_modification_dates() {
+        let page = page();
+        assert!(page.is_fresh(&request(&[("If-Modified-Since", "Wed, 01 May 2024 12:00:00 GMT")])));
+        assert!(page.is_fresh(&request(&[("If-Modified-Since", "Thu, 02 May 2024 00:00:00 GMT")])));
+        assert!(!page.is_fresh(&request(&[("If-Modified-Since", "Wed, 01 May 2024 11:59:59 GMT")])));
+        // Stored blobs carry no date to compare
+        let blob = Validators::content("abc123");
+        assert!(!blob.is_fresh(&request(&[("If-Modified-Since", "Thu, 02 May 2024 00:00:00 GMT")])));
+    }
+
+    #[test]
+    fn entity_tags_win_over_dates() {
+        let page = page();
+        let req = request(&[
+            ("If-None-Match", "W/\"other\""),
+            ("If-Modified-Since", "Thu, 02 May 2024 00:00:00 GMT"),
+        ]);
+        assert!(!page.is_fresh(&req));
+    }
+}
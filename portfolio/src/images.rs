@@ -0,0 +1,261 @@
+use std::collections::HashMap;
+use std::io::Cursor;
+use image::codecs::jpeg::JpegEncoder;
+use image::codecs::png::{CompressionType, FilterType as PngFilterType, PngEncoder};
+use image::codecs::webp::WebPEncoder;
+use image::imageops::FilterType;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader};
+use serde_json::Value;
+
+/// Widths of the narrower copies stored for uploaded images wider than them.
+pub const VARIANT_WIDTHS: [u32; 4] = [320, 640, 960, 1600];
+
+// Uploads larger than this in either direction are scaled down before being stored
+const MAX_DIMENSION: u32 = 2400;
+const JPEG_QUALITY: u8 = 82;
+const WEBP: &str = "image/webp";
+
+/// One stored encoding of an image.
+pub struct Encoded {
+    pub width: u32,
+    pub content_type: &'static str,
+    pub data: Vec<u8>,
+}
+
+/// An upload ready to store: the image itself plus its responsive variants.
+pub struct ProcessedImage {
+    /// The upload turned upright and scaled down to the size cap. JPEGs and WebPs are
+    /// re-encoded, which also strips their metadata (camera, location).
+    pub original: Encoded,
+    pub height: u32,
+    /// Narrower copies in the upload's format, and WebP copies at every width
+    /// (including full size) where WebP comes out smaller.
+    pub variants: Vec<Encoded>,
+}
+
+/// Decodes an uploaded JPEG, PNG or WebP and builds what gets stored for it.
+/// Returns `None` for anything else (GIFs keep their animation, documents are
+/// not images), which is then stored as uploaded.
+pub fn process(bytes: &[u8]) -> Option<ProcessedImage> {
+    let reader = ImageReader::new(Cursor::new(bytes)).with_guessed_format().ok()?;
+    let format = reader.format()?;
+    if !matches!(format, ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::WebP) {
+        return None;
+    }
+
+    let mut decoder = reader.into_decoder().ok()?;
+    // Phone cameras store photos sideways and record the rotation in EXIF,
+    // which re-encoding drops
+    let orientation = decoder.orientation().ok()?;
+    let mut image = DynamicImage::from_decoder(decoder).ok()?;
+    image.apply_orientation(orientation);
+    let oversized = image.width() > MAX_DIMENSION || image.height() > MAX_DIMENSION;
+    if oversized {
+        image = image.resize(MAX_DIMENSION, MAX_DIMENSION, FilterType::CatmullRom);
+    }
+
+    let mut original = encode(&image, format)?;
+    // PNG is lossless and rarely carries anything private, so an upload that
+    // needed no scaling is kept when our encoding is no smaller
+    if format == ImageFormat::Png && !oversized && bytes.len() <= original.data.len() {
+        original.data = bytes.to_vec();
+    }
+    let mut variants = Vec::new();
+    if format != ImageFormat::WebP {
+        push_smaller_webp(&mut variants, &image, &original);
+    }
+    for width in VARIANT_WIDTHS.into_iter().filter(|&width| width < image.width()) {
+        let height = (u64::from(image.height()) * u64::from(width) / u64::from(image.width())).max(1) as u32;
+        let resized = image.resize_exact(width, height, FilterType::CatmullRom);
+        let fallback = encode(&resized, format)?;
+        if format != ImageFormat::WebP {
+            push_smaller_webp(&mut variants, &resized, &fallback);
+        }
+        variants.push(fallback);
+    }
+    // A variant is only worth serving if it is lighter than the image itself
+    variants.retain(|variant| variant.data.len() < original.data.len());
+
+    Some(ProcessedImage { height: image.height(), original, variants })
+}
+
+fn encode(image: &DynamicImage, format: ImageFormat) -> Option<Encoded> {
+    let mut data = Vec::new();
+    let content_type = match format {
+        // JPEG has no alpha channel
+        ImageFormat::Jpeg => {
+            JpegEncoder::new_with_quality(&mut data, JPEG_QUALITY)
+                .encode_image(&DynamicImage::ImageRgb8(image.to_rgb8()))
+                .ok()?;
+            "image/jpeg"
+        }
+        ImageFormat::Png => {
+            image
+                .write_with_encoder(PngEncoder::new_with_quality(&mut data, CompressionType::Best, PngFilterType::Adaptive))
+                .ok()?;
+            "image/png"
+        }
+        _ => {
+            encode_webp(image, &mut data)?;
+            WEBP
+        }
+    };
+    Some(Encoded { width: image.width(), content_type, data })
+}
+
+fn encode_webp(image: &DynamicImage, data: &mut Vec<u8>) -> Option<()> {
+    // The WebP encoder only takes 8-bit RGB(A)
+    let image = if image.color().has_alpha() {
+        DynamicImage::ImageRgba8(image.to_rgba8())
+    } else {
+        DynamicImage::ImageRgb8(image.to_rgb8())
+    };
+    image.write_with_encoder(WebPEncoder::new_lossless(data)).ok()
+}
+
+/// Adds a WebP copy of `image` when it beats `fallback` on size; the encoder is
+/// lossless, so for photos it often doesn't.
+fn push_smaller_webp(variants: &mut Vec<Encoded>, image: &DynamicImage, fallback: &Encoded) {
+    let mut data = Vec::new();
+    if encode_webp(image, &mut data).is_some() && data.len() < fallback.data.len() {
+        variants.push(Encoded { width: image.width(), content_type: WEBP, data });
+    }
+}
+
+/// A stored encoding of an image, without its bytes.
+pub struct StoredEncoding {
+    /// `None` for images stored before variants existed, which count as the widest.
+    pub width: Option<u32>,
+    pub content_type: String,
+    /// Whether this is the image itself rather than one of its variants.
+    pub is_original: bool,
+}
+
+/// Picks what to send for `/images/{id}?w=`: the narrowest encoding at least
+/// `requested` wide (the widest one if none is, or if no width was asked for),
+/// preferring WebP when the browser accepts it.
+pub fn choose_encoding(stored: &[StoredEncoding], requested: Option<u32>, accepts_webp: bool) -> Option<&StoredEncoding> {
+    let usable: Vec<&StoredEncoding> = stored
+        .iter()
+        .filter(|encoding| encoding.is_original || accepts_webp || encoding.content_type != WEBP)
+        .collect();
+    let width = |encoding: &StoredEncoding| encoding.width.unwrap_or(u32::MAX);
+
+    let widest = usable.iter().map(|encoding| width(encoding)).max()?;
+    let target = requested
+        .and_then(|requested| {
+            usable
+                .iter()
+                .map(|encoding| width(encoding))
+                .filter(|&width| width >= requested)
+                .min()
+        })
+        .unwrap_or(widest);
+
+    usable
+        .into_iter()
+        .filter(|encoding| width(encoding) == target)
+        .min_by_key(|encoding| encoding.content_type != WEBP)
+}
+
+/// The id in the URL of an uploaded image (`/images/{id}`), if it is one.
+pub fn upload_id(url: &str) -> Option<&str> {
+    url.strip_prefix("/images/")
+        .filter(|id| !id.is_empty() && !id.contains(['/', '?', '#']))
+}
+
+/// Tera filter turning the URL of an uploaded image into a `srcset` value listing
+/// the widths stored for it, for example
+/// `<img src="{{ url }}" srcset="{{ url | srcset(widths=image_widths) }}" sizes="...">`,
+/// where `image_widths` maps image URLs to their widths (see `db::get_image_widths`).
+/// Gives an empty string for images hosted elsewhere or of unknown width (GIFs,
+/// SVGs, uploads from before processing), which the browser then takes from `src`.
+pub fn srcset_filter(value: &Value, args: &HashMap<String, Value>) -> tera::Result<Value> {
+    let url = value.as_str().unwrap_or_default();
+    let widths = args
+        .get("widths")
+        .and_then(|widths| widths.get(url))
+        .and_then(Value::as_array)
+        .filter(|_| upload_id(url).is_some());
+    let Some(widths) = widths else {
+        return Ok(Value::String(String::new()));
+    };
+
+    let candidates: Vec<String> = widths
+        .iter()
+        .filter_map(Value::as_u64)
+        .map(|width| format!("{}?w={} {}w", url, width, width))
+        .collect();
+    Ok(Value::String(candidates.join(", ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stored(width: Option<u32>, content_type: &str, is_original: bool) -> StoredEncoding {
+        StoredEncoding { width, content_type: content_type.to_string(), is_original }
+    }
+
+    fn photo() -> Vec<StoredEncoding> {
+        vec![
+            stored(Some(1200), "image/jpeg", true),
+            stored(Some(1200), WEBP, false),
+            stored(Some(320), "image/jpeg", false),
+            stored(Some(640), WEBP, false),
+            stored(Some(640), "image/jpeg", false),
+            stored(Some(960), "image/jpeg", false),
+        ]
+    }
+
+    fn pick(stored: &[StoredEncoding], requested: Option<u32>, accepts_webp: bool) -> (Option<u32>, &str) {
+        let encoding = choose_encoding(stored, requested, accepts_webp).unwrap();
+        (encoding.width, encoding.content_type.as_str())
+    }
+
+    #[test]
+    fn picks_the_narrowest_encoding_wide_enough() {
+        let photo = photo();
+        assert_eq!(pick(&photo, Some(500), false), (Some(640), "image/jpeg"));
+        assert_eq!(pick(&photo, Some(640), false), (Some(640), "image/jpeg"));
+        assert_eq!(pick(&photo, Some(100), true), (Some(320), "image/jpeg"));
+        assert_eq!(pick(&photo, Some(961), false), (Some(1200), "image/jpeg"));
+    }
+
+    #[test]
+    fn falls_back_to_the_widest_encoding() {
+        let photo = photo();
+        assert_eq!(pick(&photo, None, false), (Some(1200), "image/jpeg"));
+        assert_eq!(pick(&photo, Some(5000), false), (Some(1200), "image/jpeg"));
+    }
+
+    #[test]
+    fn prefers_webp_only_when_accepted() {
+        let photo = photo();
+        assert_eq!(pick(&photo, Some(500), true), (Some(640), WEBP));
+        assert_eq!(pick(&photo, None, true), (Some(1200), WEBP));
+        // An uploaded WebP is all there is to send
+        let webp = [stored(Some(800), WEBP, true)];
+        assert_eq!(pick(&webp, Some(500), false), (Some(800), WEBP));
+    }
+
+    #[test]
+    fn legacy_uploads_are_sent_whatever_the_width() {
+        let legacy = [stored(None, "image/gif", true)];
+        assert_eq!(pick(&legacy, Some(320), true), (None, "image/gif"));
+        assert!(choose_encoding(&[], None, true).is_none());
+    }
+
+    #[test]
+    fn srcset_lists_only_stored_widths() {
+        let url = "/images/abc";
+        let widths = serde_json::json!({ url: [320, 640, 720] });
+        let args = HashMap::from([("widths".to_string(), widths)]);
+        let srcset = |url: &str| srcset_filter(&Value::from(url), &args).unwrap();
+
+        assert_eq!(srcset(url), "/images/abc?w=320 320w, /images/abc?w=640 640w, /images/abc?w=720 720w");
+        assert_eq!(srcset("/images/unknown"), "");
+        assert_eq!(srcset("https://example.com/photo.jpg"), "");
+        assert_eq!(srcset_filter(&Value::from(url), &HashMap::new()).unwrap(), "");
+    }
+}
@@ -1,19 +1,46 @@
+mod blobs;
+mod cli;
+mod config;
+mod csrf;
 mod db;
+mod error;
+mod feeds;
+mod http_cache;
+mod images;
+mod migrations;
 mod models;
+mod pagination;
+mod preview;
+mod publishing;
+mod revisions;
 mod routes;
+mod seo;
+mod trash;
 mod admin;
 mod auth;
+mod two_factor;
+mod uploads;
 pub mod email;
 
 use actix_files as fs;
-use actix_session::{SessionMiddleware, storage::CookieSessionStore};
-use actix_web::{web, App, HttpServer, middleware::Logger, cookie::Key};
-use std::sync::Mutex;
+use actix_session::{SessionMiddleware, config::PersistentSession, storage::CookieSessionStore};
+use actix_web::{web, App, HttpServer, middleware::{self, Logger}, cookie::time::Duration};
+use chrono::SubsecRound;
 use tera::Tera;
 
 pub struct AppState {
-    pub db: Mutex<rusqlite::Connection>,
+    pub db: db::DbPool,
+    /// Where the bytes of uploads are kept.
+    pub blobs: blobs::SharedBlobStore,
     pub tera: Tera,
+    pub session_config: config::SessionConfig,
+    pub login_limits: config::LoginLimits,
+    pub site_url: Option<String>,
+    pub preview_links: preview::PreviewLinks,
+    pub trash_retention_days: Option<i64>,
+    /// When the server started, to the second. Rendered pages can change with
+    /// each deploy, so their validators include it.
+    pub started_at: chrono::DateTime<chrono::Utc>,
 }
 
 #[actix_web::main]
@@ -21,16 +48,47 @@ async fn main() -> std::io::Result<()> {
     dotenv::dotenv().ok();
     env_logger::init_from_env(env_logger::Env::default().default_filter_or("info"));
 
-    let conn = db::init_db().expect("Failed to initialize database");
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(result) = cli::run(&args) {
+        return result;
+    }
+
+    let pool = db::create_pool().expect("Failed to create database pool");
+    {
+        let mut conn = pool.get().expect("Failed to get database connection");
+        db::init_db(&mut conn).expect("Failed to initialize database");
+    }
+    
+    let blob_backend = config::BlobBackend::from_env()?;
+    let blob_store = blobs::open(&blob_backend, &pool)?;
+    log::info!("Storing uploads in the {} blob store", blob_backend.name());
+    
+    let mut tera = Tera::new("templates/**/*").expect("Failed to initialize Tera");
+    tera.register_filter("srcset", images::srcset_filter);
     
-    let tera = Tera::new("templates/**/*").expect("Failed to initialize Tera");
+    let session_config = config::SessionConfig::from_env();
+    let secret_key = config::load_session_key().expect("Failed to load session key");
+    
+    // Scheduled posts go live in the background
+    let mut publish_hooks = publishing::PublishHooks::default();
+    publish_hooks.register(|blog| log::info!("Published scheduled post \"{}\" (/blogs/{})", blog.title, blog.slug));
+    actix_web::rt::spawn(publishing::run_scheduler(pool.clone(), publish_hooks));
+    let trash_retention_days = config::trash_retention_days();
+    if let Some(days) = trash_retention_days {
+        actix_web::rt::spawn(trash::run_purger(pool.clone(), days));
+    }
     
     let app_state = web::Data::new(AppState {
-        db: Mutex::new(conn),
+        db: pool,
+        blobs: blob_store,
         tera,
+        session_config: session_config.clone(),
+        login_limits: config::LoginLimits::from_env(),
+        site_url: config::site_url(),
+        preview_links: preview::PreviewLinks::new(&secret_key, config::preview_link_ttl_secs()),
+        trash_retention_days,
+        started_at: chrono::Utc::now().trunc_subsecs(0),
     });
-
-    let secret_key = Key::generate();
     
     // Get host and port from environment, defaulting to 0.0.0.0:8080 for Docker
     let host = std::env::var("HOST").unwrap_or_else(|_| "0.0.0.0".to_string());
@@ -42,10 +100,19 @@ async fn main() -> std::io::Result<()> {
     HttpServer::new(move || {
         App::new()
             .app_data(app_state.clone())
+            .wrap(middleware::from_fn(csrf::verify_csrf))
+            .wrap(error::error_pages())
             .wrap(Logger::default())
+            .wrap(middleware::from_fn(auth::refresh_admin_session))
+            .wrap(middleware::from_fn(auth::enforce_session_timeouts))
             .wrap(
                 SessionMiddleware::builder(CookieSessionStore::default(), secret_key.clone())
-                    .cookie_secure(false)
+                    .cookie_secure(session_config.cookie_secure)
+                    .cookie_http_only(session_config.cookie_http_only)
+                    .cookie_same_site(session_config.cookie_same_site)
+                    .session_lifecycle(
+                        PersistentSession::default().session_ttl(Duration::seconds(session_config.ttl_secs))
+                    )
                     .build()
             )
             // Static files
@@ -54,19 +121,35 @@ async fn main() -> std::io::Result<()> {
             .route("/", web::get().to(routes::index))
             .route("/about", web::get().to(routes::about))
             .route("/projects", web::get().to(routes::projects))
+            .route("/projects/tech/{tech}", web::get().to(routes::projects_by_tech))
             .route("/projects/{slug}", web::get().to(routes::project_detail))
             .route("/blogs", web::get().to(routes::blogs))
+            .route("/blogs/tag/{tag}", web::get().to(routes::blogs_by_tag))
+            .route("/blogs/preview/{id}", web::get().to(routes::blog_preview))
             .route("/blogs/{slug}", web::get().to(routes::blog_detail))
+            .route("/search", web::get().to(routes::search))
             .route("/contact", web::get().to(routes::contact))
             .route("/contact", web::post().to(routes::submit_contact))
+            // Feeds
+            .route("/feed.xml", web::get().to(feeds::rss))
+            .route("/atom.xml", web::get().to(feeds::atom))
+            .route("/sitemap.xml", web::get().to(seo::sitemap))
+            .route("/robots.txt", web::get().to(seo::robots))
             // Auth routes
             .route("/admin/login", web::get().to(auth::login_page))
             .route("/admin/login", web::post().to(auth::login))
+            .route("/admin/login/2fa", web::get().to(auth::two_factor_page))
+            .route("/admin/login/2fa", web::post().to(auth::two_factor_login))
             .route("/admin/logout", web::get().to(auth::logout))
+            .route("/admin/setup", web::get().to(auth::setup_page))
+            .route("/admin/setup", web::post().to(auth::setup))
             // Admin routes
             .route("/admin", web::get().to(admin::dashboard))
             .route("/admin/profile", web::get().to(admin::profile_page))
             .route("/admin/profile", web::post().to(admin::update_profile_with_image))
+            .route("/admin/profile/2fa/enable", web::post().to(admin::enable_two_factor))
+            .route("/admin/profile/2fa/disable", web::post().to(admin::disable_two_factor))
+            .route("/admin/profile/2fa/recovery-codes", web::post().to(admin::regenerate_recovery_codes))
             .route("/admin/skills", web::get().to(admin::skills_page))
             .route("/admin/skills/add", web::post().to(admin::add_skill))
             .route("/admin/skills/delete/{id}", web::post().to(admin::delete_skill))
@@ -89,14 +172,29 @@ async fn main() -> std::io::Result<()> {
             .route("/admin/services/edit/{id}", web::post().to(admin::update_service_with_image))
             .route("/admin/services/delete/{id}", web::post().to(admin::delete_service))
             .route("/admin/messages", web::get().to(admin::messages_page))
+            .route("/admin/search", web::get().to(admin::search_page))
             .route("/admin/messages/delete/{id}", web::post().to(admin::delete_message))
             // Email settings routes
+            .route("/admin/account", web::get().to(admin::account_page))
+            .route("/admin/account", web::post().to(admin::update_account))
+            .route("/admin/users", web::get().to(admin::users_page))
+            .route("/admin/users/add", web::post().to(admin::add_user))
+            .route("/admin/users/{id}/role", web::post().to(admin::update_user_role))
+            .route("/admin/users/delete/{id}", web::post().to(admin::delete_user))
             .route("/admin/email-settings", web::get().to(admin::email_settings_page))
             .route("/admin/email-settings", web::post().to(admin::update_email_settings))
             .route("/admin/email-settings/test", web::post().to(admin::test_email))
             // Site content routes
             .route("/admin/site-content", web::get().to(admin::site_content_page))
             .route("/admin/site-content", web::post().to(admin::update_site_content))
+            .route("/admin/revisions/diff", web::get().to(admin::revision_diff))
+            .route("/admin/revisions/{id}/restore", web::post().to(admin::restore_revision))
+            .route("/admin/trash", web::get().to(admin::trash_page))
+            .route("/admin/trash/{kind}/{id}/restore", web::post().to(admin::restore_trash_item))
+            .route("/admin/trash/{kind}/{id}/purge", web::post().to(admin::purge_trash_item))
+            .route("/admin/media", web::get().to(admin::media_page))
+            .route("/admin/media/remove-unused", web::post().to(admin::remove_unused_media))
+            .route("/admin/media/{id}/delete", web::post().to(admin::delete_media))
             // Experience routes
             .route("/admin/experience", web::get().to(admin::experience_page))
             .route("/admin/experience/add", web::get().to(admin::add_experience_page))
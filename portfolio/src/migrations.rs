@@ -0,0 +1,633 @@
+use rusqlite::{Connection, OptionalExtension, Result};
+
+/// A numbered schema change, tracked in the `schema_migrations` table.
+pub struct Migration {
+    pub version: i64,
+    pub name: &'static str,
+    pub sql: &'static str,
+}
+
+// Append new migrations to the end of this list. Never edit or reorder a
+// migration that has already shipped - add a new one instead.
+pub const MIGRATIONS: &[Migration] = &[
+    Migration {
+        version: 1,
+        name: "initial_schema",
+        sql: "
+        -- Profile table
+        CREATE TABLE IF NOT EXISTS profile (
+            id INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            title TEXT NOT NULL,
+            bio TEXT NOT NULL,
+            email TEXT NOT NULL,
+            phone TEXT,
+            location TEXT,
+            github_url TEXT,
+            linkedin_url TEXT,
+            twitter_url TEXT,
+            resume_url TEXT,
+            avatar_url TEXT
+        );
+
+        -- Skills table
+        CREATE TABLE IF NOT EXISTS skills (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            category TEXT NOT NULL,
+            proficiency INTEGER DEFAULT 80,
+            icon TEXT,
+            icon_url TEXT
+        );
+
+        -- Projects table
+        CREATE TABLE IF NOT EXISTS projects (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            title TEXT NOT NULL,
+            slug TEXT NOT NULL UNIQUE,
+            description TEXT NOT NULL,
+            content TEXT,
+            image_url TEXT,
+            demo_url TEXT,
+            github_url TEXT,
+            technologies TEXT,
+            featured INTEGER DEFAULT 0,
+            created_at TEXT NOT NULL
+        );
+
+        -- Blogs table
+        CREATE TABLE IF NOT EXISTS blogs (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            title TEXT NOT NULL,
+            slug TEXT NOT NULL UNIQUE,
+            excerpt TEXT NOT NULL,
+            content TEXT NOT NULL,
+            image_url TEXT,
+            tags TEXT,
+            published INTEGER DEFAULT 0,
+            created_at TEXT NOT NULL,
+            updated_at TEXT NOT NULL
+        );
+
+        -- Experience table
+        CREATE TABLE IF NOT EXISTS experience (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            company TEXT NOT NULL,
+            position TEXT NOT NULL,
+            description TEXT,
+            start_date TEXT NOT NULL,
+            end_date TEXT,
+            current INTEGER DEFAULT 0
+        );
+
+        -- Education table
+        CREATE TABLE IF NOT EXISTS education (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            institution TEXT NOT NULL,
+            degree TEXT NOT NULL,
+            field TEXT NOT NULL,
+            start_date TEXT NOT NULL,
+            end_date TEXT,
+            description TEXT
+        );
+
+        -- Messages table (contact form)
+        CREATE TABLE IF NOT EXISTS messages (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            email TEXT NOT NULL,
+            subject TEXT NOT NULL,
+            message TEXT NOT NULL,
+            read INTEGER DEFAULT 0,
+            created_at TEXT NOT NULL
+        );
+
+        -- Admin table
+        CREATE TABLE IF NOT EXISTS admin (
+            id INTEGER PRIMARY KEY,
+            username TEXT NOT NULL UNIQUE,
+            password_hash TEXT NOT NULL
+        );
+
+        -- Images table for storing uploaded images
+        CREATE TABLE IF NOT EXISTS images (
+            id TEXT PRIMARY KEY,
+            filename TEXT NOT NULL,
+            content_type TEXT NOT NULL,
+            data BLOB NOT NULL,
+            created_at TEXT NOT NULL
+        );
+
+        -- Services table
+        CREATE TABLE IF NOT EXISTS services (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL,
+            description TEXT,
+            image_url TEXT,
+            icon TEXT,
+            order_index INTEGER DEFAULT 0
+        );
+
+        -- Email Settings table
+        CREATE TABLE IF NOT EXISTS email_settings (
+            id INTEGER PRIMARY KEY CHECK (id = 1),
+            smtp_server TEXT NOT NULL DEFAULT '',
+            smtp_port INTEGER NOT NULL DEFAULT 587,
+            smtp_username TEXT NOT NULL DEFAULT '',
+            smtp_password TEXT NOT NULL DEFAULT '',
+            notification_email TEXT NOT NULL DEFAULT '',
+            enabled INTEGER NOT NULL DEFAULT 0
+        );
+
+        -- Site Content table for dynamic text
+        CREATE TABLE IF NOT EXISTS site_content (
+            key TEXT PRIMARY KEY,
+            value TEXT NOT NULL DEFAULT '',
+            section TEXT NOT NULL DEFAULT 'general',
+            description TEXT DEFAULT ''
+        );
+        ",
+    },
+    Migration {
+        version: 2,
+        name: "login_throttling",
+        sql: "
+        -- Consecutive login failures per client IP and per username
+        CREATE TABLE login_throttle (
+            scope TEXT NOT NULL,
+            key TEXT NOT NULL,
+            failures INTEGER NOT NULL DEFAULT 0,
+            last_failure_at INTEGER NOT NULL,
+            locked_until INTEGER NOT NULL DEFAULT 0,
+            PRIMARY KEY (scope, key)
+        );
+
+        -- Log of login attempts shown on the dashboard
+        CREATE TABLE login_attempts (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            ip TEXT NOT NULL,
+            username TEXT NOT NULL,
+            outcome TEXT NOT NULL,
+            created_at TEXT NOT NULL
+        );
+        CREATE INDEX idx_login_attempts_created_at ON login_attempts (created_at);
+        ",
+    },
+    Migration {
+        version: 3,
+        name: "admin_two_factor",
+        sql: "
+        -- TOTP secret (NULL while 2FA is off) and the last time step accepted, to refuse replays
+        ALTER TABLE admin ADD COLUMN totp_secret TEXT;
+        ALTER TABLE admin ADD COLUMN totp_last_step INTEGER NOT NULL DEFAULT 0;
+
+        -- One-time recovery codes, stored as SHA-256 hashes
+        CREATE TABLE admin_recovery_codes (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            admin_id INTEGER NOT NULL REFERENCES admin(id) ON DELETE CASCADE,
+            code_hash TEXT NOT NULL,
+            used_at TEXT
+        );
+        CREATE INDEX idx_admin_recovery_codes_admin ON admin_recovery_codes (admin_id);
+        ",
+    },
+    Migration {
+        version: 4,
+        name: "admin_roles_and_blog_authors",
+        sql: "
+        -- Existing accounts keep full access
+        ALTER TABLE admin ADD COLUMN role TEXT NOT NULL DEFAULT 'owner'
+            CHECK (role IN ('owner', 'editor', 'author'));
+
+        ALTER TABLE blogs ADD COLUMN author_id INTEGER REFERENCES admin(id) ON DELETE SET NULL;
+        UPDATE blogs SET author_id = (SELECT MIN(id) FROM admin);
+        ",
+    },
+    Migration {
+        version: 5,
+        name: "admin_session_version",
+        sql: "
+        -- Bumped whenever credentials change; sessions carrying an older value are logged out
+        ALTER TABLE admin ADD COLUMN session_version INTEGER NOT NULL DEFAULT 0;
+        ",
+    },
+    Migration {
+        version: 6,
+        name: "seo_overrides",
+        sql: "
+        -- Optional search/social overrides; empty means use the title and excerpt/description
+        ALTER TABLE projects ADD COLUMN seo_title TEXT NOT NULL DEFAULT '';
+        ALTER TABLE projects ADD COLUMN seo_description TEXT NOT NULL DEFAULT '';
+        ALTER TABLE blogs ADD COLUMN seo_title TEXT NOT NULL DEFAULT '';
+        ALTER TABLE blogs ADD COLUMN seo_description TEXT NOT NULL DEFAULT '';
+        ",
+    },
+    Migration {
+        version: 7,
+        name: "full_text_search",
+        sql: "
+        -- External-content FTS5 indexes; the rowid is the id of the source row
+        CREATE VIRTUAL TABLE blogs_fts USING fts5(
+            title, excerpt, content, tags,
+            content = 'blogs', content_rowid = 'id', tokenize = 'porter unicode61'
+        );
+        CREATE VIRTUAL TABLE projects_fts USING fts5(
+            title, description, content, technologies,
+            content = 'projects', content_rowid = 'id', tokenize = 'porter unicode61'
+        );
+        CREATE VIRTUAL TABLE messages_fts USING fts5(
+            name, email, subject, message,
+            content = 'messages', content_rowid = 'id', tokenize = 'porter unicode61'
+        );
+
+        CREATE TRIGGER blogs_fts_insert AFTER INSERT ON blogs BEGIN
+            INSERT INTO blogs_fts (rowid, title, excerpt, content, tags)
+            VALUES (new.id, new.title, new.excerpt, new.content, new.tags);
+        END;
+        CREATE TRIGGER blogs_fts_delete AFTER DELETE ON blogs BEGIN
+            INSERT INTO blogs_fts (blogs_fts, rowid, title, excerpt, content, tags)
+            VALUES ('delete', old.id, old.title, old.excerpt, old.content, old.tags);
+        END;
+        CREATE TRIGGER blogs_fts_update AFTER UPDATE OF title, excerpt, content, tags ON blogs BEGIN
+            INSERT INTO blogs_fts (blogs_fts, rowid, title, excerpt, content, tags)
+            VALUES ('delete', old.id, old.title, old.excerpt, old.content, old.tags);
+            INSERT INTO blogs_fts (rowid, title, excerpt, content, tags)
+            VALUES (new.id, new.title, new.excerpt, new.content, new.tags);
+        END;
+
+        CREATE TRIGGER projects_fts_insert AFTER INSERT ON projects BEGIN
+            INSERT INTO projects_fts (rowid, title, description, content, technologies)
+            VALUES (new.id, new.title, new.description, new.content, new.technologies);
+        END;
+        CREATE TRIGGER projects_fts_delete AFTER DELETE ON projects BEGIN
+            INSERT INTO projects_fts (projects_fts, rowid, title, description, content, technologies)
+            VALUES ('delete', old.id, old.title, old.description, old.content, old.technologies);
+        END;
+        CREATE TRIGGER projects_fts_update AFTER UPDATE OF title, description, content, technologies ON projects BEGIN
+            INSERT INTO projects_fts (projects_fts, rowid, title, description, content, technologies)
+            VALUES ('delete', old.id, old.title, old.description, old.content, old.technologies);
+            INSERT INTO projects_fts (rowid, title, description, content, technologies)
+            VALUES (new.id, new.title, new.description, new.content, new.technologies);
+        END;
+
+        CREATE TRIGGER messages_fts_insert AFTER INSERT ON messages BEGIN
+            INSERT INTO messages_fts (rowid, name, email, subject, message)
+            VALUES (new.id, new.name, new.email, new.subject, new.message);
+        END;
+        CREATE TRIGGER messages_fts_delete AFTER DELETE ON messages BEGIN
+            INSERT INTO messages_fts (messages_fts, rowid, name, email, subject, message)
+            VALUES ('delete', old.id, old.name, old.email, old.subject, old.message);
+        END;
+        CREATE TRIGGER messages_fts_update AFTER UPDATE OF name, email, subject, message ON messages BEGIN
+            INSERT INTO messages_fts (messages_fts, rowid, name, email, subject, message)
+            VALUES ('delete', old.id, old.name, old.email, old.subject, old.message);
+            INSERT INTO messages_fts (rowid, name, email, subject, message)
+            VALUES (new.id, new.name, new.email, new.subject, new.message);
+        END;
+
+        -- Index the rows that already exist
+        INSERT INTO blogs_fts (blogs_fts) VALUES ('rebuild');
+        INSERT INTO projects_fts (projects_fts) VALUES ('rebuild');
+        INSERT INTO messages_fts (messages_fts) VALUES ('rebuild');
+        ",
+    },
+    Migration {
+        version: 8,
+        name: "normalized_tags",
+        sql: "
+        -- Blog tags and project technologies share one vocabulary. Names are
+        -- unique ignoring case; the first spelling seen is kept.
+        CREATE TABLE tags (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            name TEXT NOT NULL UNIQUE COLLATE NOCASE
+        );
+        CREATE TABLE blog_tags (
+            blog_id INTEGER NOT NULL REFERENCES blogs(id) ON DELETE CASCADE,
+            tag_id INTEGER NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+            PRIMARY KEY (blog_id, tag_id)
+        );
+        CREATE TABLE project_tags (
+            project_id INTEGER NOT NULL REFERENCES projects(id) ON DELETE CASCADE,
+            tag_id INTEGER NOT NULL REFERENCES tags(id) ON DELETE CASCADE,
+            PRIMARY KEY (project_id, tag_id)
+        );
+        CREATE INDEX idx_blog_tags_tag ON blog_tags(tag_id);
+        CREATE INDEX idx_project_tags_tag ON project_tags(tag_id);
+
+        -- Split the existing comma-separated strings
+        WITH RECURSIVE split(blog_id, tag, rest) AS (
+            SELECT id, '', tags || ',' FROM blogs
+            UNION ALL
+            SELECT blog_id, trim(substr(rest, 1, instr(rest, ',') - 1)), substr(rest, instr(rest, ',') + 1)
+            FROM split WHERE rest <> ''
+        )
+        INSERT OR IGNORE INTO tags (name) SELECT tag FROM split WHERE tag <> '' ORDER BY blog_id;
+
+        WITH RECURSIVE split(project_id, tag, rest) AS (
+            SELECT id, '', technologies || ',' FROM projects
+            UNION ALL
+            SELECT project_id, trim(substr(rest, 1, instr(rest, ',') - 1)), substr(rest, instr(rest, ',') + 1)
+            FROM split WHERE rest <> ''
+        )
+        INSERT OR IGNORE INTO tags (name) SELECT tag FROM split WHERE tag <> '' ORDER BY project_id;
+
+        WITH RECURSIVE split(blog_id, tag, rest) AS (
+            SELECT id, '', tags || ',' FROM blogs
+            UNION ALL
+            SELECT blog_id, trim(substr(rest, 1, instr(rest, ',') - 1)), substr(rest, instr(rest, ',') + 1)
+            FROM split WHERE rest <> ''
+        )
+        INSERT OR IGNORE INTO blog_tags (blog_id, tag_id)
+        SELECT split.blog_id, tags.id FROM split JOIN tags ON tags.name = split.tag;
+
+        WITH RECURSIVE split(project_id, tag, rest) AS (
+            SELECT id, '', technologies || ',' FROM projects
+            UNION ALL
+            SELECT project_id, trim(substr(rest, 1, instr(rest, ',') - 1)), substr(rest, instr(rest, ',') + 1)
+            FROM split WHERE rest <> ''
+        )
+        INSERT OR IGNORE INTO project_tags (project_id, tag_id)
+        SELECT split.project_id, tags.id FROM split JOIN tags ON tags.name = split.tag;
+
+        -- The strings stay as the display copy; rewrite them without duplicates
+        UPDATE blogs SET tags = COALESCE((
+            SELECT group_concat(name, ', ') FROM (
+                SELECT t.name FROM blog_tags bt JOIN tags t ON t.id = bt.tag_id
+                WHERE bt.blog_id = blogs.id ORDER BY bt.rowid
+            )
+        ), '');
+        UPDATE projects SET technologies = COALESCE((
+            SELECT group_concat(name, ', ') FROM (
+                SELECT t.name FROM project_tags pt JOIN tags t ON t.id = pt.tag_id
+                WHERE pt.project_id = projects.id ORDER BY pt.rowid
+            )
+        ), '');
+        ",
+    },
+    Migration {
+        version: 9,
+        name: "scheduled_publishing",
+        sql: "
+        -- UTC time a published post goes live; NULL once it is live
+        ALTER TABLE blogs ADD COLUMN publish_at TEXT;
+        CREATE INDEX idx_blogs_publish_at ON blogs(publish_at) WHERE publish_at IS NOT NULL;
+        ",
+    },
+    Migration {
+        version: 10,
+        name: "revisions",
+        sql: "
+        -- Full snapshot of a blog post, project or the site content after each save.
+        -- Site content is a single document with entity_id 0.
+        CREATE TABLE revisions (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            entity TEXT NOT NULL,
+            entity_id INTEGER NOT NULL,
+            snapshot TEXT NOT NULL,
+            note TEXT NOT NULL DEFAULT '',
+            author_id INTEGER REFERENCES admin(id) ON DELETE SET NULL,
+            created_at TEXT NOT NULL
+        );
+        CREATE INDEX idx_revisions_entity ON revisions(entity, entity_id, id);
+        ",
+    },
+    Migration {
+        version: 11,
+        name: "soft_delete",
+        sql: "
+        -- Deleting moves a row to the trash by setting deleted_at; it is purged for good
+        -- from the trash page or once it has been there longer than the retention period.
+        ALTER TABLE projects ADD COLUMN deleted_at TEXT;
+        ALTER TABLE blogs ADD COLUMN deleted_at TEXT;
+        ALTER TABLE services ADD COLUMN deleted_at TEXT;
+        ALTER TABLE experience ADD COLUMN deleted_at TEXT;
+        ALTER TABLE education ADD COLUMN deleted_at TEXT;
+        ALTER TABLE skills ADD COLUMN deleted_at TEXT;
+        ALTER TABLE messages ADD COLUMN deleted_at TEXT;
+        ",
+    },
+    Migration {
+        version: 12,
+        name: "image_variants",
+        sql: "
+        -- Pixel size of processed uploads; NULL for files that aren't processed images
+        -- (documents, GIFs) and for images uploaded before processing existed.
+        ALTER TABLE images ADD COLUMN width INTEGER;
+        ALTER TABLE images ADD COLUMN height INTEGER;
+
+        -- Resized and WebP copies of an uploaded image, served by /images/{id}?w=
+        CREATE TABLE image_variants (
+            image_id TEXT NOT NULL REFERENCES images(id) ON DELETE CASCADE,
+            width INTEGER NOT NULL,
+            content_type TEXT NOT NULL,
+            data BLOB NOT NULL,
+            PRIMARY KEY (image_id, width, content_type)
+        );
+        ",
+    },
+    Migration {
+        version: 13,
+        name: "blob_storage",
+        sql: "
+        -- Upload bytes move out of the rows describing them and into a blob store
+        -- (this table, a directory or an S3 bucket), looked up by blob_key.
+        -- New blobs are keyed by the SHA-256 of their content; existing ones keep
+        -- a per-row key until `portfolio move-blobs` re-keys them.
+        CREATE TABLE blobs (
+            key TEXT PRIMARY KEY,
+            data BLOB NOT NULL
+        );
+
+        ALTER TABLE images ADD COLUMN blob_key TEXT;
+        ALTER TABLE images ADD COLUMN size INTEGER;
+        ALTER TABLE image_variants ADD COLUMN blob_key TEXT;
+        ALTER TABLE image_variants ADD COLUMN size INTEGER;
+
+        UPDATE images SET blob_key = 'legacy-' || id, size = length(data);
+        INSERT INTO blobs (key, data) SELECT blob_key, data FROM images;
+        UPDATE image_variants
+        SET blob_key = 'legacy-' || image_id || '-' || width || '-' || replace(content_type, '/', '-'),
+            size = length(data);
+        INSERT INTO blobs (key, data) SELECT blob_key, data FROM image_variants;
+
+        ALTER TABLE images DROP COLUMN data;
+        ALTER TABLE image_variants DROP COLUMN data;
+        ",
+    },
+    Migration {
+        version: 14,
+        name: "content_updated_at",
+        sql: "
+        -- Public pages are revalidated against updated_at, so every change to a
+        -- post or project (tag renames, restores, scheduled publishing) bumps it.
+        -- An UPDATE that sets updated_at itself is left alone.
+        ALTER TABLE projects ADD COLUMN updated_at TEXT NOT NULL DEFAULT '';
+        UPDATE projects SET updated_at = created_at;
+
+        CREATE TRIGGER projects_touch AFTER UPDATE ON projects WHEN new.updated_at IS old.updated_at BEGIN
+            UPDATE projects SET updated_at = datetime('now') WHERE id = new.id;
+        END;
+        CREATE TRIGGER blogs_touch AFTER UPDATE ON blogs WHEN new.updated_at IS old.updated_at BEGIN
+            UPDATE blogs SET updated_at = datetime('now') WHERE id = new.id;
+        END;
+        ",
+    },
+];
+
+#[derive(Debug)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub name: &'static str,
+    pub applied_at: Option<String>,
+}
+
+fn ensure_migrations_table(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS schema_migrations (
+            version INTEGER PRIMARY KEY,
+            name TEXT NOT NULL,
+            applied_at TEXT NOT NULL
+        );",
+    )
+}
+
+fn applied_at(conn: &Connection, version: i64) -> Result<Option<String>> {
+    conn.query_row(
+        "SELECT applied_at FROM schema_migrations WHERE version = ?1",
+        [version],
+        |row| row.get(0),
+    )
+    .optional()
+}
+
+/// Lists every known migration together with the time it was applied, if it has been.
+pub fn status(conn: &Connection) -> Result<Vec<MigrationStatus>> {
+    ensure_migrations_table(conn)?;
+    MIGRATIONS
+        .iter()
+        .map(|m| {
+            Ok(MigrationStatus {
+                version: m.version,
+                name: m.name,
+                applied_at: applied_at(conn, m.version)?,
+            })
+        })
+        .collect()
+}
+
+fn apply(conn: &Connection, migration: &Migration) -> Result<()> {
+    conn.execute_batch(migration.sql)?;
+    let now = chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string();
+    conn.execute(
+        "INSERT INTO schema_migrations (version, name, applied_at) VALUES (?1, ?2, ?3)",
+        rusqlite::params![migration.version, migration.name, now],
+    )?;
+    Ok(())
+}
+
+/// Applies all pending migrations in order, each in its own transaction.
+///
+/// With `dry_run` set, the pending migrations are executed inside a single
+/// transaction that is rolled back at the end, so SQL errors still surface but
+/// nothing is written. Returns the versions that were (or would be) applied.
+pub fn run(conn: &mut Connection, dry_run: bool) -> Result<Vec<i64>> {
+    ensure_migrations_table(conn)?;
+
+    let mut pending = Vec::new();
+    for migration in MIGRATIONS {
+        if applied_at(conn, migration.version)?.is_none() {
+            pending.push(migration);
+        }
+    }
+
+    if dry_run {
+        let tx = conn.transaction()?;
+        for migration in &pending {
+            apply(&tx, migration)?;
+            log::info!("[dry run] Migration {} ({}) would be applied", migration.version, migration.name);
+        }
+        tx.rollback()?;
+    } else {
+        for migration in &pending {
+            let tx = conn.transaction()?;
+            apply(&tx, migration)?;
+            tx.commit()?;
+            log::info!("Applied migration {} ({})", migration.version, migration.name);
+        }
+    }
+
+    Ok(pending.iter().map(|m| m.version).collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A database as it was when `version` was the newest migration.
+    fn database_at(version: i64) -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        ensure_migrations_table(&conn).unwrap();
+        for migration in MIGRATIONS.iter().take_while(|m| m.version <= version) {
+            apply(&conn, migration).unwrap();
+        }
+        conn
+    }
+
+    fn strings(conn: &Connection, sql: &str) -> Vec<String> {
+        let mut stmt = conn.prepare(sql).unwrap();
+        let rows = stmt.query_map([], |row| row.get(0)).unwrap();
+        rows.collect::<Result<_>>().unwrap()
+    }
+
+    #[test]
+    fn migration_8_moves_tag_strings_into_tag_tables() {
+        let mut conn = database_at(7);
+        conn.execute_batch(
+            "INSERT INTO blogs (id, title, slug, excerpt, content, tags, created_at, updated_at) VALUES
+                (1, 'One', 'one', '', '', ' Rust, web,rust ,, ', '2024-01-01', '2024-01-01'),
+                (2, 'Two', 'two', '', '', 'WEB, SQLite', '2024-01-02', '2024-01-02'),
+                (3, 'Three', 'three', '', '', NULL, '2024-01-03', '2024-01-03');
+             INSERT INTO projects (id, title, slug, description, technologies, created_at) VALUES
+                (1, 'Site', 'site', '', 'rust, Actix', '2024-01-01');",
+        )
+        .unwrap();
+
+        run(&mut conn, false).unwrap();
+
+        assert_eq!(strings(&conn, "SELECT name FROM tags ORDER BY id"), ["Rust", "web", "SQLite", "Actix"]);
+        assert_eq!(strings(&conn, "SELECT tags FROM blogs ORDER BY id"), ["Rust, web", "web, SQLite", ""]);
+        assert_eq!(strings(&conn, "SELECT technologies FROM projects"), ["Rust, Actix"]);
+        let links: i64 = conn
+            .query_row("SELECT (SELECT count(*) FROM blog_tags) + (SELECT count(*) FROM project_tags)", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(links, 6);
+    }
+
+    #[test]
+    fn migration_13_moves_upload_bytes_into_blobs() {
+        let mut conn = database_at(12);
+        conn.execute_batch(
+            "INSERT INTO images (id, filename, content_type, data, created_at, width) VALUES
+                ('a', 'a.jpg', 'image/jpeg', x'FFD8FF0102', '2024-01-01', 800),
+                ('b', 'b.pdf', 'application/pdf', x'255044462D', '2024-01-01', NULL);
+             INSERT INTO image_variants (image_id, width, content_type, data) VALUES
+                ('a', 320, 'image/webp', x'52494646');",
+        )
+        .unwrap();
+
+        run(&mut conn, false).unwrap();
+
+        let images = strings(&conn, "SELECT id || ' ' || blob_key || ' ' || size FROM images ORDER BY id");
+        assert_eq!(images, ["a legacy-a 5", "b legacy-b 5"]);
+        let variants = strings(&conn, "SELECT blob_key || ' ' || size FROM image_variants");
+        assert_eq!(variants, ["legacy-a-320-image-webp 4"]);
+        let blobs = strings(&conn, "SELECT key || ' ' || hex(data) FROM blobs ORDER BY key");
+        assert_eq!(blobs, ["legacy-a FFD8FF0102", "legacy-a-320-image-webp 52494646", "legacy-b 255044462D"]);
+        let data_columns = strings(
+            &conn,
+            "SELECT 'images' FROM pragma_table_info('images') WHERE name = 'data'
+             UNION ALL SELECT 'image_variants' FROM pragma_table_info('image_variants') WHERE name = 'data'",
+        );
+        assert!(data_columns.is_empty(), "{:?}", data_columns);
+    }
+}
@@ -1,6 +1,6 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Serialize, Deserialize, Clone)]
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
 pub struct Profile {
     pub id: i32,
     pub name: String,
@@ -39,6 +39,9 @@ pub struct Project {
     pub technologies: String,
     pub featured: bool,
     pub created_at: String,
+    pub seo_title: String,
+    pub seo_description: String,
+    pub updated_at: String,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -53,6 +56,12 @@ pub struct Blog {
     pub published: bool,
     pub created_at: String,
     pub updated_at: String,
+    pub author_id: Option<i32>,
+    pub author: Option<String>,
+    pub seo_title: String,
+    pub seo_description: String,
+    /// Set while a published post is scheduled for later (UTC).
+    pub publish_at: Option<String>,
 }
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
@@ -98,11 +107,243 @@ pub struct Service {
     pub order_index: i32,
 }
 
+/// Admin roles, ordered from least to most privileged.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Can create and edit their own blog drafts.
+    Author,
+    /// Can manage all site content.
+    Editor,
+    /// Can additionally manage admin users and email settings.
+    Owner,
+}
+
+impl Role {
+    pub const ALL: [Role; 3] = [Role::Owner, Role::Editor, Role::Author];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Role::Author => "author",
+            Role::Editor => "editor",
+            Role::Owner => "owner",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<Role> {
+        Role::ALL.into_iter().find(|role| role.as_str() == s)
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct Admin {
     pub id: i32,
     pub username: String,
     pub password_hash: String,
+    pub totp_secret: Option<String>,
+    pub totp_last_step: i64,
+    pub role: Role,
+    pub session_version: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct NewAdminForm {
+    pub username: String,
+    pub password: String,
+    pub role: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AccountForm {
+    pub username: String,
+    pub current_password: String,
+    /// Left blank to keep the current password
+    pub new_password: String,
+    pub confirm_password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RoleForm {
+    pub role: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TwoFactorForm {
+    pub code: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct LoginAttempt {
+    pub id: i64,
+    pub ip: String,
+    pub username: String,
+    /// `success`, `failure`, `2fa_failure` or `locked` (rejected without checking credentials)
+    pub outcome: String,
+    pub created_at: String,
+}
+
+/// One hit from the full-text search.
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub struct SearchResult {
+    /// `blog`, `project` or `message`
+    pub kind: String,
+    pub id: i32,
+    pub title: String,
+    /// Empty for messages, which have no public page
+    pub slug: String,
+    /// Matching excerpt; terms are wrapped in `db::SNIPPET_START`/`db::SNIPPET_END`
+    pub snippet: String,
+    pub published: bool,
+    pub created_at: String,
+}
+
+/// What a revision is a snapshot of.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum RevisionKind {
+    Blog,
+    Project,
+    SiteContent,
+}
+
+impl RevisionKind {
+    pub const ALL: [RevisionKind; 3] = [RevisionKind::Blog, RevisionKind::Project, RevisionKind::SiteContent];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RevisionKind::Blog => "blog",
+            RevisionKind::Project => "project",
+            RevisionKind::SiteContent => "site_content",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<RevisionKind> {
+        RevisionKind::ALL.into_iter().find(|kind| kind.as_str() == s)
+    }
+}
+
+/// A saved version of a blog post, project or the site content.
+#[derive(Debug, Serialize, Clone)]
+pub struct Revision {
+    pub id: i64,
+    pub kind: RevisionKind,
+    pub entity_id: i32,
+    /// JSON object of the saved fields: a `BlogForm`, a `ProjectForm`, or a key/value map.
+    #[serde(skip)]
+    pub snapshot: String,
+    pub note: String,
+    pub author: Option<String>,
+    pub created_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RevisionDiffQuery {
+    pub from: i64,
+    pub to: i64,
+}
+
+/// The kinds of admin content that go to the trash when deleted.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum TrashKind {
+    Project,
+    Blog,
+    Service,
+    Experience,
+    Education,
+    Skill,
+    Message,
+}
+
+impl TrashKind {
+    pub const ALL: [TrashKind; 7] = [
+        TrashKind::Project,
+        TrashKind::Blog,
+        TrashKind::Service,
+        TrashKind::Experience,
+        TrashKind::Education,
+        TrashKind::Skill,
+        TrashKind::Message,
+    ];
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TrashKind::Project => "project",
+            TrashKind::Blog => "blog",
+            TrashKind::Service => "service",
+            TrashKind::Experience => "experience",
+            TrashKind::Education => "education",
+            TrashKind::Skill => "skill",
+            TrashKind::Message => "message",
+        }
+    }
+
+    pub fn parse(s: &str) -> Option<TrashKind> {
+        TrashKind::ALL.into_iter().find(|kind| kind.as_str() == s)
+    }
+}
+
+/// A deleted item waiting in the trash.
+#[derive(Debug, Serialize, Clone)]
+pub struct TrashItem {
+    pub kind: TrashKind,
+    pub id: i32,
+    pub title: String,
+    pub deleted_at: String,
+}
+
+/// Somewhere an upload is used.
+#[derive(Debug, Serialize, Clone)]
+pub struct MediaUsage {
+    /// What uses it, e.g. "Blog" or "Skill icon".
+    pub kind: String,
+    pub title: String,
+    pub edit_url: String,
+    pub in_trash: bool,
+}
+
+/// An upload as listed in the media library.
+#[derive(Debug, Serialize, Clone)]
+pub struct MediaItem {
+    pub id: String,
+    pub filename: String,
+    pub content_type: String,
+    /// `/images/{id}` for images, `/files/{id}` for documents.
+    pub url: String,
+    pub is_image: bool,
+    /// Bytes taken by the upload itself and by its responsive variants.
+    pub size: i64,
+    pub variants_size: i64,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
+    pub created_at: String,
+    pub usages: Vec<MediaUsage>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MediaQuery {
+    /// How many unused uploads were just removed.
+    pub removed: Option<usize>,
+}
+
+/// A tag or technology with the number of published items using it.
+#[derive(Debug, Serialize, Clone)]
+pub struct TagCount {
+    pub name: String,
+    pub count: i64,
+    /// Size step 1-4 in a tag cloud, relative to the most used tag.
+    pub level: i64,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SearchQuery {
+    pub q: Option<String>,
+}
+
+/// `?w=` of an image URL: the width in pixels the page wants.
+#[derive(Debug, Deserialize)]
+pub struct ImageQuery {
+    pub w: Option<u32>,
 }
 
 // Form structs
@@ -130,7 +371,7 @@ pub struct SkillForm {
     pub icon_url: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct ProjectForm {
     pub title: String,
     pub description: String,
@@ -140,9 +381,11 @@ pub struct ProjectForm {
     pub github_url: String,
     pub technologies: String,
     pub featured: Option<String>,
+    pub seo_title: String,
+    pub seo_description: String,
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Serialize, Deserialize)]
 pub struct BlogForm {
     pub title: String,
     pub excerpt: String,
@@ -150,6 +393,10 @@ pub struct BlogForm {
     pub image_url: String,
     pub tags: String,
     pub published: Option<String>,
+    pub seo_title: String,
+    pub seo_description: String,
+    /// When to publish, as `YYYY-MM-DD HH:MM:SS` UTC; `None` publishes right away.
+    pub publish_at: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -166,6 +413,13 @@ pub struct LoginForm {
     pub password: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct SetupForm {
+    pub username: String,
+    pub password: String,
+    pub confirm_password: String,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct ServiceForm {
     pub name: String,
@@ -0,0 +1,77 @@
+use serde::{Deserialize, Serialize};
+
+// Upper bound on `?limit=` so a single request can't pull the whole table
+const MAX_PER_PAGE: i64 = 100;
+
+/// `?page=&limit=` query parameters of a paginated list. Pages start at 1.
+#[derive(Debug, Deserialize)]
+pub struct PageQuery {
+    pub page: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+impl PageQuery {
+    /// The requested page and page size, clamped to sane values.
+    pub fn resolve(&self, default_per_page: i64) -> (i64, i64) {
+        let page = self.page.unwrap_or(1).max(1);
+        let per_page = self.limit.unwrap_or(default_per_page).clamp(1, MAX_PER_PAGE);
+        (page, per_page)
+    }
+}
+
+/// Position within a paginated list, with ready-made links to the neighbouring pages.
+#[derive(Debug, Serialize)]
+pub struct Pagination {
+    pub page: i64,
+    pub per_page: i64,
+    pub total: i64,
+    pub total_pages: i64,
+    /// Path and query of the previous page, if there is one.
+    pub prev_url: Option<String>,
+    pub next_url: Option<String>,
+}
+
+impl Pagination {
+    /// `path` is the list's URL without a query string. A non-default page size is
+    /// carried over into the prev/next links.
+    pub fn new(path: &str, page: i64, per_page: i64, default_per_page: i64, total: i64) -> Self {
+        let total_pages = ((total + per_page - 1) / per_page).max(1);
+        let url = |page: i64| {
+            let mut params = Vec::new();
+            if page > 1 {
+                params.push(format!("page={}", page));
+            }
+            if per_page != default_per_page {
+                params.push(format!("limit={}", per_page));
+            }
+            if params.is_empty() {
+                path.to_string()
+            } else {
+                format!("{}?{}", path, params.join("&"))
+            }
+        };
+
+        Pagination {
+            page,
+            per_page,
+            total,
+            total_pages,
+            prev_url: (page > 1).then(|| url((page - 1).min(total_pages))),
+            next_url: (page < total_pages).then(|| url(page + 1)),
+        }
+    }
+
+    /// URL of the current page, used as its canonical link.
+    pub fn current_url(&self, path: &str) -> String {
+        if self.page > 1 {
+            format!("{}?page={}", path, self.page)
+        } else {
+            path.to_string()
+        }
+    }
+}
+
+/// Row offset of the first item on `page`.
+pub fn offset(page: i64, per_page: i64) -> i64 {
+    (page - 1).saturating_mul(per_page)
+}
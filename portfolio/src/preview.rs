@@ -0,0 +1,97 @@
+use actix_web::cookie::Key;
+use base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+
+/// Query string of a preview link.
+#[derive(Debug, Deserialize)]
+pub struct PreviewQuery {
+    pub expires: i64,
+    pub sig: String,
+}
+
+/// Signs and checks the expiring links that let anyone holding one read a draft post.
+pub struct PreviewLinks {
+    key: Vec<u8>,
+    ttl_secs: i64,
+}
+
+impl PreviewLinks {
+    /// Links are signed with the session key, so rotating it revokes every outstanding link.
+    pub fn new(key: &Key, ttl_secs: i64) -> Self {
+        PreviewLinks { key: key.master().to_vec(), ttl_secs }
+    }
+
+    pub fn ttl_secs(&self) -> i64 {
+        self.ttl_secs
+    }
+
+    fn mac(&self, blog_id: i32, expires: i64) -> Hmac<Sha256> {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.key).expect("HMAC accepts any key length");
+        // Prefixed so the signature can't be mistaken for anything else signed with this key
+        mac.update(format!("blog-preview:{}:{}", blog_id, expires).as_bytes());
+        mac
+    }
+
+    /// Path and query of a fresh preview link for the post.
+    pub fn url(&self, blog_id: i32) -> String {
+        let expires = chrono::Utc::now().timestamp() + self.ttl_secs;
+        let sig = URL_SAFE_NO_PAD.encode(self.mac(blog_id, expires).finalize().into_bytes());
+        format!("/blogs/preview/{}?expires={}&sig={}", blog_id, expires, sig)
+    }
+
+    /// Whether the link was signed by us for this post and hasn't expired.
+    pub fn verify(&self, blog_id: i32, query: &PreviewQuery) -> bool {
+        if query.expires < chrono::Utc::now().timestamp() {
+            return false;
+        }
+        let Ok(sig) = URL_SAFE_NO_PAD.decode(&query.sig) else {
+            return false;
+        };
+        self.mac(blog_id, query.expires).verify_slice(&sig).is_ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn links(key_byte: u8) -> PreviewLinks {
+        PreviewLinks::new(&Key::from(&[key_byte; 64]), 3600)
+    }
+
+    /// The query of a link as a browser would send it back.
+    fn query(url: &str) -> PreviewQuery {
+        let (_, query) = url.split_once('?').unwrap();
+        actix_web::web::Query::<PreviewQuery>::from_query(query).unwrap().into_inner()
+    }
+
+    #[test]
+    fn accepts_its_own_links() {
+        let links = links(1);
+        let url = links.url(7);
+        assert!(url.starts_with("/blogs/preview/7?"));
+        assert!(links.verify(7, &query(&url)));
+    }
+
+    #[test]
+    fn rejects_links_for_other_posts_or_keys() {
+        let url = links(1).url(7);
+        assert!(!links(1).verify(8, &query(&url)));
+        assert!(!links(2).verify(7, &query(&url)));
+    }
+
+    #[test]
+    fn rejects_tampered_and_expired_links() {
+        let links = links(1);
+        let mut tampered = query(&links.url(7));
+        tampered.expires += 3600;
+        assert!(!links.verify(7, &tampered));
+        let garbled = PreviewQuery { sig: "not base64!".to_string(), ..query(&links.url(7)) };
+        assert!(!links.verify(7, &garbled));
+
+        let expired = PreviewLinks::new(&Key::from(&[1; 64]), -1);
+        assert!(!expired.verify(7, &query(&expired.url(7))));
+    }
+}
@@ -0,0 +1,40 @@
+use std::time::Duration;
+use crate::db::{self, DbPool};
+use crate::models::Blog;
+
+// How often the scheduler looks for posts that are due
+const CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Called with each scheduled post as it goes live.
+pub type PublishHook = Box<dyn Fn(&Blog) + Send + Sync>;
+
+/// Work to do when a scheduled post goes live, such as invalidating caches
+/// or pinging feed readers.
+#[derive(Default)]
+pub struct PublishHooks {
+    hooks: Vec<PublishHook>,
+}
+
+impl PublishHooks {
+    pub fn register(&mut self, hook: impl Fn(&Blog) + Send + Sync + 'static) {
+        self.hooks.push(Box::new(hook));
+    }
+
+    fn fire(&self, blog: &Blog) {
+        for hook in &self.hooks {
+            hook(blog);
+        }
+    }
+}
+
+/// Background task that publishes due posts once a minute and runs the hooks for each.
+pub async fn run_scheduler(pool: DbPool, hooks: PublishHooks) {
+    let mut interval = tokio::time::interval(CHECK_INTERVAL);
+    loop {
+        interval.tick().await;
+        match db::call(&pool, |conn| db::publish_due_blogs(conn)).await {
+            Ok(blogs) => blogs.iter().for_each(|blog| hooks.fire(blog)),
+            Err(e) => log::error!("Failed to publish scheduled posts: {}", e),
+        }
+    }
+}
@@ -0,0 +1,207 @@
+use std::collections::{BTreeMap, BTreeSet};
+use serde::Serialize;
+use serde_json::Value;
+
+// Unchanged lines kept around each change; longer unchanged runs are collapsed
+const CONTEXT_LINES: usize = 3;
+// Beyond this many line pairs a field is shown as replaced wholesale rather than diffed
+const MAX_DIFF_CELLS: usize = 4_000_000;
+
+/// One line of a field diff. `kind` is "same", "added", "removed" or "skip"
+/// (a collapsed run of unchanged lines, with `text` saying how many).
+#[derive(Debug, Serialize)]
+pub struct DiffLine {
+    pub kind: &'static str,
+    pub text: String,
+}
+
+/// Line diff of one field that differs between two snapshots.
+#[derive(Debug, Serialize)]
+pub struct FieldDiff {
+    pub field: String,
+    pub lines: Vec<DiffLine>,
+}
+
+/// Compares two revision snapshots field by field, leaving out the fields that didn't change.
+pub fn diff_snapshots(old: &str, new: &str) -> Vec<FieldDiff> {
+    let old = fields(old);
+    let new = fields(new);
+    let names: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+
+    names
+        .into_iter()
+        .filter_map(|name| {
+            let before = old.get(name).map(String::as_str).unwrap_or_default();
+            let after = new.get(name).map(String::as_str).unwrap_or_default();
+            (before != after).then(|| FieldDiff {
+                field: name.clone(),
+                lines: diff_lines(before, after),
+            })
+        })
+        .collect()
+}
+
+/// Snapshot fields as display text; unset optional fields read as empty.
+fn fields(snapshot: &str) -> BTreeMap<String, String> {
+    let values: BTreeMap<String, Value> = serde_json::from_str(snapshot).unwrap_or_default();
+    values
+        .into_iter()
+        .map(|(name, value)| {
+            let text = match value {
+                Value::Null => String::new(),
+                Value::String(s) => s,
+                other => other.to_string(),
+            };
+            (name, text)
+        })
+        .collect()
+}
+
+fn diff_lines(old: &str, new: &str) -> Vec<DiffLine> {
+    let old: Vec<&str> = old.lines().collect();
+    let new: Vec<&str> = new.lines().collect();
+
+    if old.len().saturating_mul(new.len()) > MAX_DIFF_CELLS {
+        let removed = old.iter().map(|line| DiffLine { kind: "removed", text: line.to_string() });
+        let added = new.iter().map(|line| DiffLine { kind: "added", text: line.to_string() });
+        return removed.chain(added).collect();
+    }
+
+    // Longest common subsequence table: lcs[i][j] covers old[i..] and new[j..]
+    let mut lcs = vec![vec![0u32; new.len() + 1]; old.len() + 1];
+    for i in (0..old.len()).rev() {
+        for j in (0..new.len()).rev() {
+            lcs[i][j] = if old[i] == new[j] {
+                lcs[i + 1][j + 1] + 1
+            } else {
+                lcs[i + 1][j].max(lcs[i][j + 1])
+            };
+        }
+    }
+
+    let mut lines = Vec::new();
+    let (mut i, mut j) = (0, 0);
+    while i < old.len() || j < new.len() {
+        if i < old.len() && j < new.len() && old[i] == new[j] {
+            lines.push(DiffLine { kind: "same", text: old[i].to_string() });
+            i += 1;
+            j += 1;
+        } else if i < old.len() && (j == new.len() || lcs[i + 1][j] >= lcs[i][j + 1]) {
+            lines.push(DiffLine { kind: "removed", text: old[i].to_string() });
+            i += 1;
+        } else {
+            lines.push(DiffLine { kind: "added", text: new[j].to_string() });
+            j += 1;
+        }
+    }
+
+    collapse_unchanged(lines)
+}
+
+/// Replaces unchanged lines far from any change with a single "skip" marker.
+fn collapse_unchanged(lines: Vec<DiffLine>) -> Vec<DiffLine> {
+    let changed: Vec<usize> = lines
+        .iter()
+        .enumerate()
+        .filter(|(_, line)| line.kind != "same")
+        .map(|(index, _)| index)
+        .collect();
+    let near_change = |index: usize| {
+        changed.iter().any(|&c| c.abs_diff(index) <= CONTEXT_LINES)
+    };
+
+    let mut collapsed = Vec::new();
+    let mut skipped = 0;
+    for (index, line) in lines.into_iter().enumerate() {
+        if line.kind == "same" && !near_change(index) {
+            skipped += 1;
+            continue;
+        }
+        if skipped > 0 {
+            collapsed.push(skip_marker(skipped));
+            skipped = 0;
+        }
+        collapsed.push(line);
+    }
+    if skipped > 0 {
+        collapsed.push(skip_marker(skipped));
+    }
+    collapsed
+}
+
+fn skip_marker(count: usize) -> DiffLine {
+    let text = if count == 1 {
+        "1 unchanged line".to_string()
+    } else {
+        format!("{} unchanged lines", count)
+    };
+    DiffLine { kind: "skip", text }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(lines: &[DiffLine]) -> Vec<String> {
+        lines.iter().map(|line| format!("{} {}", line.kind, line.text)).collect()
+    }
+
+    #[test]
+    fn insert_in_the_middle() {
+        let lines = diff_lines("a\nb\nc", "a\nb\nx\nc");
+        assert_eq!(render(&lines), ["same a", "same b", "added x", "same c"]);
+    }
+
+    #[test]
+    fn delete_at_the_end() {
+        let lines = diff_lines("a\nb\nc", "a\nb");
+        assert_eq!(render(&lines), ["same a", "same b", "removed c"]);
+    }
+
+    #[test]
+    fn empty_fields_diff_as_whole_additions_or_removals() {
+        assert_eq!(render(&diff_lines("", "a\nb")), ["added a", "added b"]);
+        assert_eq!(render(&diff_lines("a", "")), ["removed a"]);
+    }
+
+    #[test]
+    fn long_unchanged_runs_collapse() {
+        let old: Vec<String> = (1..=20).map(|n| n.to_string()).collect();
+        let mut new = old.clone();
+        new[9] = "ten".to_string();
+
+        let lines = diff_lines(&old.join("\n"), &new.join("\n"));
+        assert_eq!(
+            render(&lines),
+            [
+                "skip 6 unchanged lines", "same 7", "same 8", "same 9", "removed 10", "added ten",
+                "same 11", "same 12", "same 13", "skip 7 unchanged lines",
+            ]
+        );
+        let lines = diff_lines("1\n2\n3\n4\n5", "1\n2\n3\n4\nfive");
+        assert_eq!(render(&lines)[0], "skip 1 unchanged line");
+    }
+
+    #[test]
+    fn huge_fields_are_shown_replaced() {
+        let old = vec!["same"; 2001].join("\n");
+        let new = format!("{}\nextra", old);
+        let lines = diff_lines(&old, &new);
+        assert_eq!(lines.len(), 2001 + 2002);
+        assert!(lines[..2001].iter().all(|line| line.kind == "removed"));
+        assert!(lines[2001..].iter().all(|line| line.kind == "added"));
+    }
+
+    #[test]
+    fn snapshots_diff_added_and_removed_fields() {
+        let old = r#"{"title": "Post", "tags": "rust", "image_url": null}"#;
+        let new = r#"{"title": "Post", "summary": "Short", "featured": true}"#;
+
+        let diffs = diff_snapshots(old, new);
+        let fields: Vec<&str> = diffs.iter().map(|diff| diff.field.as_str()).collect();
+        assert_eq!(fields, ["featured", "summary", "tags"]);
+        assert_eq!(render(&diffs[0].lines), ["added true"]);
+        assert_eq!(render(&diffs[1].lines), ["added Short"]);
+        assert_eq!(render(&diffs[2].lines), ["removed rust"]);
+    }
+}
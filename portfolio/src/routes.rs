@@ -1,26 +1,99 @@
-use actix_web::{web, HttpResponse};
+use actix_web::{web, HttpRequest, HttpResponse, HttpResponseBuilder};
 use crate::AppState;
+use crate::blobs;
 use crate::db;
-use crate::models::ContactForm;
+use crate::error::AppError;
+use crate::http_cache::Validators;
+use crate::models::{ContactForm, ImageQuery, SearchQuery};
+use crate::pagination::{self, PageQuery, Pagination};
+use crate::preview::PreviewQuery;
+use crate::seo::{self, PageMeta};
+use crate::uploads;
 use pulldown_cmark::{Parser, html};
+use rusqlite::OptionalExtension;
 
-fn markdown_to_html(markdown: &str) -> String {
+pub fn markdown_to_html(markdown: &str) -> String {
     let parser = Parser::new(markdown);
     let mut html_output = String::new();
     html::push_html(&mut html_output, parser);
     html_output
 }
 
-pub async fn index(data: web::Data<AppState>) -> HttpResponse {
-    let conn = data.db.lock().unwrap();
-    
-    let profile = db::get_profile(&conn).unwrap();
-    let skills = db::get_skills(&conn).unwrap_or_default();
-    let featured_projects = db::get_featured_projects(&conn).unwrap_or_default();
-    let recent_blogs = db::get_recent_blogs(&conn, 3).unwrap_or_default();
-    let experience = db::get_experience(&conn).unwrap_or_default();
-    let services = db::get_services(&conn).unwrap_or_default();
-    let content = db::get_site_content(&conn).unwrap_or_default();
+// Default page sizes of the public listings; both render in a three-column grid
+const PROJECTS_PER_PAGE: i64 = 9;
+const BLOGS_PER_PAGE: i64 = 9;
+
+// Pages and files are revalidated on every visit, which costs a 304 when unchanged
+const REVALIDATE: &str = "no-cache";
+const IMAGE_CACHE: &str = "public, max-age=31536000";
+
+// Most results shown on a search page
+pub const SEARCH_LIMIT: i64 = 50;
+
+/// HTML-escapes a search snippet and turns its match markers into `<mark>` tags.
+pub fn highlight_snippet(snippet: &str) -> String {
+    tera::escape_html(snippet)
+        .replace(db::SNIPPET_START, "<mark>")
+        .replace(db::SNIPPET_END, "</mark>")
+}
+
+/// Widths stored for the uploaded images among `urls`, for the `srcset` filter.
+async fn image_widths(
+    data: &AppState,
+    urls: Vec<String>,
+) -> Result<std::collections::HashMap<String, Vec<u32>>, AppError> {
+    Ok(db::call(&data.db, move |conn| db::get_image_widths(conn, &urls)).await?)
+}
+
+/// Percent-encodes a tag for use as a path segment. Matches Tera's
+/// `urlencode_strict` filter, so links built in templates agree with canonical URLs.
+pub fn encode_path_segment(segment: &str) -> String {
+    segment
+        .bytes()
+        .map(|b| if b.is_ascii_alphanumeric() { (b as char).to_string() } else { format!("%{:02X}", b) })
+        .collect()
+}
+
+/// Parses a `created_at`/`updated_at` column (UTC); unparsable values map to the epoch.
+pub fn parse_timestamp(value: &str) -> chrono::DateTime<chrono::Utc> {
+    chrono::NaiveDateTime::parse_from_str(value, "%Y-%m-%d %H:%M:%S")
+        .map(|naive| naive.and_utc())
+        .unwrap_or_default()
+}
+
+/// Weak validators for a page rendered from one post or project: they change
+/// when the item, the profile shown around it, the site URL or the deploy does.
+fn page_validators<T: serde::Serialize>(data: &AppState, req: &HttpRequest, updated_at: &str, profile: &T, site_url: &str) -> Validators {
+    let last_modified = parse_timestamp(updated_at).max(data.started_at);
+    let profile = serde_json::to_vec(profile).unwrap_or_default();
+    let started_at = data.started_at.timestamp().to_be_bytes();
+    Validators::page(last_modified, &[req.path().as_bytes(), updated_at.as_bytes(), &profile, site_url.as_bytes(), &started_at])
+}
+
+/// Absolute base URL for links that leave the site, e.g. in feeds and the sitemap.
+pub fn base_url(data: &AppState, req: &HttpRequest) -> String {
+    match &data.site_url {
+        Some(url) => url.clone(),
+        None => {
+            let info = req.connection_info();
+            format!("{}://{}", info.scheme(), info.host())
+        }
+    }
+}
+
+pub async fn index(data: web::Data<AppState>, req: HttpRequest) -> Result<HttpResponse, AppError> {
+    let (profile, skills, featured_projects, recent_blogs, experience, services, content) =
+        db::call(&data.db, |conn| {
+            Ok((
+                db::get_profile(conn)?,
+                db::get_skills(conn)?,
+                db::get_featured_projects(conn)?,
+                db::get_recent_blogs(conn, 3)?,
+                db::get_experience(conn)?,
+                db::get_services(conn)?,
+                db::get_site_content(conn)?,
+            ))
+        }).await?;
     
     let mut context = tera::Context::new();
     context.insert("profile", &profile);
@@ -31,19 +104,33 @@ pub async fn index(data: web::Data<AppState>) -> HttpResponse {
     context.insert("services", &services);
     context.insert("content", &content);
     context.insert("page_title", "Home");
+    let urls = featured_projects
+        .iter()
+        .map(|project| project.image_url.clone())
+        .chain(services.iter().map(|service| service.image_url.clone()))
+        .collect();
+    context.insert("image_widths", &image_widths(&data, urls).await?);
+    
+    let site_url = base_url(&data, &req);
+    let meta = PageMeta::new(&site_url, "/", &profile.bio, "website")
+        .with_image(&site_url, &profile.avatar_url)
+        .with_json_ld(seo::person_json_ld(&profile, &site_url));
+    context.insert("meta", &meta);
     
-    let rendered = data.tera.render("index.html", &context).unwrap();
-    HttpResponse::Ok().content_type("text/html").body(rendered)
+    let rendered = data.tera.render("index.html", &context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
 }
 
-pub async fn about(data: web::Data<AppState>) -> HttpResponse {
-    let conn = data.db.lock().unwrap();
-    
-    let profile = db::get_profile(&conn).unwrap();
-    let skills = db::get_skills(&conn).unwrap_or_default();
-    let experience = db::get_experience(&conn).unwrap_or_default();
-    let education = db::get_education(&conn).unwrap_or_default();
-    let content = db::get_site_content(&conn).unwrap_or_default();
+pub async fn about(data: web::Data<AppState>, req: HttpRequest) -> Result<HttpResponse, AppError> {
+    let (profile, skills, experience, education, content) = db::call(&data.db, |conn| {
+        Ok((
+            db::get_profile(conn)?,
+            db::get_skills(conn)?,
+            db::get_experience(conn)?,
+            db::get_education(conn)?,
+            db::get_site_content(conn)?,
+        ))
+    }).await?;
     
     let mut context = tera::Context::new();
     context.insert("profile", &profile);
@@ -53,185 +140,522 @@ pub async fn about(data: web::Data<AppState>) -> HttpResponse {
     context.insert("content", &content);
     context.insert("page_title", "About");
     
-    let rendered = data.tera.render("about.html", &context).unwrap();
-    HttpResponse::Ok().content_type("text/html").body(rendered)
+    let site_url = base_url(&data, &req);
+    let meta = PageMeta::new(&site_url, "/about", &profile.bio, "profile")
+        .with_image(&site_url, &profile.avatar_url)
+        .with_json_ld(seo::person_json_ld(&profile, &site_url));
+    context.insert("meta", &meta);
+    
+    let rendered = data.tera.render("about.html", &context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
+}
+
+pub async fn projects(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    query: web::Query<PageQuery>,
+) -> Result<HttpResponse, AppError> {
+    project_listing(data, req, query.into_inner(), None).await
+}
+
+pub async fn projects_by_tech(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<PageQuery>,
+) -> Result<HttpResponse, AppError> {
+    project_listing(data, req, query.into_inner(), Some(path.into_inner())).await
 }
 
-pub async fn projects(data: web::Data<AppState>) -> HttpResponse {
-    let conn = data.db.lock().unwrap();
+/// The projects page, optionally narrowed to the projects using one technology.
+async fn project_listing(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    query: PageQuery,
+    tech: Option<String>,
+) -> Result<HttpResponse, AppError> {
+    let (page, per_page) = query.resolve(PROJECTS_PER_PAGE);
+    let offset = pagination::offset(page, per_page);
+    let listing = db::call(&data.db, move |conn| {
+        let (tech, projects, total) = match tech {
+            Some(tech) => match db::find_tag(conn, &tech)? {
+                Some(tech) => (
+                    Some(tech.clone()),
+                    db::get_projects_by_tech_page(conn, &tech, per_page, offset)?,
+                    db::count_projects_by_tech(conn, &tech)?,
+                ),
+                None => return Ok(None),
+            },
+            None => (
+                None,
+                db::get_projects_page(conn, per_page, offset)?,
+                db::count_projects(conn)?,
+            ),
+        };
+        Ok(Some((
+            db::get_profile(conn)?,
+            tech,
+            projects,
+            total,
+            db::get_project_tech_counts(conn)?,
+            db::get_site_content(conn)?,
+        )))
+    }).await?;
     
-    let profile = db::get_profile(&conn).unwrap();
-    let projects = db::get_projects(&conn).unwrap_or_default();
-    let content = db::get_site_content(&conn).unwrap_or_default();
+    let (profile, tech, projects, total, techs, content) = match listing {
+        Some((_, Some(_), _, 0, _, _)) | None => return Err(AppError::NotFound("Technology")),
+        Some(listing) => listing,
+    };
+    let path = match &tech {
+        Some(tech) => format!("/projects/tech/{}", encode_path_segment(tech)),
+        None => "/projects".to_string(),
+    };
+    let pagination = Pagination::new(&path, page, per_page, PROJECTS_PER_PAGE, total);
     
     let mut context = tera::Context::new();
     context.insert("profile", &profile);
     context.insert("projects", &projects);
+    let urls = projects.iter().map(|project| project.image_url.clone()).collect();
+    context.insert("image_widths", &image_widths(&data, urls).await?);
+    context.insert("pagination", &pagination);
+    context.insert("tech", &tech);
+    context.insert("techs", &techs);
     context.insert("content", &content);
-    context.insert("page_title", "Projects");
     
-    let rendered = data.tera.render("projects.html", &context).unwrap();
-    HttpResponse::Ok().content_type("text/html").body(rendered)
+    let description = match &tech {
+        Some(tech) => {
+            context.insert("page_title", &format!("{} Projects", tech));
+            format!("Projects by {} built with {}.", profile.name, tech)
+        }
+        None => {
+            context.insert("page_title", "Projects");
+            format!("Projects by {}, {}.", profile.name, profile.title)
+        }
+    };
+    let site_url = base_url(&data, &req);
+    let meta = PageMeta::new(&site_url, &pagination.current_url(&path), &description, "website")
+        .with_pagination(&site_url, &pagination);
+    context.insert("meta", &meta);
+    
+    let rendered = data.tera.render("projects.html", &context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
 }
 
 pub async fn project_detail(
     data: web::Data<AppState>,
+    req: HttpRequest,
     path: web::Path<String>,
-) -> HttpResponse {
-    let conn = data.db.lock().unwrap();
+) -> Result<HttpResponse, AppError> {
     let slug = path.into_inner();
     
-    let profile = db::get_profile(&conn).unwrap();
+    let (profile, project) = db::call(&data.db, move |conn| {
+        Ok((db::get_profile(conn)?, db::get_project_by_slug(conn, &slug).optional()?))
+    }).await?;
     
-    match db::get_project_by_slug(&conn, &slug) {
-        Ok(project) => {
+    match project {
+        Some(project) => {
+            let site_url = base_url(&data, &req);
+            let validators = page_validators(&data, &req, &project.updated_at, &profile, &site_url);
+            if validators.is_fresh(&req) {
+                return Ok(validators.not_modified().insert_header(("Cache-Control", REVALIDATE)).finish());
+            }
+            
             let content_html = markdown_to_html(&project.content);
             
             let mut context = tera::Context::new();
             context.insert("profile", &profile);
             context.insert("project", &project);
             context.insert("content_html", &content_html);
-            context.insert("page_title", &project.title);
+            context.insert("page_title", seo::title(&project.seo_title, &project.title));
             
-            let rendered = data.tera.render("project_detail.html", &context).unwrap();
-            HttpResponse::Ok().content_type("text/html").body(rendered)
+            let description = seo::description(&project.seo_description, &project.description);
+            let meta = PageMeta::new(&site_url, &format!("/projects/{}", project.slug), description, "website")
+                .with_image(&site_url, &project.image_url)
+                .with_json_ld(seo::project_json_ld(&project, &profile, &site_url));
+            context.insert("meta", &meta);
+            
+            let rendered = data.tera.render("project_detail.html", &context)?;
+            let mut response = HttpResponse::Ok();
+            validators.apply(&mut response);
+            Ok(response.content_type("text/html").insert_header(("Cache-Control", REVALIDATE)).body(rendered))
         }
-        Err(_) => HttpResponse::NotFound().body("Project not found"),
+        None => Err(AppError::NotFound("Project")),
     }
 }
 
-pub async fn blogs(data: web::Data<AppState>) -> HttpResponse {
-    let conn = data.db.lock().unwrap();
+pub async fn blogs(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    query: web::Query<PageQuery>,
+) -> Result<HttpResponse, AppError> {
+    blog_listing(data, req, query.into_inner(), None).await
+}
+
+pub async fn blogs_by_tag(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    path: web::Path<String>,
+    query: web::Query<PageQuery>,
+) -> Result<HttpResponse, AppError> {
+    blog_listing(data, req, query.into_inner(), Some(path.into_inner())).await
+}
+
+/// The blog page, optionally narrowed to the published posts with one tag.
+async fn blog_listing(
+    data: web::Data<AppState>,
+    req: HttpRequest,
+    query: PageQuery,
+    tag: Option<String>,
+) -> Result<HttpResponse, AppError> {
+    let (page, per_page) = query.resolve(BLOGS_PER_PAGE);
+    let offset = pagination::offset(page, per_page);
+    let listing = db::call(&data.db, move |conn| {
+        let (tag, blogs, total) = match tag {
+            Some(tag) => match db::find_tag(conn, &tag)? {
+                Some(tag) => (
+                    Some(tag.clone()),
+                    db::get_published_blogs_by_tag_page(conn, &tag, per_page, offset)?,
+                    db::count_published_blogs_by_tag(conn, &tag)?,
+                ),
+                None => return Ok(None),
+            },
+            None => (
+                None,
+                db::get_published_blogs_page(conn, per_page, offset)?,
+                db::count_published_blogs(conn)?,
+            ),
+        };
+        Ok(Some((
+            db::get_profile(conn)?,
+            tag,
+            blogs,
+            total,
+            db::get_blog_tag_counts(conn)?,
+            db::get_site_content(conn)?,
+        )))
+    }).await?;
     
-    let profile = db::get_profile(&conn).unwrap();
-    let blogs = db::get_published_blogs(&conn).unwrap_or_default();
-    let content = db::get_site_content(&conn).unwrap_or_default();
+    // A tag used only by drafts is as good as unknown
+    let (profile, tag, blogs, total, tags, content) = match listing {
+        Some((_, Some(_), _, 0, _, _)) | None => return Err(AppError::NotFound("Tag")),
+        Some(listing) => listing,
+    };
+    let path = match &tag {
+        Some(tag) => format!("/blogs/tag/{}", encode_path_segment(tag)),
+        None => "/blogs".to_string(),
+    };
+    let pagination = Pagination::new(&path, page, per_page, BLOGS_PER_PAGE, total);
     
     let mut context = tera::Context::new();
     context.insert("profile", &profile);
     context.insert("blogs", &blogs);
+    let urls = blogs.iter().map(|blog| blog.image_url.clone()).collect();
+    context.insert("image_widths", &image_widths(&data, urls).await?);
+    context.insert("pagination", &pagination);
+    context.insert("tag", &tag);
+    context.insert("tags", &tags);
     context.insert("content", &content);
-    context.insert("page_title", "Blog");
     
-    let rendered = data.tera.render("blogs.html", &context).unwrap();
-    HttpResponse::Ok().content_type("text/html").body(rendered)
+    let description = match &tag {
+        Some(tag) => {
+            context.insert("page_title", &format!("Posts tagged {}", tag));
+            format!("Articles about {} by {}.", tag, profile.name)
+        }
+        None => {
+            context.insert("page_title", "Blog");
+            format!("Articles and tutorials by {}, {}.", profile.name, profile.title)
+        }
+    };
+    let site_url = base_url(&data, &req);
+    let meta = PageMeta::new(&site_url, &pagination.current_url(&path), &description, "website")
+        .with_pagination(&site_url, &pagination);
+    context.insert("meta", &meta);
+    
+    let rendered = data.tera.render("blogs.html", &context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
 }
 
 pub async fn blog_detail(
     data: web::Data<AppState>,
+    req: HttpRequest,
     path: web::Path<String>,
-) -> HttpResponse {
-    let conn = data.db.lock().unwrap();
+) -> Result<HttpResponse, AppError> {
     let slug = path.into_inner();
     
-    let profile = db::get_profile(&conn).unwrap();
+    let (profile, blog) = db::call(&data.db, move |conn| {
+        Ok((db::get_profile(conn)?, db::get_blog_by_slug(conn, &slug).optional()?))
+    }).await?;
+    
+    match blog {
+        Some(blog) => {
+            let site_url = base_url(&data, &req);
+            let validators = page_validators(&data, &req, &blog.updated_at, &profile, &site_url);
+            if validators.is_fresh(&req) {
+                return Ok(validators.not_modified().insert_header(("Cache-Control", REVALIDATE)).finish());
+            }
+            
+            let content_html = markdown_to_html(&blog.content);
+            
+            let mut context = tera::Context::new();
+            context.insert("profile", &profile);
+            context.insert("blog", &blog);
+            context.insert("content_html", &content_html);
+            context.insert("page_title", seo::title(&blog.seo_title, &blog.title));
+            
+            let description = seo::description(&blog.seo_description, &blog.excerpt);
+            let meta = PageMeta::new(&site_url, &format!("/blogs/{}", blog.slug), description, "article")
+                .with_image(&site_url, &blog.image_url)
+                .with_json_ld(seo::blog_json_ld(&blog, &profile, &site_url));
+            context.insert("meta", &meta);
+            
+            let rendered = data.tera.render("blog_detail.html", &context)?;
+            let mut response = HttpResponse::Ok();
+            validators.apply(&mut response);
+            Ok(response.content_type("text/html").insert_header(("Cache-Control", REVALIDATE)).body(rendered))
+        }
+        None => Err(AppError::NotFound("Blog post")),
+    }
+}
+
+/// Shows an unpublished post to whoever holds a signed preview link for it.
+pub async fn blog_preview(
+    data: web::Data<AppState>,
+    path: web::Path<i32>,
+    query: web::Query<PreviewQuery>,
+) -> Result<HttpResponse, AppError> {
+    let id = path.into_inner();
+    if !data.preview_links.verify(id, &query) {
+        return Err(AppError::NotFound("Preview"));
+    }
+    
+    let (profile, blog, is_live) = db::call(&data.db, move |conn| {
+        let blog = db::get_blog_by_id(conn, id).optional()?;
+        let is_live = match &blog {
+            Some(blog) => db::get_blog_by_slug(conn, &blog.slug).is_ok(),
+            None => false,
+        };
+        Ok((db::get_profile(conn)?, blog, is_live))
+    }).await?;
     
-    match db::get_blog_by_slug(&conn, &slug) {
-        Ok(blog) => {
+    match blog {
+        // Once the post is out there is nothing left to preview
+        Some(blog) if is_live => Ok(HttpResponse::Found()
+            .append_header(("Location", format!("/blogs/{}", blog.slug)))
+            .finish()),
+        Some(blog) => {
             let content_html = markdown_to_html(&blog.content);
             
             let mut context = tera::Context::new();
             context.insert("profile", &profile);
             context.insert("blog", &blog);
             context.insert("content_html", &content_html);
-            context.insert("page_title", &blog.title);
+            context.insert("page_title", &format!("Preview: {}", blog.title));
+            context.insert("preview", &true);
             
-            let rendered = data.tera.render("blog_detail.html", &context).unwrap();
-            HttpResponse::Ok().content_type("text/html").body(rendered)
+            let rendered = data.tera.render("blog_detail.html", &context)?;
+            Ok(HttpResponse::Ok()
+                .content_type("text/html")
+                .insert_header(("Cache-Control", "private, no-store"))
+                .insert_header(("X-Robots-Tag", "noindex, nofollow"))
+                .body(rendered))
         }
-        Err(_) => HttpResponse::NotFound().body("Blog post not found"),
+        None => Err(AppError::NotFound("Blog post")),
     }
 }
 
-pub async fn contact(data: web::Data<AppState>) -> HttpResponse {
-    let conn = data.db.lock().unwrap();
+pub async fn search(
+    data: web::Data<AppState>,
+    query: web::Query<SearchQuery>,
+) -> Result<HttpResponse, AppError> {
+    let q = query.into_inner().q.unwrap_or_default().trim().to_string();
     
-    let profile = db::get_profile(&conn).unwrap();
-    let content = db::get_site_content(&conn).unwrap_or_default();
+    let term = q.clone();
+    let (profile, content, mut results) = db::call(&data.db, move |conn| {
+        Ok((
+            db::get_profile(conn)?,
+            db::get_site_content(conn)?,
+            db::search_content(conn, &term, false, SEARCH_LIMIT)?,
+        ))
+    }).await?;
+    for result in &mut results {
+        result.snippet = highlight_snippet(&result.snippet);
+    }
+    
+    let mut context = tera::Context::new();
+    context.insert("profile", &profile);
+    context.insert("content", &content);
+    context.insert("query", &q);
+    context.insert("results", &results);
+    context.insert("page_title", "Search");
+    
+    let rendered = data.tera.render("search.html", &context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
+}
+
+pub async fn contact(data: web::Data<AppState>, req: HttpRequest) -> Result<HttpResponse, AppError> {
+    let (profile, content) = db::call(&data.db, |conn| {
+        Ok((db::get_profile(conn)?, db::get_site_content(conn)?))
+    }).await?;
     
     let mut context = tera::Context::new();
     context.insert("profile", &profile);
     context.insert("content", &content);
     context.insert("page_title", "Contact");
+    
+    let description = format!("Get in touch with {}, {}.", profile.name, profile.title);
+    let meta = PageMeta::new(&base_url(&data, &req), "/contact", &description, "website");
+    context.insert("meta", &meta);
     context.insert("success", &false);
     
-    let rendered = data.tera.render("contact.html", &context).unwrap();
-    HttpResponse::Ok().content_type("text/html").body(rendered)
+    let rendered = data.tera.render("contact.html", &context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
 }
 
 pub async fn submit_contact(
     data: web::Data<AppState>,
+    req: HttpRequest,
     form: web::Form<ContactForm>,
-) -> HttpResponse {
-    let conn = data.db.lock().unwrap();
+) -> Result<HttpResponse, AppError> {
+    let form = form.into_inner();
     
-    let profile = db::get_profile(&conn).unwrap();
+    // Save message to database and load email settings
+    let (profile, email_settings, form) = db::call(&data.db, move |conn| {
+        let profile = db::get_profile(conn)?;
+        db::add_message(conn, &form)?;
+        Ok((profile, db::get_email_settings(conn)?, form))
+    }).await?;
     
-    // Save message to database
-    let _ = db::add_message(&conn, &form);
-    
-    // Get email settings and send notification asynchronously
-    if let Ok(email_settings) = db::get_email_settings(&conn) {
-        if email_settings.enabled {
-            let sender_name = form.name.clone();
-            let sender_email = form.email.clone();
-            let subject = form.subject.clone();
-            let message_body = form.message.clone();
-            
-            tokio::spawn(async move {
-                crate::email::send_notification_email_async(
-                    email_settings,
-                    sender_name,
-                    sender_email,
-                    subject,
-                    message_body,
-                ).await;
-            });
-        }
+    // Send notification asynchronously
+    if email_settings.enabled {
+        tokio::spawn(async move {
+            crate::email::send_notification_email_async(
+                email_settings,
+                form.name,
+                form.email,
+                form.subject,
+                form.message,
+            ).await;
+        });
     }
     
     let mut context = tera::Context::new();
     context.insert("profile", &profile);
     context.insert("page_title", "Contact");
+    
+    let description = format!("Get in touch with {}, {}.", profile.name, profile.title);
+    let meta = PageMeta::new(&base_url(&data, &req), "/contact", &description, "website");
+    context.insert("meta", &meta);
     context.insert("success", &true);
     
-    let rendered = data.tera.render("contact.html", &context).unwrap();
-    HttpResponse::Ok().content_type("text/html").body(rendered)
+    let rendered = data.tera.render("contact.html", &context)?;
+    Ok(HttpResponse::Ok().content_type("text/html").body(rendered))
 }
 
+/// Serves an uploaded image. `?w=` asks for the narrowest variant at least that
+/// wide, and browsers that accept WebP get it where a WebP variant exists.
 pub async fn serve_image(
     data: web::Data<AppState>,
+    req: HttpRequest,
     path: web::Path<String>,
-) -> HttpResponse {
-    let conn = data.db.lock().unwrap();
+    query: web::Query<ImageQuery>,
+) -> Result<HttpResponse, AppError> {
     let image_id = path.into_inner();
+    let requested_width = query.w;
+    let accepts_webp = req
+        .headers()
+        .get("Accept")
+        .and_then(|accept| accept.to_str().ok())
+        .is_some_and(|accept| accept.contains("image/webp"));
+    
+    let Some((content_type, key)) = blobs::find_image(&data.db, image_id, requested_width, accepts_webp).await? else {
+        return Err(AppError::NotFound("Image"));
+    };
     
-    match db::get_image(&conn, &image_id) {
-        Ok((content_type, image_data)) => {
-            HttpResponse::Ok()
-                .content_type(content_type)
-                .insert_header(("Cache-Control", "public, max-age=31536000"))
-                .body(image_data)
+    match read_upload(&data, &req, key).await? {
+        Some((validators, None)) => Ok(validators
+            .not_modified()
+            .insert_header(("Cache-Control", IMAGE_CACHE))
+            .insert_header(("Vary", "Accept"))
+            .finish()),
+        Some((validators, Some(image_data))) => {
+            let mut response = HttpResponse::Ok();
+            response
+                .insert_header(("Cache-Control", IMAGE_CACHE))
+                .insert_header(("Vary", "Accept"));
+            validators.apply(&mut response);
+            untrusted_content_headers(&mut response, &content_type, "image");
+            Ok(response.body(image_data))
         }
-        Err(_) => HttpResponse::NotFound().body("Image not found"),
+        None => Err(AppError::NotFound("Image")),
     }
 }
 
 pub async fn serve_file(
     data: web::Data<AppState>,
+    req: HttpRequest,
     path: web::Path<String>,
-) -> HttpResponse {
-    let conn = data.db.lock().unwrap();
+) -> Result<HttpResponse, AppError> {
     let file_id = path.into_inner();
     
-    match db::get_image_with_filename(&conn, &file_id) {
-        Ok((filename, content_type, file_data)) => {
-            let disposition = format!("attachment; filename=\"{}\"", filename);
-            HttpResponse::Ok()
-                .content_type(content_type)
-                .insert_header(("Content-Disposition", disposition))
-                .body(file_data)
+    let Some((filename, content_type, key)) = blobs::find_file(&data.db, file_id).await? else {
+        return Err(AppError::NotFound("File"));
+    };
+    
+    match read_upload(&data, &req, key).await? {
+        Some((validators, None)) => Ok(validators.not_modified().insert_header(("Cache-Control", REVALIDATE)).finish()),
+        Some((validators, Some(file_data))) => {
+            // The name came from the uploader, so nothing in it may end the header value early
+            let filename: String = filename
+                .chars()
+                .filter(|c| !c.is_control() && !matches!(c, '"' | '\\'))
+                .collect();
+            let mut response = HttpResponse::Ok();
+            response.insert_header(("Cache-Control", REVALIDATE));
+            validators.apply(&mut response);
+            untrusted_content_headers(&mut response, &content_type, "file");
+            Ok(response
+                .insert_header(("Content-Disposition", format!("attachment; filename=\"{}\"", filename)))
+                .body(file_data))
+        }
+        None => Err(AppError::NotFound("File")),
+    }
+}
+
+/// Reads the blob behind an upload unless the client's copy is current. Gives the
+/// upload's strong validators, with the bytes when they need sending, or `None`
+/// if the store has lost them. Blobs keyed by content hash are revalidated without
+/// being read.
+async fn read_upload(data: &AppState, req: &HttpRequest, key: String) -> Result<Option<(Validators, Option<Vec<u8>>)>, AppError> {
+    if blobs::is_content_key(&key) {
+        let validators = Validators::content(&key);
+        if validators.is_fresh(req) {
+            return Ok(Some((validators, None)));
         }
-        Err(_) => HttpResponse::NotFound().body("File not found"),
+    }
+    
+    let Some(bytes) = blobs::read(&data.blobs, key.clone()).await? else {
+        return Ok(None);
+    };
+    let validators = Validators::content(&blobs::content_hash(&key, &bytes));
+    if validators.is_fresh(req) {
+        return Ok(Some((validators, None)));
+    }
+    Ok(Some((validators, Some(bytes))))
+}
+
+/// Headers for serving uploaded bytes. Browsers are told not to second-guess
+/// the content type, and nothing in the file may run scripts or load anything
+/// (SVGs opened directly are documents). Types stored before uploads were
+/// checked, if not on the allow-list, go out as a download instead.
+fn untrusted_content_headers(response: &mut HttpResponseBuilder, content_type: &str, download_name: &str) {
+    response
+        .insert_header(("X-Content-Type-Options", "nosniff"))
+        .insert_header(("Content-Security-Policy", "default-src 'none'; img-src data:; style-src 'unsafe-inline'; sandbox"));
+    if uploads::is_servable(content_type) {
+        response.content_type(content_type);
+    } else {
+        response
+            .content_type("application/octet-stream")
+            .insert_header(("Content-Disposition", format!("attachment; filename=\"{}\"", download_name)));
     }
 }
@@ -0,0 +1,233 @@
+use actix_web::{web, HttpRequest, HttpResponse};
+use serde::Serialize;
+use serde_json::json;
+use crate::AppState;
+use crate::db;
+use crate::error::AppError;
+use crate::models::{Blog, Profile, Project};
+use crate::pagination::Pagination;
+use crate::routes::{base_url, encode_path_segment, parse_timestamp};
+
+// Search engines cut descriptions off at around this many characters
+const DESCRIPTION_LENGTH: usize = 160;
+
+/// Per-page metadata rendered into `<head>` by `base.html`: meta description,
+/// canonical URL, OpenGraph/Twitter tags and JSON-LD.
+#[derive(Debug, Serialize)]
+pub struct PageMeta {
+    pub description: String,
+    pub canonical: String,
+    /// Absolute URL of the share image, if the page has one.
+    pub image: Option<String>,
+    /// OpenGraph type: `website`, `article` or `profile`.
+    pub og_type: &'static str,
+    pub json_ld: Option<String>,
+    /// Absolute URLs for the `rel="prev"`/`rel="next"` links of a paginated list.
+    pub prev: Option<String>,
+    pub next: Option<String>,
+}
+
+impl PageMeta {
+    pub fn new(site_url: &str, path: &str, description: &str, og_type: &'static str) -> Self {
+        PageMeta {
+            description: summarize(description),
+            canonical: format!("{}{}", site_url, path),
+            image: None,
+            og_type,
+            json_ld: None,
+            prev: None,
+            next: None,
+        }
+    }
+
+    pub fn with_image(mut self, site_url: &str, image_url: &str) -> Self {
+        if !image_url.trim().is_empty() {
+            self.image = Some(absolute_url(site_url, image_url));
+        }
+        self
+    }
+
+    pub fn with_pagination(mut self, site_url: &str, pagination: &Pagination) -> Self {
+        self.prev = pagination.prev_url.as_ref().map(|url| format!("{}{}", site_url, url));
+        self.next = pagination.next_url.as_ref().map(|url| format!("{}{}", site_url, url));
+        self
+    }
+
+    pub fn with_json_ld(mut self, value: serde_json::Value) -> Self {
+        // Escape `<` so a `</script>` inside user content can't end the script block
+        self.json_ld = Some(value.to_string().replace('<', "\\u003c"));
+        self
+    }
+}
+
+/// Title to show for a page: the SEO override when set, otherwise the entity's own title.
+pub fn title<'a>(seo_title: &'a str, fallback: &'a str) -> &'a str {
+    if seo_title.trim().is_empty() { fallback } else { seo_title }
+}
+
+/// Description for a page: the SEO override when set, otherwise `fallback`.
+pub fn description<'a>(seo_description: &'a str, fallback: &'a str) -> &'a str {
+    if seo_description.trim().is_empty() { fallback } else { seo_description }
+}
+
+fn absolute_url(site_url: &str, url: &str) -> String {
+    if url.starts_with("http://") || url.starts_with("https://") {
+        url.to_string()
+    } else {
+        format!("{}/{}", site_url, url.trim_start_matches('/'))
+    }
+}
+
+/// Collapses whitespace and shortens `text` to a meta-description length.
+fn summarize(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.chars().count() <= DESCRIPTION_LENGTH {
+        return text;
+    }
+    let cut: String = text.chars().take(DESCRIPTION_LENGTH - 1).collect();
+    // Prefer ending on a word boundary
+    let cut = cut.rsplit_once(' ').map(|(head, _)| head.to_string()).unwrap_or(cut);
+    format!("{}…", cut.trim_end_matches(|c: char| c.is_ascii_punctuation()))
+}
+
+/// schema.org `Person` for the site owner.
+pub fn person_json_ld(profile: &Profile, site_url: &str) -> serde_json::Value {
+    let same_as: Vec<&str> = [&profile.github_url, &profile.linkedin_url, &profile.twitter_url]
+        .into_iter()
+        .map(String::as_str)
+        .filter(|url| !url.trim().is_empty())
+        .collect();
+
+    let mut person = json!({
+        "@context": "https://schema.org",
+        "@type": "Person",
+        "name": profile.name,
+        "jobTitle": profile.title,
+        "description": summarize(&profile.bio),
+        "url": format!("{}/", site_url),
+        "sameAs": same_as,
+    });
+    if !profile.avatar_url.trim().is_empty() {
+        person["image"] = json!(absolute_url(site_url, &profile.avatar_url));
+    }
+    person
+}
+
+/// schema.org `BlogPosting` for a published post.
+pub fn blog_json_ld(blog: &Blog, profile: &Profile, site_url: &str) -> serde_json::Value {
+    let url = format!("{}/blogs/{}", site_url, blog.slug);
+    let created = parse_timestamp(&blog.created_at);
+    let mut posting = json!({
+        "@context": "https://schema.org",
+        "@type": "BlogPosting",
+        "headline": title(&blog.seo_title, &blog.title),
+        "description": summarize(description(&blog.seo_description, &blog.excerpt)),
+        "url": url,
+        "mainEntityOfPage": url,
+        "datePublished": created.to_rfc3339(),
+        "dateModified": parse_timestamp(&blog.updated_at).max(created).to_rfc3339(),
+        "keywords": blog.tags,
+        // Not `blog.author`: that is an admin login name
+        "author": {
+            "@type": "Person",
+            "name": profile.name,
+        },
+        "publisher": {
+            "@type": "Person",
+            "name": profile.name,
+            "url": format!("{}/", site_url),
+        },
+    });
+    if !blog.image_url.trim().is_empty() {
+        posting["image"] = json!(absolute_url(site_url, &blog.image_url));
+    }
+    posting
+}
+
+/// schema.org `SoftwareSourceCode` for projects with a repository, `CreativeWork` otherwise.
+pub fn project_json_ld(project: &Project, profile: &Profile, site_url: &str) -> serde_json::Value {
+    let has_source = !project.github_url.trim().is_empty();
+    let mut work = json!({
+        "@context": "https://schema.org",
+        "@type": if has_source { "SoftwareSourceCode" } else { "CreativeWork" },
+        "name": title(&project.seo_title, &project.title),
+        "description": summarize(description(&project.seo_description, &project.description)),
+        "url": format!("{}/projects/{}", site_url, project.slug),
+        "dateCreated": parse_timestamp(&project.created_at).to_rfc3339(),
+        "dateModified": parse_timestamp(&project.updated_at).max(parse_timestamp(&project.created_at)).to_rfc3339(),
+        "keywords": project.technologies,
+        "creator": {
+            "@type": "Person",
+            "name": profile.name,
+        },
+    });
+    if has_source {
+        work["codeRepository"] = json!(project.github_url);
+    }
+    if !project.image_url.trim().is_empty() {
+        work["image"] = json!(absolute_url(site_url, &project.image_url));
+    }
+    work
+}
+
+// Public pages that are not backed by a database row
+const STATIC_PAGES: &[&str] = &["/", "/about", "/projects", "/blogs", "/contact"];
+
+#[derive(Debug, Serialize)]
+struct SitemapUrl {
+    loc: String,
+    lastmod: Option<String>,
+}
+
+pub async fn sitemap(data: web::Data<AppState>, req: HttpRequest) -> Result<HttpResponse, AppError> {
+    let (projects, blogs, techs, tags) = db::call(&data.db, |conn| {
+        Ok((
+            db::get_projects(conn)?,
+            db::get_published_blogs(conn)?,
+            db::get_project_tech_counts(conn)?,
+            db::get_blog_tag_counts(conn)?,
+        ))
+    }).await?;
+    
+    let site_url = base_url(&data, &req);
+    let mut urls: Vec<SitemapUrl> = STATIC_PAGES
+        .iter()
+        .map(|path| SitemapUrl { loc: format!("{}{}", site_url, path), lastmod: None })
+        .collect();
+    
+    urls.extend(projects.iter().map(|project| SitemapUrl {
+        loc: format!("{}/projects/{}", site_url, project.slug),
+        lastmod: Some(parse_timestamp(&project.updated_at).max(parse_timestamp(&project.created_at)).to_rfc3339()),
+    }));
+    urls.extend(blogs.iter().map(|blog| SitemapUrl {
+        loc: format!("{}/blogs/{}", site_url, blog.slug),
+        lastmod: Some(parse_timestamp(&blog.updated_at).max(parse_timestamp(&blog.created_at)).to_rfc3339()),
+    }));
+    urls.extend(techs.iter().map(|tech| SitemapUrl {
+        loc: format!("{}/projects/tech/{}", site_url, encode_path_segment(&tech.name)),
+        lastmod: None,
+    }));
+    urls.extend(tags.iter().map(|tag| SitemapUrl {
+        loc: format!("{}/blogs/tag/{}", site_url, encode_path_segment(&tag.name)),
+        lastmod: None,
+    }));
+    
+    let mut context = tera::Context::new();
+    context.insert("urls", &urls);
+    
+    let rendered = data.tera.render("sitemap.xml", &context)?;
+    Ok(HttpResponse::Ok().content_type("application/xml; charset=utf-8").body(rendered))
+}
+
+pub async fn robots(data: web::Data<AppState>, req: HttpRequest) -> Result<HttpResponse, AppError> {
+    let content = db::call(&data.db, |conn| db::get_site_content(conn)).await?;
+    
+    let mut body = String::from("User-agent: *\nDisallow: /admin\n");
+    let extra = content.get("robots_disallow").map(String::as_str).unwrap_or_default();
+    for path in extra.split(',').map(str::trim).filter(|path| !path.is_empty()) {
+        body.push_str(&format!("Disallow: {}\n", path));
+    }
+    body.push_str(&format!("\nSitemap: {}/sitemap.xml\n", base_url(&data, &req)));
+    
+    Ok(HttpResponse::Ok().content_type("text/plain; charset=utf-8").body(body))
+}
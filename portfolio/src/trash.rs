@@ -0,0 +1,19 @@
+use std::time::Duration;
+use crate::db::{self, DbPool};
+
+// How often the trash is checked for items past the retention period
+const PURGE_INTERVAL: Duration = Duration::from_secs(3600);
+
+/// Background task that purges, once an hour, whatever has been in the trash
+/// for more than `retention_days` days.
+pub async fn run_purger(pool: DbPool, retention_days: i64) {
+    let mut interval = tokio::time::interval(PURGE_INTERVAL);
+    loop {
+        interval.tick().await;
+        match db::call(&pool, move |conn| db::purge_expired_trash(conn, retention_days)).await {
+            Ok(0) => {}
+            Ok(purged) => log::info!("Purged {} item(s) from the trash", purged),
+            Err(e) => log::error!("Failed to purge the trash: {}", e),
+        }
+    }
+}
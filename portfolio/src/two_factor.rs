@@ -0,0 +1,82 @@
+use qrcode::render::svg;
+use qrcode::QrCode;
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use totp_rs::{Algorithm, Secret, TOTP};
+use crate::csrf;
+
+const ISSUER: &str = "Portfolio Admin";
+const STEP_SECS: i64 = 30;
+const RECOVERY_CODE_COUNT: usize = 10;
+// No 0/o, 1/l/i so codes survive being written down
+const RECOVERY_CODE_ALPHABET: &[u8] = b"abcdefghjkmnpqrstuvwxyz23456789";
+
+/// Session key holding a secret that has been shown on the profile page but not yet confirmed.
+pub const PENDING_SECRET_KEY: &str = "totp_pending_secret";
+
+/// Generates a new base32-encoded 160-bit TOTP secret.
+pub fn generate_secret() -> String {
+    Secret::generate_secret().to_encoded().to_string()
+}
+
+fn totp(secret: &str, account: &str) -> Option<TOTP> {
+    let bytes = Secret::Encoded(secret.to_string()).to_bytes().ok()?;
+    TOTP::new(Algorithm::SHA1, 6, 1, STEP_SECS as u64, bytes, Some(ISSUER.to_string()), account.to_string()).ok()
+}
+
+/// Renders the `otpauth://` enrollment URL for `secret` as an SVG QR code.
+pub fn qr_code_svg(secret: &str, account: &str) -> Option<String> {
+    let url = totp(secret, account)?.get_url();
+    let code = QrCode::new(url.as_bytes()).ok()?;
+    Some(
+        code.render::<svg::Color>()
+            .min_dimensions(200, 200)
+            .quiet_zone(true)
+            .build(),
+    )
+}
+
+fn normalize(code: &str) -> String {
+    code.chars()
+        .filter(|c| c.is_ascii_alphanumeric())
+        .map(|c| c.to_ascii_lowercase())
+        .collect()
+}
+
+/// Whether the input looks like an authenticator code rather than a recovery code.
+pub fn is_totp_code(code: &str) -> bool {
+    let code = normalize(code);
+    code.len() == 6 && code.chars().all(|c| c.is_ascii_digit())
+}
+
+/// Checks an RFC 6238 code, allowing one step of clock drift either way.
+/// Steps at or before `last_step` are refused so a code can't be replayed;
+/// on success the matching step is returned for the caller to record.
+pub fn verify_code(secret: &str, code: &str, last_step: i64) -> Option<i64> {
+    let code = normalize(code);
+    let totp = totp(secret, "")?;
+    let current = chrono::Utc::now().timestamp() / STEP_SECS;
+
+    (current - 1..=current + 1)
+        .filter(|step| *step > last_step)
+        .find(|step| csrf::constant_time_eq(totp.generate((step * STEP_SECS) as u64).as_bytes(), code.as_bytes()))
+}
+
+/// Generates a fresh set of one-time recovery codes in `xxxxx-xxxxx` form.
+pub fn generate_recovery_codes() -> Vec<String> {
+    let mut rng = rand::thread_rng();
+    (0..RECOVERY_CODE_COUNT)
+        .map(|_| {
+            let chars: String = (0..10)
+                .map(|_| RECOVERY_CODE_ALPHABET[rng.gen_range(0..RECOVERY_CODE_ALPHABET.len())] as char)
+                .collect();
+            format!("{}-{}", &chars[..5], &chars[5..])
+        })
+        .collect()
+}
+
+/// Hash stored for a recovery code. The codes are long and random, so a
+/// plain SHA-256 is enough and keeps checking them cheap.
+pub fn hash_recovery_code(code: &str) -> String {
+    format!("{:x}", Sha256::digest(normalize(code).as_bytes()))
+}
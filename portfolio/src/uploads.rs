@@ -0,0 +1,325 @@
+use actix_multipart::Field;
+use futures_util::StreamExt;
+
+/// Largest file accepted in a single form field.
+pub const MAX_FILE_BYTES: usize = 10 * 1024 * 1024;
+/// Largest text value accepted in a single form field.
+pub const MAX_TEXT_BYTES: usize = 1024 * 1024;
+/// Most parts a multipart form may have.
+pub const MAX_FIELDS: usize = 100;
+
+/// What an image field accepts, identified by content rather than by what the browser claims.
+pub const IMAGE_TYPES: &[&str] = &["image/jpeg", "image/png", "image/gif", "image/webp", SVG];
+/// What a document field (the resume) accepts.
+pub const DOCUMENT_TYPES: &[&str] = &["application/pdf"];
+
+const SVG: &str = "image/svg+xml";
+
+/// Why an uploaded form was refused.
+#[derive(Debug)]
+pub enum UploadError {
+    /// A field went over its size cap while being read.
+    TooLarge { field: String, limit: usize },
+    /// A file's content is not one of the types the field accepts.
+    Unsupported { filename: String },
+    TooManyFields,
+    /// The multipart body could not be read.
+    Malformed,
+}
+
+impl std::fmt::Display for UploadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UploadError::TooLarge { field, limit } => {
+                write!(f, "The \"{}\" field is larger than the {} MB limit.", field, limit / (1024 * 1024))
+            }
+            UploadError::Unsupported { filename } => {
+                write!(f, "\"{}\" is not a supported file type.", filename)
+            }
+            UploadError::TooManyFields => write!(f, "The form has too many fields."),
+            UploadError::Malformed => write!(f, "The upload could not be read. Please try again."),
+        }
+    }
+}
+
+impl std::error::Error for UploadError {}
+
+/// Reads one multipart field into memory, giving up as soon as it passes `limit`
+/// rather than after the whole thing has arrived.
+pub async fn read_field(field: &mut Field, name: &str, limit: usize) -> Result<Vec<u8>, UploadError> {
+    let mut data = Vec::new();
+    while let Some(chunk) = field.next().await {
+        let chunk = chunk.map_err(|_| UploadError::Malformed)?;
+        if data.len() + chunk.len() > limit {
+            return Err(UploadError::TooLarge { field: name.to_string(), limit });
+        }
+        data.extend_from_slice(&chunk);
+    }
+    Ok(data)
+}
+
+/// Checks an uploaded file against the types its field accepts and returns the
+/// content type to store it under, with SVGs cleaned of anything scriptable.
+pub fn validate(filename: &str, data: Vec<u8>, allowed: &[&str]) -> Result<(&'static str, Vec<u8>), UploadError> {
+    let unsupported = || UploadError::Unsupported { filename: filename.to_string() };
+    let content_type = sniff(&data).filter(|ct| allowed.contains(ct)).ok_or_else(unsupported)?;
+    if content_type == SVG {
+        let clean = sanitize_svg(&data).ok_or_else(unsupported)?;
+        return Ok((content_type, clean));
+    }
+    Ok((content_type, data))
+}
+
+/// Identifies a file from its leading bytes. Only the types we are willing to store are recognised.
+pub fn sniff(data: &[u8]) -> Option<&'static str> {
+    if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some("image/jpeg")
+    } else if data.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some("image/png")
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        Some("image/gif")
+    } else if data.len() >= 12 && data.starts_with(b"RIFF") && &data[8..12] == b"WEBP" {
+        Some("image/webp")
+    } else if data.starts_with(b"%PDF-") {
+        Some("application/pdf")
+    } else if looks_like_svg(data) {
+        Some(SVG)
+    } else {
+        None
+    }
+}
+
+/// Whether a stored content type may be served as itself. Anything else (uploads
+/// from before validation existed) goes out as an opaque download.
+pub fn is_servable(content_type: &str) -> bool {
+    IMAGE_TYPES.contains(&content_type) || DOCUMENT_TYPES.contains(&content_type)
+}
+
+fn looks_like_svg(data: &[u8]) -> bool {
+    let Ok(text) = std::str::from_utf8(data) else {
+        return false;
+    };
+    let text = text.trim_start_matches('\u{feff}').trim_start();
+    text.starts_with('<') && text.contains("<svg")
+}
+
+// SVG sanitization
+//
+// SVGs are rebuilt tag by tag, keeping only known drawing elements and dropping
+// event handlers and links that go anywhere but within the document. Anything
+// that doesn't parse as plain XML (including a DOCTYPE, which could declare
+// entities) rejects the file.
+
+const SVG_ELEMENTS: &[&str] = &[
+    "svg", "g", "defs", "symbol", "use", "title", "desc", "style", "switch", "view",
+    "path", "rect", "circle", "ellipse", "line", "polyline", "polygon", "image",
+    "text", "tspan", "textPath", "linearGradient", "radialGradient", "stop", "pattern",
+    "clipPath", "mask", "marker", "filter", "animateTransform", "animateMotion", "mpath",
+];
+
+fn allowed_element(name: &str) -> bool {
+    // Filter primitives: feGaussianBlur, feOffset, feMerge, ...
+    SVG_ELEMENTS.contains(&name) || (name.starts_with("fe") && name[2..].starts_with(|c: char| c.is_ascii_uppercase()))
+}
+
+fn allowed_attribute(name: &str, value: &str) -> bool {
+    let lower = name.to_ascii_lowercase();
+    if lower.starts_with("on") {
+        return false;
+    }
+    // Browsers ignore whitespace and control characters inside a URL scheme
+    let compact: String = value
+        .chars()
+        .filter(|c| !c.is_whitespace() && !c.is_control())
+        .collect::<String>()
+        .to_ascii_lowercase();
+    if compact.contains("javascript:") {
+        return false;
+    }
+    if lower == "href" || lower == "xlink:href" {
+        return compact.starts_with('#')
+            || ["data:image/png", "data:image/jpeg", "data:image/gif", "data:image/webp"]
+                .iter()
+                .any(|prefix| compact.starts_with(prefix));
+    }
+    // Editor namespaces (inkscape:, sodipodi:, ...) carry nothing the drawing needs
+    !lower.contains(':') || lower.starts_with("xmlns") || lower.starts_with("xlink:") || lower.starts_with("xml:")
+}
+
+/// Returns the SVG with everything scriptable removed, or `None` if it isn't a well-formed SVG.
+pub fn sanitize_svg(data: &[u8]) -> Option<Vec<u8>> {
+    let text = std::str::from_utf8(data).ok()?;
+    let mut rest = text.trim_start_matches('\u{feff}');
+    let mut out = String::with_capacity(rest.len());
+    // Open elements, and how many of them are inside a dropped element
+    let mut open: Vec<String> = Vec::new();
+    let mut dropped_depth = 0usize;
+    let mut seen_root = false;
+
+    while !rest.is_empty() {
+        let Some(start) = rest.find('<') else {
+            if dropped_depth == 0 {
+                out.push_str(rest);
+            }
+            break;
+        };
+        if dropped_depth == 0 {
+            out.push_str(&rest[..start]);
+        }
+        rest = &rest[start..];
+
+        if let Some(after) = rest.strip_prefix("<!--") {
+            let end = after.find("-->")?;
+            rest = &after[end + 3..];
+        } else if let Some(after) = rest.strip_prefix("<![CDATA[") {
+            let end = after.find("]]>")?;
+            if dropped_depth == 0 {
+                out.push_str(&rest[..9 + end + 3]);
+            }
+            rest = &after[end + 3..];
+        } else if rest.starts_with("<?") {
+            let end = rest.find("?>")?;
+            if rest.starts_with("<?xml ") && !seen_root {
+                out.push_str(&rest[..end + 2]);
+            }
+            rest = &rest[end + 2..];
+        } else if rest.starts_with("<!") {
+            return None;
+        } else if let Some(after) = rest.strip_prefix("</") {
+            let end = after.find('>')?;
+            let name = after[..end].trim();
+            if open.pop().as_deref() != Some(name) {
+                return None;
+            }
+            if dropped_depth > 0 {
+                dropped_depth -= 1;
+            } else {
+                out.push_str("</");
+                out.push_str(name);
+                out.push('>');
+            }
+            rest = &after[end + 1..];
+        } else {
+            let (tag, remaining) = parse_tag(&rest[1..])?;
+            rest = remaining;
+            if !seen_root {
+                if tag.name != "svg" {
+                    return None;
+                }
+                seen_root = true;
+            } else if open.is_empty() {
+                // A second root element
+                return None;
+            }
+            let keep = dropped_depth == 0 && allowed_element(tag.name);
+            if keep {
+                out.push('<');
+                out.push_str(tag.name);
+                for (name, value) in tag.attributes.iter().filter(|(name, value)| allowed_attribute(name, value)) {
+                    out.push(' ');
+                    out.push_str(name);
+                    out.push_str("=\"");
+                    out.push_str(&value.replace('"', "&quot;").replace('<', "&lt;"));
+                    out.push('"');
+                }
+                out.push_str(if tag.self_closing { "/>" } else { ">" });
+            }
+            if !tag.self_closing {
+                open.push(tag.name.to_string());
+                if !keep {
+                    dropped_depth += 1;
+                }
+            }
+        }
+    }
+
+    (seen_root && open.is_empty()).then(|| out.into_bytes())
+}
+
+struct Tag<'a> {
+    name: &'a str,
+    attributes: Vec<(&'a str, &'a str)>,
+    self_closing: bool,
+}
+
+/// Parses a start tag from just after its `<`, returning it and the text after its `>`.
+fn parse_tag(input: &str) -> Option<(Tag<'_>, &str)> {
+    let is_name_char = |c: char| c.is_alphanumeric() || matches!(c, ':' | '-' | '_' | '.');
+    let name_end = input.find(|c: char| !is_name_char(c))?;
+    let name = &input[..name_end];
+    if name.is_empty() {
+        return None;
+    }
+
+    let mut attributes = Vec::new();
+    let mut rest = &input[name_end..];
+    loop {
+        rest = rest.trim_start();
+        if let Some(after) = rest.strip_prefix("/>") {
+            return Some((Tag { name, attributes, self_closing: true }, after));
+        }
+        if let Some(after) = rest.strip_prefix('>') {
+            return Some((Tag { name, attributes, self_closing: false }, after));
+        }
+
+        let attr_end = rest.find(|c: char| !is_name_char(c))?;
+        let attr_name = &rest[..attr_end];
+        if attr_name.is_empty() {
+            return None;
+        }
+        rest = rest[attr_end..].trim_start().strip_prefix('=')?.trim_start();
+        let quote = rest.chars().next().filter(|&c| c == '"' || c == '\'')?;
+        let value_end = rest[1..].find(quote)?;
+        attributes.push((attr_name, &rest[1..1 + value_end]));
+        rest = &rest[1 + value_end + 1..];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn clean(svg: &str) -> Option<String> {
+        sanitize_svg(svg.as_bytes()).map(|data| String::from_utf8(data).unwrap())
+    }
+
+    #[test]
+    fn sniffs_types_from_leading_bytes() {
+        assert_eq!(sniff(b"\xFF\xD8\xFF\xE0rest"), Some("image/jpeg"));
+        assert_eq!(sniff(b"\x89PNG\r\n\x1a\nrest"), Some("image/png"));
+        assert_eq!(sniff(b"GIF89a..."), Some("image/gif"));
+        assert_eq!(sniff(b"RIFF\0\0\0\0WEBPVP8 "), Some("image/webp"));
+        assert_eq!(sniff(b"%PDF-1.7"), Some("application/pdf"));
+        assert_eq!(sniff("\u{feff} <?xml version=\"1.0\"?><svg/>".as_bytes()), Some(SVG));
+    }
+
+    #[test]
+    fn does_not_trust_names_or_lookalikes() {
+        assert_eq!(sniff(b"RIFF\0\0\0\0WAVEfmt "), None);
+        assert_eq!(sniff(b"<html><script>alert(1)</script></html>"), None);
+        assert_eq!(sniff(b"MZ\x90\0"), None);
+        assert_eq!(sniff(b""), None);
+        assert!(validate("photo.jpg", b"<svg/>".to_vec(), &["image/jpeg"]).is_err());
+    }
+
+    #[test]
+    fn keeps_drawing_elements_and_attributes() {
+        let svg = r##"<?xml version="1.0"?><svg xmlns="http://www.w3.org/2000/svg" viewBox="0 0 10 10"><use xlink:href="#dot"/><circle id="dot" r="4" fill="red"/></svg>"##;
+        assert_eq!(clean(svg).as_deref(), Some(svg));
+    }
+
+    #[test]
+    fn strips_scripts_handlers_and_outside_links() {
+        let svg = r#"<svg onload="alert(1)"><script>alert(2)</script><a href="https://example.com"><rect/></a><foreignObject><div>hi</div></foreignObject><image href="java&#10;script:alert(3)"/><rect fill="url(javascript:alert(4))"/><path inkscape:label="x" d="M0 0"/></svg>"#;
+        assert_eq!(clean(svg).as_deref(), Some("<svg><image/><rect/><path d=\"M0 0\"/></svg>"));
+    }
+
+    #[test]
+    fn rejects_malformed_svgs() {
+        assert_eq!(clean(r#"<!DOCTYPE svg [<!ENTITY x "y">]><svg>&x;</svg>"#), None);
+        assert_eq!(clean("<html><svg/></html>"), None);
+        assert_eq!(clean("<svg><g></svg>"), None);
+        assert_eq!(clean("<svg/><svg/>"), None);
+        assert_eq!(clean("<svg><!-- unterminated </svg>"), None);
+    }
+}